@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use parser::ParseNode;
+use parser::export::read_program;
+
+use super::scope::definition_free_variables;
+
+/// Which top-level definitions of a program use which others. Names that
+/// aren't defined in the program, like primitives, aren't part of it.
+pub struct DependencyGraph{
+    pub names: Vec<String>,         // Definitions in source order
+    pub edges: Vec<Vec<usize>>      // Carries, for each definition, the ones it uses
+}
+
+impl DependencyGraph{
+    pub fn new(program: &ParseNode) -> DependencyGraph {
+        let free = definition_free_variables(program);
+        let index: HashMap<&str, usize> = free.iter()
+            .enumerate()
+            .map(|(i, &(name, _))| (name, i))
+            .collect();
+        let edges = free.iter()
+            .map(|(_, uses)| uses.iter().filter_map(|name| index.get(name.as_str()).cloned()).collect())
+            .collect();
+        DependencyGraph {
+            names: free.iter().map(|&(name, _)| name.to_string()).collect(),
+            edges
+        }
+    }
+
+    /// Strongly connected components, ordered so that every component comes
+    /// after the components it uses. Each component lists its definitions
+    /// in source order.
+    pub fn components(&self) -> Vec<Vec<usize>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: vec![None; self.names.len()],
+            lowlink: vec![0; self.names.len()],
+            stack: vec!(),
+            on_stack: vec![false; self.names.len()],
+            next: 0,
+            components: vec!()
+        };
+        for node in 0..self.names.len() {
+            if tarjan.index[node].is_none() {
+                tarjan.visit(node);
+            }
+        }
+        tarjan.components
+    }
+
+    /// Whether a component refers to itself, either through a definition
+    /// that uses itself or through several that use each other.
+    pub fn is_recursive(&self, component: &[usize]) -> bool {
+        component.len() > 1 || self.edges[component[0]].contains(&component[0])
+    }
+
+    /// Definition names in an order where each comes after the ones it
+    /// uses, except within recursive groups. Evaluation looks definitions up
+    /// by name, so programs can define them in any order; this is the order
+    /// they are reported in.
+    pub fn order(&self) -> Vec<&str> {
+        self.components().into_iter()
+            .flat_map(|component| component.into_iter())
+            .map(|node| self.names[node].as_str())
+            .collect()
+    }
+}
+
+/// Tarjan's algorithm, which finds components in reverse topological order
+/// of the graph, so dependencies come first.
+struct Tarjan<'a>{
+    graph: &'a DependencyGraph,
+    index: Vec<Option<usize>>,
+    lowlink: Vec<usize>,
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+    next: usize,
+    components: Vec<Vec<usize>>
+}
+
+impl<'a> Tarjan<'a>{
+    /// Visits everything reachable from `root` with an explicit stack, so
+    /// long chains of definitions can't overflow the Rust stack.
+    fn visit(&mut self, root: usize){
+        // Nodes being visited, each with the next of its edges to follow
+        let mut work = vec![(root, 0)];
+        self.enter(root);
+        while let Some((node, edge)) = work.pop() {
+            if let Some(&next) = self.graph.edges[node].get(edge) {
+                work.push((node, edge + 1));
+                match self.index[next] {
+                    None => {
+                        self.enter(next);
+                        work.push((next, 0));
+                    },
+                    Some(index) if self.on_stack[next] =>
+                        self.lowlink[node] = self.lowlink[node].min(index),
+                    _ => ()
+                }
+                continue;
+            }
+
+            if let Some(&(parent, _)) = work.last() {
+                self.lowlink[parent] = self.lowlink[parent].min(self.lowlink[node]);
+            }
+            if Some(self.lowlink[node]) == self.index[node] {
+                let mut component = vec!();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                component.sort();
+                self.components.push(component);
+            }
+        }
+    }
+
+    fn enter(&mut self, node: usize){
+        self.index[node] = Some(self.next);
+        self.lowlink[node] = self.next;
+        self.next += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+    }
+}
+
+/// Prints the dependency graph of the program in `path`, one component per
+/// entry with dependencies first. Returns whether the file could be read.
+pub fn print_dependencies(path: &str) -> bool {
+    let program = match read_program(Path::new(path)) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return false;
+        }
+    };
+
+    let graph = DependencyGraph::new(&program);
+    let line = |node: usize| {
+        let uses: Vec<&str> = graph.edges[node].iter().map(|&n| graph.names[n].as_str()).collect();
+        if uses.is_empty() {
+            graph.names[node].clone()
+        } else {
+            format!("{} -> {}", graph.names[node], uses.join(", "))
+        }
+    };
+    for component in graph.components() {
+        if component.len() > 1 {
+            let names: Vec<&str> = component.iter().map(|&n| graph.names[n].as_str()).collect();
+            println!("mutually recursive: {}", names.join(", "));
+            for &node in &component {
+                println!("  {}", line(node));
+            }
+        } else if graph.is_recursive(&component) {
+            println!("{} (recursive)", line(component[0]));
+        } else {
+            println!("{}", line(component[0]));
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+fn parse_program(input: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(input)).parse().unwrap()
+}
+
+#[test]
+fn dependencies_find_recursive_groups(){
+    let program = parse_program("main = even (twice id 4)
+        even = \\n. eq n 0 true (odd (sub n 1))
+        odd = \\n. eq n 0 false (even (sub n 1))
+        twice = \\f. \\x. f (f x)
+        loop = loop
+        id = \\x. x
+        true = \\t. \\f. t
+        false = \\t. \\f. f");
+    let graph = DependencyGraph::new(&program);
+
+    let components: Vec<Vec<&str>> = graph.components().iter()
+        .map(|c| c.iter().map(|&n| graph.names[n].as_str()).collect())
+        .collect();
+    assert_eq!(components, vec![
+        vec!["false"], vec!["true"], vec!["even", "odd"], vec!["id"], vec!["twice"], vec!["main"], vec!["loop"]
+    ]);
+    let recursive: Vec<bool> = graph.components().iter().map(|c| graph.is_recursive(c)).collect();
+    assert_eq!(recursive, vec![false, false, true, false, false, false, true]);
+}
+
+#[test]
+fn dependencies_order(){
+    let program = parse_program("main = k id\nk = \\x. \\y. x\nid = \\x. x");
+    assert_eq!(DependencyGraph::new(&program).order(), vec!["id", "k", "main"]);
+
+    // Deeper than the Rust stack could recurse
+    let length = 200_000;
+    let chain = DependencyGraph {
+        names: (0..length).map(|i| i.to_string()).collect(),
+        edges: (0..length).map(|i| if i + 1 < length { vec![i + 1] } else { vec![0] }).collect()
+    };
+    assert_eq!(chain.components().len(), 1);
+}
@@ -0,0 +1,5 @@
+pub mod scope;
+pub mod dependencies;
+
+pub use self::scope::{definition_free_variables, unbound_names};
+pub use self::dependencies::DependencyGraph;
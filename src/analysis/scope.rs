@@ -0,0 +1,109 @@
+use std::collections::{BTreeSet, HashSet};
+
+use parser::{ParseNode, GrammarItem};
+use errors::error_index::Error;
+use errors::suggest::closest_match;
+use eval::substitution::free_variables;
+
+/// The free variables of each top-level definition of `program`, in source
+/// order. A definition's own name counts as free if it refers to itself.
+pub fn definition_free_variables(program: &ParseNode) -> Vec<(&str, BTreeSet<String>)> {
+    match program.entry {
+        GrammarItem::Program(ref definitions) => definitions.iter()
+            .filter_map(|definition| match definition.entry {
+                GrammarItem::Assignment(ref name, ref expr) =>
+                    Some((name.as_str(), free_variables(expr).into_iter().collect())),
+                _ => None
+            })
+            .collect(),
+        _ => vec!()
+    }
+}
+
+/// Resolves every variable of `node` against the abstractions around it
+/// and `globals`, and returns an `UnboundVariable` error for each use of
+/// a name that is neither. The definitions of a program, or the name of a
+/// single definition, are in scope everywhere in it.
+pub fn unbound_names(node: &ParseNode, globals: &HashSet<String>) -> Vec<Error> {
+    let mut globals = globals.clone();
+    match node.entry {
+        GrammarItem::Program(ref definitions) => {
+            for definition in definitions {
+                if let GrammarItem::Assignment(ref name, _) = definition.entry {
+                    globals.insert(name.clone());
+                }
+            }
+        },
+        GrammarItem::Assignment(ref name, _) => { globals.insert(name.clone()); },
+        _ => ()
+    }
+
+    // Sorted so that suggestions don't depend on hashing order
+    let mut sorted: Vec<&str> = globals.iter().map(String::as_str).collect();
+    sorted.sort();
+
+    let mut errors = vec!();
+    resolve(node, &mut vec!(), &globals, &sorted, &mut errors);
+    errors
+}
+
+fn resolve(node: &ParseNode, bound: &mut Vec<String>, globals: &HashSet<String>,
+           sorted: &[&str], errors: &mut Vec<Error>){
+    match node.entry {
+        GrammarItem::Variable(ref name) => {
+            if !bound.contains(name) && !globals.contains(name) {
+                // Closer binders are better guesses than globals
+                let candidates = bound.iter().rev().map(String::as_str).chain(sorted.iter().cloned());
+                let suggestion = closest_match(name, candidates).map(str::to_string);
+                errors.push(Error::UnboundVariable(name.clone(), node.span, suggestion));
+            }
+        },
+        GrammarItem::Abstraction(ref param, ref body) => {
+            bound.push(param.clone());
+            resolve(body, bound, globals, sorted, errors);
+            bound.pop();
+        },
+        GrammarItem::Application(ref left, ref right) => {
+            resolve(left, bound, globals, sorted, errors);
+            resolve(right, bound, globals, sorted, errors);
+        },
+        GrammarItem::Assignment(_, ref expr) => resolve(expr, bound, globals, sorted, errors),
+        GrammarItem::Program(ref children) => {
+            for child in children {
+                resolve(child, bound, globals, sorted, errors);
+            }
+        },
+        GrammarItem::LiteralInt(_) => ()
+    }
+}
+
+#[cfg(test)]
+fn parse_program(input: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(input)).parse().unwrap()
+}
+
+#[test]
+fn scope_free_variables_per_definition(){
+    let program = parse_program("const = \\x. \\y. x\nloop = \\n. loop (const n m)");
+    let free = definition_free_variables(&program);
+    let names = |set: &BTreeSet<String>| set.iter().cloned().collect::<Vec<_>>();
+    assert_eq!(free.len(), 2);
+    assert_eq!((free[0].0, names(&free[0].1)), ("const", vec![]));
+    assert_eq!((free[1].0, names(&free[1].1)), ("loop", vec!["const".to_string(), "loop".to_string(), "m".to_string()]));
+}
+
+#[test]
+fn scope_reports_unbound_names(){
+    use lexer::Span;
+
+    let globals = vec!["add".to_string()].into_iter().collect();
+    let program = parse_program("twice = \\func. \\x. fnc (fun x)\nmain = twice (ad 1) 2");
+    assert_eq!(unbound_names(&program, &globals), vec![
+        Error::UnboundVariable("fnc".to_string(), Span::new(19, 22), Some("func".to_string())),
+        Error::UnboundVariable("fun".to_string(), Span::new(24, 27), Some("func".to_string())),
+        Error::UnboundVariable("ad".to_string(), Span::new(45, 47), Some("add".to_string())),
+    ]);
+    assert!(unbound_names(&parse_program("id = \\x. x\nmain = id add"), &globals).is_empty());
+}
@@ -0,0 +1,191 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::process::Command;
+
+use parser::ParseNode;
+use errors::error_index::Error;
+use eval::{Environment, Primitive};
+
+use super::closure::{self, Expr, Lifted};
+
+/// Compiles `main` of `program`, along with every definition, to a C99
+/// program that prints the value of `main`.
+pub fn compile_program(program: &ParseNode) -> Result<String, Error> {
+    closure::convert_program(program).map(|lifted| emit(&lifted))
+}
+
+/// Compiles the definitions in `env` and `entry` to a C99 program.
+pub fn compile(env: &Environment, entry: &ParseNode) -> Result<String, Error> {
+    closure::convert(env, entry).map(|lifted| emit(&lifted))
+}
+
+const RUNTIME: &str = include_str!("runtime.c");
+
+/// Writes every lifted lambda as a C function of its closure and its
+/// argument, and every global as a function that computes its value. The
+/// runtime evaluates by value, and globals once, when they are first used.
+pub fn emit(lifted: &Lifted) -> String {
+    let mut c = String::new();
+    writeln!(c, "/* Generated by lambda make --target c */").unwrap();
+    writeln!(c, "#define GLOBAL_COUNT {}", lifted.globals.len()).unwrap();
+    writeln!(c, "{}", RUNTIME).unwrap();
+    for i in 0..lifted.lambdas.len() {
+        writeln!(c, "static Value lambda_{}(Value self, Value arg);", i).unwrap();
+    }
+    for (i, (name, _)) in lifted.globals.iter().enumerate() {
+        writeln!(c, "static Value global_{}(void); /* {} */", i, name).unwrap();
+    }
+    for (i, lambda) in lifted.lambdas.iter().enumerate() {
+        writeln!(c, "\nstatic Value lambda_{}(Value self, Value arg)", i).unwrap();
+        c.push_str(&Function::body(&lambda.body, true));
+    }
+    for (i, (_, value)) in lifted.globals.iter().enumerate() {
+        writeln!(c, "\nstatic Value global_{}(void)", i).unwrap();
+        c.push_str(&Function::body(value, false));
+    }
+    writeln!(c, "\nstatic Value entry(void)").unwrap();
+    c.push_str(&Function::body(&lifted.entry, false));
+    c
+}
+
+/// Builds the body of a C function. Every intermediate value is kept in a
+/// stack slot `r[i]`, where the collector can find it.
+struct Function{
+    code: String,
+    slots: usize
+}
+
+impl Function{
+    fn body(expr: &Expr, lambda: bool) -> String {
+        // A lambda keeps its closure in r[0] and its argument in r[1]
+        let mut function = Function { code: String::new(), slots: if lambda { 2 } else { 0 } };
+        let result = function.expr(expr);
+        let mut body = String::from("{\n");
+        writeln!(body, "    Value *r = enter({});", function.slots).unwrap();
+        if lambda {
+            body.push_str("    r[0] = self;\n    r[1] = arg;\n");
+        }
+        body.push_str(&function.code);
+        writeln!(body, "    return leave(r, r[{}]);\n}}", result).unwrap();
+        body
+    }
+
+    fn slot(&mut self) -> usize {
+        self.slots += 1;
+        self.slots - 1
+    }
+
+    /// Emits code that computes `expr`, and returns the slot it ends up in.
+    fn expr(&mut self, expr: &Expr) -> usize {
+        let value = match *expr {
+            Expr::Argument => return 1,
+            Expr::Int(val) => format!("INT({})", val),
+            Expr::Captured(index) => format!("FIELD(r[0], {})", index),
+            Expr::Global(global) => format!("global({}, global_{})", global, global),
+            Expr::Primitive(primitive) => format!("primitive({})", op(primitive)),
+            Expr::Operation(primitive, ref first, ref second) => {
+                let (first, second) = (self.expr(first), self.expr(second));
+                format!("operate({}, r[{}], r[{}])", op(primitive), first, second)
+            },
+            Expr::Closure(lambda, ref captured) if captured.is_empty() => format!("closure(lambda_{}, 0, NULL)", lambda),
+            Expr::Closure(lambda, ref captured) => {
+                let slots = captured.iter().map(|value| self.expr(value)).collect::<Vec<_>>();
+                // The captured values are passed to the runtime side by side
+                let first = self.slots;
+                for slot in slots {
+                    let copy = self.slot();
+                    writeln!(self.code, "    r[{}] = r[{}];", copy, slot).unwrap();
+                }
+                format!("closure(lambda_{}, {}, &r[{}])", lambda, captured.len(), first)
+            },
+            Expr::Application(ref function, ref argument) => {
+                let (function, argument) = (self.expr(function), self.expr(argument));
+                format!("apply(r[{}], r[{}])", function, argument)
+            }
+        };
+        let slot = self.slot();
+        writeln!(self.code, "    r[{}] = {};", slot, value).unwrap();
+        slot
+    }
+}
+
+fn op(primitive: Primitive) -> &'static str {
+    match primitive {
+        Primitive::Add => "OP_ADD",
+        Primitive::Sub => "OP_SUB",
+        Primitive::Mul => "OP_MUL",
+        Primitive::Div => "OP_DIV",
+        Primitive::Eq => "OP_EQ",
+        Primitive::Lt => "OP_LT"
+    }
+}
+
+/// Runs the system `cc` on the C file `path`, writing `executable`.
+pub fn build_executable(path: &Path, executable: &Path) -> Result<(), String> {
+    let status = Command::new("cc")
+        .args(["-std=c99", "-O2", "-o"])
+        .arg(executable)
+        .arg(path)
+        .status()
+        .map_err(|e| format!("can't run cc: {}", e))?;
+    if !status.success() {
+        return Err(format!("cc failed on {}", path.display()));
+    }
+    println!("wrote {}", executable.display());
+    Ok(())
+}
+
+#[test]
+fn c_golden(){
+    use lexer::Lexer;
+    use parser::Parser;
+
+    let program = Parser::new(Lexer::new("k = \\x. \\y. x\nmain = k (add 1 2) sub")).parse().unwrap();
+    let c = compile_program(&program).unwrap();
+    assert!(c.starts_with("/* Generated by lambda make --target c */\n#define GLOBAL_COUNT 2\n"));
+    // Only what is generated, not the runtime
+    let generated = &c[c.find("static Value lambda_0(Value self, Value arg);").unwrap()..];
+    assert_eq!(generated, include_str!("golden/k.c"));
+}
+
+/// Builds the corpus with `cc`, if there is one, and compares what it
+/// prints with evaluation by value.
+#[test]
+fn c_runs_natively(){
+    use std::{env, fs, process};
+    use eval::{DbTerm, Strategy};
+    use lexer::Lexer;
+    use parser::Parser;
+    use machine::corpus;
+
+    if Command::new("cc").arg("--version").output().is_err() {
+        eprintln!("skipped c_runs_natively: cc isn't installed");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("lambda-c-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let run = |name: &str, c: String| {
+        let path = dir.join(format!("{}.c", name));
+        fs::write(&path, c).unwrap();
+        let executable = path.with_extension("");
+        let built = Command::new("cc").args(["-std=c99", "-pedantic", "-o"]).arg(&executable).arg(&path).output().unwrap();
+        assert!(built.status.success(), "building {}: {}", name, String::from_utf8_lossy(&built.stderr));
+        let output = Command::new(&executable).output().unwrap();
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    for (i, &(definitions, input)) in corpus::CASES.iter().enumerate() {
+        let (env, term) = corpus::load(definitions, input);
+        let expected = match corpus::substitution(&env, &term, Strategy::CallByValue) {
+            Ok(DbTerm::Int(val)) => val.to_string(),
+            Ok(DbTerm::Abstraction(_)) => "<function>".to_string(),
+            _ => continue
+        };
+        assert_eq!(run(&format!("case{}", i), compile(&env, &term).unwrap()), expected, "running {}", input);
+    }
+
+    // Enough allocation to collect many times
+    let program = format!("{}\nmain = add (fact 10) (fib 25)", corpus::ARITHMETIC);
+    let c = compile_program(&Parser::new(Lexer::new(&program)).parse().unwrap()).unwrap();
+    assert_eq!(run("recursive", c), (3628800 + 75025).to_string());
+    fs::remove_dir_all(&dir).unwrap();
+}
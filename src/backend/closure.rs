@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use parser::{ParseNode, GrammarItem};
+use errors::error_index::Error;
+use eval::{Environment, Primitive};
+
+/// An expression after closure conversion. Lambdas are lifted out into a
+/// table, and refer to their parameter and to the variables they capture
+/// by position instead of by name.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr{
+    Int(i32),
+    Argument,                                   // The parameter of the enclosing lambda
+    Captured(usize),                            // Carries the position in the closure
+    Global(usize),
+    Primitive(Primitive),                       // An unapplied primitive
+    Operation(Primitive, Box<Expr>, Box<Expr>), // A primitive with both its operands
+    Closure(usize, Vec<Expr>),                  // Carries the lambda, and the values it captures
+    Application(Box<Expr>, Box<Expr>)
+}
+
+/// A lifted lambda, whose body refers to `captured` values of its closure.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lambda{
+    pub captured: usize,
+    pub body: Expr
+}
+
+/// A closure converted program. Global bodies and the entry have no
+/// enclosing lambda, so they only use globals, primitives and closures.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lifted{
+    pub globals: Vec<(String, Expr)>,
+    pub lambdas: Vec<Lambda>,
+    pub entry: Expr
+}
+
+/// Converts `main` of `program`, along with every definition.
+pub fn convert_program(program: &ParseNode) -> Result<Lifted, Error> {
+    let mut env = Environment::new();
+    env.load(program);
+    if !env.contains("main") {
+        return Err(Error::InvalidAst("the program has no 'main' definition".to_string()));
+    }
+    convert(&env, &ParseNode::variable("main"))
+}
+
+/// Converts the definitions in `env`, in alphabetical order, and `entry`.
+/// Names that are neither bound, defined nor primitives are errors, since
+/// compiled code has nothing to show them as.
+pub fn convert(env: &Environment, entry: &ParseNode) -> Result<Lifted, Error> {
+    let globals = env.iter().enumerate().map(|(i, (name, _))| (name.clone(), i)).collect();
+    let mut converter = Converter { globals, lambdas: vec!() };
+    let mut converted = vec!();
+    for (name, value) in env.iter() {
+        converted.push((name.clone(), converter.convert(value, &Scope::top())?));
+    }
+    let entry = converter.convert(entry, &Scope::top())?;
+    Ok(Lifted { globals: converted, lambdas: converter.lambdas, entry })
+}
+
+/// The local names visible in a lambda body.
+struct Scope<'a>{
+    param: Option<&'a str>,
+    captured: Vec<String>
+}
+
+impl<'a> Scope<'a>{
+    fn top() -> Scope<'a> {
+        Scope { param: None, captured: vec!() }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.param == Some(name) || self.captured.iter().any(|c| c == name)
+    }
+
+    fn lookup(&self, name: &str) -> Option<Expr> {
+        if self.param == Some(name) {
+            return Some(Expr::Argument);
+        }
+        self.captured.iter().position(|c| c == name).map(Expr::Captured)
+    }
+}
+
+struct Converter{
+    globals: HashMap<String, usize>,
+    lambdas: Vec<Lambda>
+}
+
+impl Converter{
+    fn convert(&mut self, node: &ParseNode, scope: &Scope) -> Result<Expr, Error> {
+        match node.entry {
+            GrammarItem::LiteralInt(val) => Ok(Expr::Int(val)),
+            GrammarItem::Variable(ref name) => self.variable(node, name, scope),
+            GrammarItem::Abstraction(ref param, ref body) => {
+                let mut captured = vec!();
+                free_locals(body, &mut vec!(param.clone()), scope, &mut captured);
+                let values = captured.iter().map(|name| scope.lookup(name).unwrap()).collect();
+                let inner = Scope { param: Some(param), captured };
+                let body = self.convert(body, &inner)?;
+                self.lambdas.push(Lambda { captured: inner.captured.len(), body });
+                Ok(Expr::Closure(self.lambdas.len() - 1, values))
+            },
+            GrammarItem::Application(ref left, ref right) => {
+                if let GrammarItem::Application(ref op, ref first) = left.entry {
+                    if let Some(primitive) = self.primitive(op, scope) {
+                        let first = self.convert(first, scope)?;
+                        let second = self.convert(right, scope)?;
+                        return Ok(Expr::Operation(primitive, Box::new(first), Box::new(second)));
+                    }
+                }
+                let function = self.convert(left, scope)?;
+                Ok(Expr::Application(Box::new(function), Box::new(self.convert(right, scope)?)))
+            },
+            _ => Err(Error::InvalidAst("only expressions can be closure converted".to_string()))
+        }
+    }
+
+    fn variable(&self, node: &ParseNode, name: &str, scope: &Scope) -> Result<Expr, Error> {
+        if let Some(local) = scope.lookup(name) {
+            return Ok(local);
+        }
+        if let Some(&global) = self.globals.get(name) {
+            return Ok(Expr::Global(global));
+        }
+        match Primitive::from_name(name) {
+            Some(primitive) => Ok(Expr::Primitive(primitive)),
+            None => Err(Error::UnboundVariable(name.to_string(), node.span, None))
+        }
+    }
+
+    /// The primitive `node` names, unless a local or a definition hides it.
+    fn primitive(&self, node: &ParseNode, scope: &Scope) -> Option<Primitive> {
+        match node.entry {
+            GrammarItem::Variable(ref name) if !scope.contains(name) && !self.globals.contains_key(name) =>
+                Primitive::from_name(name),
+            _ => None
+        }
+    }
+}
+
+/// Adds to `free` the names of `scope` that `node` uses without binding
+/// them itself, in order of first use.
+fn free_locals(node: &ParseNode, bound: &mut Vec<String>, scope: &Scope, free: &mut Vec<String>){
+    match node.entry {
+        GrammarItem::Variable(ref name) if scope.contains(name) && !bound.contains(name) && !free.contains(name) =>
+            free.push(name.clone()),
+        GrammarItem::Abstraction(ref param, ref body) => {
+            bound.push(param.clone());
+            free_locals(body, bound, scope, free);
+            bound.pop();
+        },
+        GrammarItem::Application(ref left, ref right) => {
+            free_locals(left, bound, scope, free);
+            free_locals(right, bound, scope, free);
+        },
+        _ => ()
+    }
+}
+
+#[test]
+fn closure_conversion_lifts_lambdas(){
+    use lexer::Lexer;
+    use parser::Parser;
+
+    let program = Parser::new(Lexer::new("k = \\x. \\y. x\nmain = k (add 1 2) sub")).parse().unwrap();
+    let lifted = convert_program(&program).unwrap();
+    assert_eq!(lifted.lambdas, vec![
+        Lambda { captured: 1, body: Expr::Captured(0) },
+        Lambda { captured: 0, body: Expr::Closure(0, vec![Expr::Argument]) },
+    ]);
+    assert_eq!(lifted.globals, vec![
+        ("k".to_string(), Expr::Closure(1, vec!())),
+        ("main".to_string(), Expr::Application(
+            Box::new(Expr::Application(
+                Box::new(Expr::Global(0)),
+                Box::new(Expr::Operation(Primitive::Add, Box::new(Expr::Int(1)), Box::new(Expr::Int(2)))))),
+            Box::new(Expr::Primitive(Primitive::Sub))
+        )),
+    ]);
+    assert_eq!(lifted.entry, Expr::Global(1));
+}
@@ -0,0 +1,50 @@
+static Value lambda_0(Value self, Value arg);
+static Value lambda_1(Value self, Value arg);
+static Value global_0(void); /* k */
+static Value global_1(void); /* main */
+
+static Value lambda_0(Value self, Value arg)
+{
+    Value *r = enter(3);
+    r[0] = self;
+    r[1] = arg;
+    r[2] = FIELD(r[0], 0);
+    return leave(r, r[2]);
+}
+
+static Value lambda_1(Value self, Value arg)
+{
+    Value *r = enter(4);
+    r[0] = self;
+    r[1] = arg;
+    r[2] = r[1];
+    r[3] = closure(lambda_0, 1, &r[2]);
+    return leave(r, r[3]);
+}
+
+static Value global_0(void)
+{
+    Value *r = enter(1);
+    r[0] = closure(lambda_1, 0, NULL);
+    return leave(r, r[0]);
+}
+
+static Value global_1(void)
+{
+    Value *r = enter(7);
+    r[0] = global(0, global_0);
+    r[1] = INT(1);
+    r[2] = INT(2);
+    r[3] = operate(OP_ADD, r[1], r[2]);
+    r[4] = apply(r[0], r[3]);
+    r[5] = primitive(OP_SUB);
+    r[6] = apply(r[4], r[5]);
+    return leave(r, r[6]);
+}
+
+static Value entry(void)
+{
+    Value *r = enter(1);
+    r[0] = global(1, global_1);
+    return leave(r, r[0]);
+}
@@ -0,0 +1,39 @@
+  (table 8 funcref)
+  (elem (i32.const 0)
+    $boolean_first
+    $boolean_second
+    $primitive_first
+    $primitive_second
+    $lambda_0
+    $lambda_1
+    $global_0
+    $global_1)
+
+  (func $lambda_0 (type $code)
+    (i64.load offset=8 (i32.wrap_i64 (local.get 0))))
+
+  (func $lambda_1 (type $code)
+    (local $object i32)
+    (block (result i64)
+      (local.set $object (call $allocate (i32.const 4) (i32.const 1)))
+      (i64.store offset=8 (local.get $object) (local.get 1))
+      (i64.extend_i32_u (local.get $object))))
+
+  ;; k
+  (func $global_0 (type $thunk)
+    (local $object i32)
+    (block (result i64)
+      (local.set $object (call $allocate (i32.const 5) (i32.const 0)))
+      (i64.extend_i32_u (local.get $object))))
+
+  ;; main
+  (func $global_1 (type $thunk)
+    (call $apply
+      (call $apply
+        (call $global (i32.const 0) (i32.const 6))
+        (call $operate (i32.const 0) (i64.const 3) (i64.const 5)))
+      (call $primitive (i32.const 1))))
+
+  (func (export "main") (result i32)
+    (i32.wrap_i64 (call $int (call $global (i32.const 1) (i32.const 7)))))
+)
@@ -0,0 +1,192 @@
+use std::fmt::Write;
+
+use parser::{ParseNode, GrammarItem};
+use errors::error_index::Error;
+use eval::{Environment, Primitive};
+
+/// Compiles `main` of `program`, along with every definition, to an ES
+/// module that exports a function evaluating `main`.
+pub fn compile_program(program: &ParseNode) -> Result<String, Error> {
+    let mut env = Environment::new();
+    env.load(program);
+    if !env.contains("main") {
+        return Err(Error::InvalidAst("the program has no 'main' definition".to_string()));
+    }
+    compile(&env, &ParseNode::variable("main"))
+}
+
+/// Compiles the definitions in `env` and `entry` to an ES module. Lambdas
+/// become arrow functions taking their argument unevaluated, as a `Thunk`
+/// that is forced at most once, so evaluation is call by need. Arguments
+/// that are already values, or variables, are passed without a thunk.
+/// The exported `main` returns the value of `entry`, or throws an
+/// `EvaluationError` when it is stuck, loops or overflows the stack.
+pub fn compile(env: &Environment, entry: &ParseNode) -> Result<String, Error> {
+    let compiler = JsCompiler { env };
+    let mut module = String::from(RUNTIME);
+    writeln!(module).unwrap();
+    for (name, value) in env.iter() {
+        let value = match value.entry {
+            GrammarItem::Abstraction(_, _) | GrammarItem::LiteralInt(_) => compiler.value(value, &mut vec!())?,
+            // Definitions may refer to ones further down, so anything else
+            // is only evaluated once it is used
+            _ => format!("new Thunk(() => {})", compiler.value(value, &mut vec!())?)
+        };
+        writeln!(module, "const {} = {};", identifier(name), value).unwrap();
+    }
+    writeln!(module).unwrap();
+    writeln!(module, "export function main() {{\n  return evaluate(() => {});\n}}", compiler.value(entry, &mut vec!())?).unwrap();
+    Ok(module)
+}
+
+/// Makes the runtime available under names that can't clash with the
+/// program's, which all start with `$`.
+const RUNTIME: &str = include_str!("runtime.js");
+
+struct JsCompiler<'a>{
+    env: &'a Environment
+}
+
+impl<'a> JsCompiler<'a>{
+    /// An expression that evaluates `node` to a number or a function.
+    fn value(&self, node: &ParseNode, scope: &mut Vec<String>) -> Result<String, Error> {
+        match node.entry {
+            GrammarItem::LiteralInt(val) => Ok(val.to_string()),
+            GrammarItem::Variable(ref name) => match self.primitive(name, scope) {
+                Some(primitive) => Ok(format!("curry(primitives.{})", primitive.name())),
+                None => Ok(format!("force({})", self.variable(node, name, scope)?))
+            },
+            GrammarItem::Abstraction(ref param, ref body) => {
+                scope.push(param.clone());
+                let body = self.value(body, scope);
+                scope.pop();
+                Ok(format!("({} => {})", identifier(param), body?))
+            },
+            GrammarItem::Application(ref left, ref right) => {
+                // Saturated primitives are called directly
+                if let GrammarItem::Application(ref op, ref first) = left.entry {
+                    if let GrammarItem::Variable(ref name) = op.entry {
+                        if let Some(primitive) = self.primitive(name, scope) {
+                            let first = self.value(first, scope)?;
+                            let second = self.value(right, scope)?;
+                            return Ok(format!("primitives.{}({}, {})", primitive.name(), first, second));
+                        }
+                    }
+                }
+                let function = self.value(left, scope)?;
+                Ok(format!("app({}, {})", function, self.argument(right, scope)?))
+            },
+            _ => Err(Error::InvalidAst("only expressions can be compiled to JavaScript".to_string()))
+        }
+    }
+
+    /// An expression for `node` as an argument, which is only evaluated if
+    /// the function it is passed to forces it.
+    fn argument(&self, node: &ParseNode, scope: &mut Vec<String>) -> Result<String, Error> {
+        match node.entry {
+            GrammarItem::Variable(ref name) if self.primitive(name, scope).is_none() => self.variable(node, name, scope),
+            GrammarItem::Variable(_) | GrammarItem::LiteralInt(_) | GrammarItem::Abstraction(_, _) => self.value(node, scope),
+            _ => Ok(format!("new Thunk(() => {})", self.value(node, scope)?))
+        }
+    }
+
+    fn variable(&self, node: &ParseNode, name: &str, scope: &[String]) -> Result<String, Error> {
+        if scope.iter().any(|bound| bound == name) || self.env.contains(name) {
+            Ok(identifier(name))
+        } else {
+            Err(Error::UnboundVariable(name.to_string(), node.span, None))
+        }
+    }
+
+    /// The primitive `name` refers to, unless a binder or definition hides it.
+    fn primitive(&self, name: &str, scope: &[String]) -> Option<Primitive> {
+        if scope.iter().any(|bound| bound == name) || self.env.contains(name) {
+            return None;
+        }
+        Primitive::from_name(name)
+    }
+}
+
+/// `name` prefixed with `$`, so that it is never a keyword or a runtime
+/// name. Characters JavaScript might not accept are written as `$` and
+/// their code point in hex, which can't be confused with a source name as
+/// those never contain `$`.
+pub fn identifier(name: &str) -> String {
+    let mut identifier = String::from("$");
+    for ch in name.chars() {
+        if ch.is_ascii_alphanumeric() || ch == '_' {
+            identifier.push(ch);
+        } else {
+            write!(identifier, "${:x}", ch as u32).unwrap();
+        }
+    }
+    identifier
+}
+
+#[cfg(test)]
+fn program(definitions: &str, main: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(&format!("{}\nmain = {}", definitions, main))).parse().unwrap()
+}
+
+#[test]
+fn js_golden(){
+    let module = compile_program(&program("twice = \\f. \\x. f (f x)\nsucc = add 1", "twice succ (mul 2 3)")).unwrap();
+    assert_eq!(module, include_str!("golden/twice.mjs"));
+    assert_eq!(identifier("if"), "$if");
+    assert_eq!(identifier("λ"), "$$3bb");
+    assert_eq!(compile_program(&program("", "f 1")).map(|_| ()),
+        Err(Error::UnboundVariable("f".to_string(), ::lexer::Span::new(8, 9), None)));
+}
+
+/// Runs the corpus on `node`, if it is installed, and compares what `main`
+/// shows as with the normal form. Failures should read as the C runtime
+/// reports them.
+#[test]
+fn js_runs_on_node(){
+    use std::{env, fs, process};
+    use eval::DbTerm;
+    use machine::corpus;
+
+    if process::Command::new("node").arg("--version").output().is_err() {
+        eprintln!("skipped js_runs_on_node: node isn't installed");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("lambda-js-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let run = |name: &str, module: String| {
+        let path = dir.join(format!("{}.mjs", name));
+        fs::write(&path, module).unwrap();
+        let script = format!("import {{ main, show, EvaluationError }} from {:?};\n\
+            try {{ console.log(show(main())); }}\n\
+            catch (e) {{ if (!(e instanceof EvaluationError)) throw e; console.log(`Error: ${{e.message}}`); }}",
+            path.display().to_string());
+        let output = process::Command::new("node").args(["--input-type=module", "-e", &script]).output().unwrap();
+        assert!(output.status.success(), "running {}: {}", name, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    for (i, &(definitions, input)) in corpus::CASES.iter().enumerate() {
+        let (env, term) = corpus::load(definitions, input);
+        let expected = match corpus::normal_form(&env, &term) {
+            Ok(DbTerm::Int(val)) => val.to_string(),
+            Ok(DbTerm::Abstraction(_)) => "<function>".to_string(),
+            // Free names don't compile, and stuck terms are checked below
+            _ => continue
+        };
+        assert_eq!(run(&format!("case{}", i), compile(&env, &term).unwrap()), expected, "running {}", input);
+    }
+
+    let down = "down = \\n. (eq n 0) 0 (add 1 (down (sub n 1)))";
+    let failures = [
+        ("add 2147483647 1", "Error: stuck: the result is out of range"),
+        ("div 1 0", "Error: stuck: division by zero"),
+        ("3 4", "Error: stuck: an integer can't be applied"),
+        ("down 1000000", "Error: stack overflow")
+    ];
+    for (i, &(main, expected)) in failures.iter().enumerate() {
+        let module = compile_program(&program(down, main)).unwrap();
+        assert_eq!(run(&format!("failure{}", i), module), expected, "running {}", main);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,117 @@
+pub mod c;
+pub mod closure;
+pub mod js;
+pub mod wat;
+
+use std::fs;
+use std::path::Path;
+
+use errors::report::report;
+use parser::export::read_program;
+use bytecode;
+
+/// What `make` compiles a program to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Target{
+    Bytecode,
+    Js,
+    C,
+    Wat
+}
+
+static TARGETS: &[Target] = &[Target::Bytecode, Target::Js, Target::C, Target::Wat];
+
+impl Target{
+    pub fn all() -> &'static [Target] {
+        TARGETS
+    }
+
+    pub fn from_name(name: &str) -> Option<Target> {
+        TARGETS.iter().cloned().find(|t| t.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Bytecode => "bytecode",
+            Target::Js => "js",
+            Target::C => "c",
+            Target::Wat => "wat"
+        }
+    }
+
+    /// Extension of the file `make` writes.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Target::Bytecode => "lcb",
+            Target::Js => "mjs",
+            Target::C => "c",
+            Target::Wat => "wat"
+        }
+    }
+}
+
+/// Compiles the program in `path` for `target` and writes it next to it,
+/// unless that would overwrite `path` itself. Returns whether that worked.
+pub fn make_file(path: &str, target: Target) -> bool {
+    let output = Path::new(path).with_extension(target.extension());
+    if output == Path::new(path) {
+        return report(Err(format!("can't write {} over the program it is compiled from", output.display())));
+    }
+    let compile = match target {
+        Target::Bytecode => return bytecode::make_file(path),
+        Target::Js => js::compile_program,
+        Target::C => c::compile_program,
+        Target::Wat => wat::compile_program
+    };
+    let result = read_program(Path::new(path))
+        .and_then(|program| compile(&program).map_err(|e| format!("can't compile {}: {:?}", path, e)))
+        .and_then(|code| fs::write(&output, code).map_err(|e| format!("can't write {}: {}", output.display(), e)));
+    report(result.map(|()| println!("wrote {}", output.display())))
+}
+
+/// Builds a native executable from the C that `make` wrote for `path`,
+/// named like `path` without its extension. Refuses when `path` has none,
+/// as the executable would replace it.
+pub fn build_native(path: &str) -> bool {
+    let executable = Path::new(path).with_extension("");
+    if executable == Path::new(path) {
+        return report(Err(format!("can't write the executable over {}, give it an extension such as .lc", path)));
+    }
+    report(c::build_executable(&Path::new(path).with_extension("c"), &executable))
+}
+
+#[test]
+fn target_names(){
+    for &target in Target::all() {
+        assert_eq!(Target::from_name(target.name()), Some(target));
+    }
+    assert_eq!(Target::from_name("llvm"), None);
+}
+
+#[test]
+fn make_keeps_the_source(){
+    use std::{env, process};
+
+    let dir = env::temp_dir().join(format!("lambda-make-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for &target in Target::all() {
+        let path = dir.join(format!("main.{}", target.extension()));
+        fs::write(&path, "main = 1\n").unwrap();
+        assert!(!make_file(path.to_str().unwrap(), target), "making {}", path.display());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "main = 1\n");
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn native_build_keeps_the_source(){
+    use std::{env, process};
+
+    let dir = env::temp_dir().join(format!("lambda-native-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("prog");
+    fs::write(&path, "main = 1\n").unwrap();
+    assert!(!build_native(path.to_str().unwrap()));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "main = 1\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,244 @@
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* Integers are tagged with a 1 in the lowest bit, and everything else is a
+ * pointer to a closure. */
+typedef uintptr_t Value;
+typedef Value (*Code)(Value self, Value arg);
+
+typedef struct Object {
+    struct Object *forward;     /* Where collection copied the object to */
+    Code code;
+    size_t size;                /* Number of captured values */
+    Value fields[];
+} Object;
+
+#define INT(n) (((Value)(intptr_t)(n) << 1) | 1)
+#define IS_INT(v) ((v) & 1)
+#define INT_VALUE(v) ((int32_t)(((intptr_t)(v) - 1) / 2))
+#define OBJECT(v) ((Object *)(v))
+#define FIELD(v, i) (OBJECT(v)->fields[i])
+
+static void fail(const char *message)
+{
+    fprintf(stderr, "Error: %s\n", message);
+    exit(1);
+}
+
+/* Every value that must survive an allocation is kept on this stack, so
+ * that collection can find and move it. */
+#define STACK_SLOTS (1 << 18)
+static Value stack[STACK_SLOTS];
+static Value *sp = stack;
+
+static Value *enter(size_t slots)
+{
+    Value *frame = sp;
+    size_t i;
+    if (slots > (size_t)(stack + STACK_SLOTS - sp))
+        fail("stack overflow");
+    for (i = 0; i < slots; i++)
+        frame[i] = INT(0);
+    sp += slots;
+    return frame;
+}
+
+static Value leave(Value *frame, Value result)
+{
+    sp = frame;
+    return result;
+}
+
+/* Globals are evaluated the first time they are used. */
+enum { UNEVALUATED, EVALUATING, DONE };
+static Value globals[GLOBAL_COUNT + 1];
+static char global_state[GLOBAL_COUNT + 1];
+
+static Value global(size_t i, Value (*code)(void))
+{
+    Value value;
+    if (global_state[i] == DONE)
+        return globals[i];
+    if (global_state[i] == EVALUATING)
+        fail("evaluation loops forever");
+    global_state[i] = EVALUATING;
+    value = code();
+    globals[i] = value;
+    global_state[i] = DONE;
+    return value;
+}
+
+/* A copying collector. Live objects are copied to a new space, from the
+ * stack and the globals, and then from the objects already copied. */
+static size_t heap_size = 1 << 20;
+static char *heap, *heap_top, *heap_end;
+
+static size_t object_bytes(size_t fields)
+{
+    return sizeof(Object) + fields * sizeof(Value);
+}
+
+static Value copy(Value v)
+{
+    Object *object;
+    if (IS_INT(v))
+        return v;
+    object = OBJECT(v);
+    if (object->forward == NULL) {
+        size_t bytes = object_bytes(object->size);
+        memcpy(heap_top, object, bytes);
+        object->forward = (Object *)heap_top;
+        heap_top += bytes;
+    }
+    return (Value)object->forward;
+}
+
+static void collect_into(size_t size)
+{
+    char *from = heap, *scan;
+    Value *root;
+    size_t i;
+    heap = heap_top = malloc(size);
+    if (heap == NULL)
+        fail("out of memory");
+    heap_end = heap + size;
+    for (root = stack; root < sp; root++)
+        *root = copy(*root);
+    for (i = 0; i < GLOBAL_COUNT; i++)
+        if (global_state[i] == DONE)
+            globals[i] = copy(globals[i]);
+    for (scan = heap; scan < heap_top; scan += object_bytes(((Object *)scan)->size)) {
+        Object *object = (Object *)scan;
+        for (i = 0; i < object->size; i++)
+            object->fields[i] = copy(object->fields[i]);
+    }
+    free(from);
+}
+
+static void collect(size_t need)
+{
+    collect_into(heap_size);
+    /* Grows the heap once it is half full, so collections stay rare */
+    if ((size_t)(heap_top - heap) + need > heap_size / 2) {
+        heap_size = 2 * (heap_size + need);
+        collect_into(heap_size);
+    }
+}
+
+static Object *allocate(Code code, size_t fields)
+{
+    Object *object;
+    size_t bytes = object_bytes(fields);
+    if ((size_t)(heap_end - heap_top) < bytes)
+        collect(bytes);
+    object = (Object *)heap_top;
+    heap_top += bytes;
+    object->forward = NULL;
+    object->code = code;
+    object->size = fields;
+    return object;
+}
+
+/* `captured` must be on the stack, since allocating may move what it holds. */
+static Value closure(Code code, size_t size, const Value *captured)
+{
+    Object *object = allocate(code, size);
+    size_t i;
+    for (i = 0; i < size; i++)
+        object->fields[i] = captured[i];
+    return (Value)object;
+}
+
+static Value apply(Value f, Value arg)
+{
+    if (IS_INT(f))
+        fail("stuck: an integer can't be applied");
+    return OBJECT(f)->code(f, arg);
+}
+
+/* Church booleans, \t. \f. t and \t. \f. f, which capture their flag. */
+static Value boolean_second(Value self, Value arg)
+{
+    return INT_VALUE(FIELD(self, 0)) ? FIELD(self, 1) : arg;
+}
+
+static Value boolean_first(Value self, Value arg)
+{
+    Value *r = enter(2);
+    r[0] = FIELD(self, 0);
+    r[1] = arg;
+    return leave(r, closure(boolean_second, 2, r));
+}
+
+static Value boolean(int flag)
+{
+    Value value = INT(flag);
+    return closure(boolean_first, 1, &value);
+}
+
+/* Primitives only apply to integers, and are stuck on overflow and
+ * division by zero. */
+enum { OP_ADD, OP_SUB, OP_MUL, OP_DIV, OP_EQ, OP_LT };
+
+static Value operate(int op, Value a, Value b)
+{
+    int64_t x, y, result = 0;
+    if (!IS_INT(a) || !IS_INT(b))
+        fail("stuck: primitives only apply to integers");
+    x = INT_VALUE(a);
+    y = INT_VALUE(b);
+    switch (op) {
+    case OP_ADD: result = x + y; break;
+    case OP_SUB: result = x - y; break;
+    case OP_MUL: result = x * y; break;
+    case OP_DIV:
+        if (y == 0)
+            fail("stuck: division by zero");
+        result = x / y;
+        break;
+    case OP_EQ: return boolean(x == y);
+    case OP_LT: return boolean(x < y);
+    }
+    if (result < INT32_MIN || result > INT32_MAX)
+        fail("stuck: the result is out of range");
+    return INT(result);
+}
+
+static Value primitive_second(Value self, Value arg)
+{
+    return operate(INT_VALUE(FIELD(self, 0)), FIELD(self, 1), arg);
+}
+
+static Value primitive_first(Value self, Value arg)
+{
+    Value *r = enter(2);
+    r[0] = FIELD(self, 0);
+    r[1] = arg;
+    return leave(r, closure(primitive_second, 2, r));
+}
+
+static Value primitive(int op)
+{
+    Value value = INT(op);
+    return closure(primitive_first, 1, &value);
+}
+
+static Value entry(void);
+
+int main(void)
+{
+    Value result;
+    heap = heap_top = malloc(heap_size);
+    if (heap == NULL)
+        fail("out of memory");
+    heap_end = heap + heap_size;
+    result = entry();
+    if (IS_INT(result))
+        printf("%d\n", (int)INT_VALUE(result));
+    else
+        printf("<function>\n");
+    return 0;
+}
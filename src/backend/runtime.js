@@ -0,0 +1,97 @@
+// Generated by lambda make --target js
+
+class Thunk {
+  constructor(code) {
+    this.code = code;
+    this.value = undefined;
+  }
+}
+
+class Stuck extends Error {}
+
+// What evaluating `main` fails with. The message is what the C runtime
+// prints after "Error: ".
+export class EvaluationError extends Error {}
+
+function force(v) {
+  if (!(v instanceof Thunk)) {
+    return v;
+  }
+  if (v.code === null) {
+    if (v.value === undefined) {
+      throw new EvaluationError("evaluation loops forever");
+    }
+    return v.value;
+  }
+  const code = v.code;
+  v.code = null;
+  try {
+    v.value = code();
+  } catch (e) {
+    // Left to be forced again, rather than seem to loop
+    v.code = code;
+    throw e;
+  }
+  return v.value;
+}
+
+function evaluate(code) {
+  try {
+    return code();
+  } catch (e) {
+    if (e instanceof Stuck) {
+      throw new EvaluationError(`stuck: ${e.message}`);
+    }
+    if (e instanceof RangeError) {
+      throw new EvaluationError("stack overflow");
+    }
+    throw e;
+  }
+}
+
+function app(f, arg) {
+  if (typeof f !== "function") {
+    throw new Stuck("an integer can't be applied");
+  }
+  return f(arg);
+}
+
+// Primitives only apply to integers, and are stuck on overflow and
+// division by zero
+function int(v) {
+  if (typeof v !== "number") {
+    throw new Stuck("primitives only apply to integers");
+  }
+  return v;
+}
+
+function nonzero(n) {
+  if (n === 0) {
+    throw new Stuck("division by zero");
+  }
+  return n;
+}
+
+function checked(n) {
+  if (!Number.isInteger(n) || n < -2147483648 || n > 2147483647) {
+    throw new Stuck("the result is out of range");
+  }
+  return n;
+}
+
+const church = b => b ? (t => f => force(t)) : (t => f => force(f));
+
+const primitives = {
+  add: (a, b) => checked(int(a) + int(b)),
+  sub: (a, b) => checked(int(a) - int(b)),
+  mul: (a, b) => checked(int(a) * int(b)),
+  div: (a, b) => checked(Math.trunc(int(a) / nonzero(int(b)))),
+  eq: (a, b) => church(int(a) === int(b)),
+  lt: (a, b) => church(int(a) < int(b))
+};
+
+const curry = op => a => b => op(force(a), force(b));
+
+export function show(v) {
+  return typeof v === "number" ? String(v) : "<function>";
+}
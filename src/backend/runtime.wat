@@ -0,0 +1,119 @@
+  ;; Values are i64s. Integers are tagged with a 1 in the lowest bit, and
+  ;; everything else is the address of a closure: its table index, the
+  ;; number of values it captures, then the values.
+  (type $code (func (param i64 i64) (result i64)))
+  (type $thunk (func (result i64)))
+
+  (memory (export "memory") 1)
+
+  ;; Closures are never freed, the heap only grows
+  (func $allocate (param $code i32) (param $size i32) (result i32)
+    (local $object i32)
+    (local $pages i32)
+    (local.set $object (global.get $heap))
+    (global.set $heap
+      (i32.add (local.get $object) (i32.add (i32.const 8) (i32.shl (local.get $size) (i32.const 3)))))
+    (local.set $pages (i32.add (i32.shr_u (global.get $heap) (i32.const 16)) (i32.const 1)))
+    (if (i32.gt_u (local.get $pages) (memory.size))
+      (then
+        (if (i32.eq (memory.grow (i32.sub (local.get $pages) (memory.size))) (i32.const -1))
+          (then unreachable))))
+    (i32.store (local.get $object) (local.get $code))
+    (i32.store offset=4 (local.get $object) (local.get $size))
+    (local.get $object))
+
+  (func $apply (param $f i64) (param $arg i64) (result i64)
+    (if (i32.wrap_i64 (i64.and (local.get $f) (i64.const 1)))
+      (then unreachable))
+    (call_indirect (type $code)
+      (local.get $f) (local.get $arg) (i32.load (i32.wrap_i64 (local.get $f)))))
+
+  ;; Globals are evaluated the first time they are used. Each has a state,
+  ;; 0 when unevaluated, 1 while evaluating and 2 when done, and a value.
+  (func $global (param $index i32) (param $code i32) (result i64)
+    (local $slot i32)
+    (local $value i64)
+    (local.set $slot (i32.add (i32.const 8) (i32.shl (local.get $index) (i32.const 4))))
+    (if (i32.eq (i32.load (local.get $slot)) (i32.const 2))
+      (then (return (i64.load offset=8 (local.get $slot)))))
+    (if (i32.eq (i32.load (local.get $slot)) (i32.const 1))
+      (then unreachable))
+    (i32.store (local.get $slot) (i32.const 1))
+    (local.set $value (call_indirect (type $thunk) (local.get $code)))
+    (i32.store (local.get $slot) (i32.const 2))
+    (i64.store offset=8 (local.get $slot) (local.get $value))
+    (local.get $value))
+
+  ;; Primitives only apply to integers, and trap on overflow and division
+  ;; by zero
+  (func $int (param $v i64) (result i64)
+    (if (i64.eqz (i64.and (local.get $v) (i64.const 1)))
+      (then unreachable))
+    (i64.shr_s (local.get $v) (i64.const 1)))
+
+  (func $tag (param $n i64) (result i64)
+    (if (i32.or (i64.lt_s (local.get $n) (i64.const -2147483648))
+                (i64.gt_s (local.get $n) (i64.const 2147483647)))
+      (then unreachable))
+    (i64.or (i64.shl (local.get $n) (i64.const 1)) (i64.const 1)))
+
+  (func $operate (param $op i32) (param $a i64) (param $b i64) (result i64)
+    (local $x i64)
+    (local $y i64)
+    (local.set $x (call $int (local.get $a)))
+    (local.set $y (call $int (local.get $b)))
+    (block $lt
+      (block $eq
+        (block $div
+          (block $mul
+            (block $sub
+              (block $add
+                (br_table $add $sub $mul $div $eq $lt (local.get $op)))
+              (return (call $tag (i64.add (local.get $x) (local.get $y)))))
+            (return (call $tag (i64.sub (local.get $x) (local.get $y)))))
+          (return (call $tag (i64.mul (local.get $x) (local.get $y)))))
+        (if (i64.eqz (local.get $y))
+          (then unreachable))
+        (return (call $tag (i64.div_s (local.get $x) (local.get $y)))))
+      (return (call $boolean (i64.eq (local.get $x) (local.get $y)))))
+    (call $boolean (i64.lt_s (local.get $x) (local.get $y))))
+
+  ;; Church booleans, \t. \f. t and \t. \f. f, which capture their flag
+  (func $boolean (param $flag i32) (result i64)
+    (local $object i32)
+    (local.set $object (call $allocate (i32.const 0) (i32.const 1)))
+    (i64.store offset=8 (local.get $object) (i64.extend_i32_u (local.get $flag)))
+    (i64.extend_i32_u (local.get $object)))
+
+  (func $boolean_first (type $code)
+    (local $object i32)
+    (local.set $object (call $allocate (i32.const 1) (i32.const 2)))
+    (i64.store offset=8 (local.get $object) (i64.load offset=8 (i32.wrap_i64 (local.get 0))))
+    (i64.store offset=16 (local.get $object) (local.get 1))
+    (i64.extend_i32_u (local.get $object)))
+
+  (func $boolean_second (type $code)
+    (select
+      (i64.load offset=16 (i32.wrap_i64 (local.get 0)))
+      (local.get 1)
+      (i32.wrap_i64 (i64.load offset=8 (i32.wrap_i64 (local.get 0))))))
+
+  ;; Unapplied primitives, which capture their operation
+  (func $primitive (param $op i32) (result i64)
+    (local $object i32)
+    (local.set $object (call $allocate (i32.const 2) (i32.const 1)))
+    (i64.store offset=8 (local.get $object) (i64.extend_i32_u (local.get $op)))
+    (i64.extend_i32_u (local.get $object)))
+
+  (func $primitive_first (type $code)
+    (local $object i32)
+    (local.set $object (call $allocate (i32.const 3) (i32.const 2)))
+    (i64.store offset=8 (local.get $object) (i64.load offset=8 (i32.wrap_i64 (local.get 0))))
+    (i64.store offset=16 (local.get $object) (local.get 1))
+    (i64.extend_i32_u (local.get $object)))
+
+  (func $primitive_second (type $code)
+    (call $operate
+      (i32.wrap_i64 (i64.load offset=8 (i32.wrap_i64 (local.get 0))))
+      (i64.load offset=16 (i32.wrap_i64 (local.get 0)))
+      (local.get 1)))
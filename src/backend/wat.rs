@@ -0,0 +1,224 @@
+use std::fmt::Write;
+
+use parser::ParseNode;
+use errors::error_index::Error;
+use eval::{Environment, Primitive};
+
+use super::closure::{self, Expr, Lifted};
+
+/// Compiles `main` of `program`, along with every definition, to a
+/// WebAssembly module that exports `main`.
+pub fn compile_program(program: &ParseNode) -> Result<String, Error> {
+    closure::convert_program(program).map(|lifted| emit(&lifted))
+}
+
+/// Compiles the definitions in `env` and `entry` to a WebAssembly module.
+pub fn compile(env: &Environment, entry: &ParseNode) -> Result<String, Error> {
+    closure::convert(env, entry).map(|lifted| emit(&lifted))
+}
+
+const RUNTIME: &str = include_str!("runtime.wat");
+
+/// Table slots the runtime takes before the lambdas.
+const RUNTIME_FUNCTIONS: &[&str] = &["$boolean_first", "$boolean_second", "$primitive_first", "$primitive_second"];
+
+/// Writes every lifted lambda as a function of its closure and its
+/// argument, called through the table, and every global as a function that
+/// computes its value. Evaluation is by value, and `main` returns the
+/// integer `main` evaluates to, trapping if it is a function.
+pub fn emit(lifted: &Lifted) -> String {
+    let first_global = RUNTIME_FUNCTIONS.len() + lifted.lambdas.len();
+    let mut wat = String::from(";; Generated by lambda make --target wat\n(module\n");
+    // Global states and values come first in memory, then the heap
+    writeln!(wat, "  (global $heap (mut i32) (i32.const {}))\n", 8 + 16 * lifted.globals.len()).unwrap();
+    wat.push_str(RUNTIME);
+
+    let mut table = RUNTIME_FUNCTIONS.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+    table.extend((0..lifted.lambdas.len()).map(|i| format!("$lambda_{}", i)));
+    table.extend((0..lifted.globals.len()).map(|i| format!("$global_{}", i)));
+    writeln!(wat, "\n  (table {} funcref)", table.len()).unwrap();
+    writeln!(wat, "{}", Wat::list("elem (i32.const 0)", table.iter().map(|f| Wat::atom(f)).collect()).indented(1)).unwrap();
+
+    let lowering = Lowering { lambdas: RUNTIME_FUNCTIONS.len(), globals: first_global };
+    for (i, lambda) in lifted.lambdas.iter().enumerate() {
+        writeln!(wat, "\n  (func $lambda_{} (type $code)", i).unwrap();
+        wat.push_str(&lowering.body(&lambda.body, lowering.expr(&lambda.body)));
+    }
+    for (i, (name, value)) in lifted.globals.iter().enumerate() {
+        writeln!(wat, "\n  ;; {}\n  (func $global_{} (type $thunk)", name, i).unwrap();
+        wat.push_str(&lowering.body(value, lowering.expr(value)));
+    }
+    writeln!(wat, "\n  (func (export \"main\") (result i32)").unwrap();
+    let main = Wat::list("i32.wrap_i64", vec![Wat::list("call $int", vec![lowering.expr(&lifted.entry)])]);
+    wat.push_str(&lowering.body(&lifted.entry, main));
+    wat.push_str(")\n");
+    wat
+}
+
+/// Folded WebAssembly instructions.
+enum Wat{
+    Atom(String),
+    List(String, Vec<Wat>)
+}
+
+impl Wat{
+    fn atom(text: &str) -> Wat {
+        Wat::Atom(text.to_string())
+    }
+
+    fn list(head: &str, items: Vec<Wat>) -> Wat {
+        Wat::List(head.to_string(), items)
+    }
+
+    fn flat(&self) -> String {
+        match *self {
+            Wat::Atom(ref text) => text.clone(),
+            Wat::List(ref head, ref items) =>
+                format!("({})", Some(head.clone()).into_iter().chain(items.iter().map(Wat::flat)).collect::<Vec<_>>().join(" "))
+        }
+    }
+
+    /// On one line if that fits in 100 columns, and otherwise with every
+    /// item on its own line, one level deeper.
+    fn indented(&self, level: usize) -> String {
+        let flat = self.flat();
+        match *self {
+            Wat::List(ref head, ref items) if 2 * level + flat.len() > 100 => {
+                let mut text = format!("{}({}", "  ".repeat(level), head);
+                for item in items {
+                    write!(text, "\n{}", item.indented(level + 1)).unwrap();
+                }
+                text.push(')');
+                text
+            },
+            _ => format!("{}{}", "  ".repeat(level), flat)
+        }
+    }
+}
+
+/// Where the lambdas and globals are in the table.
+struct Lowering{
+    lambdas: usize,
+    globals: usize
+}
+
+impl Lowering{
+    /// The locals a function computing `expr` needs, then `code` and the
+    /// function's closing parenthesis.
+    fn body(&self, expr: &Expr, code: Wat) -> String {
+        let mut body = String::new();
+        if allocates(expr) {
+            body.push_str("    (local $object i32)\n");
+        }
+        writeln!(body, "{})", code.indented(2)).unwrap();
+        body
+    }
+
+    /// Lambdas get their closure in local 0 and their argument in local 1.
+    fn expr(&self, expr: &Expr) -> Wat {
+        match *expr {
+            Expr::Int(val) => i64_const(2 * i64::from(val) + 1),
+            Expr::Argument => Wat::list("local.get", vec![Wat::atom("1")]),
+            Expr::Captured(index) => field(index),
+            Expr::Global(global) => Wat::list("call $global", vec![
+                i32_const(global), i32_const(self.globals + global)
+            ]),
+            Expr::Primitive(primitive) => Wat::list("call $primitive", vec![i32_const(op(primitive))]),
+            Expr::Operation(primitive, ref first, ref second) => Wat::list("call $operate", vec![
+                i32_const(op(primitive)), self.expr(first), self.expr(second)
+            ]),
+            Expr::Closure(lambda, ref captured) => {
+                let allocate = Wat::list("call $allocate", vec![i32_const(self.lambdas + lambda), i32_const(captured.len())]);
+                let mut items = vec![Wat::list("local.set $object", vec![allocate])];
+                // Captured values are only ever locals or fields, so nothing
+                // can allocate between here and the last store
+                for (i, value) in captured.iter().enumerate() {
+                    items.push(Wat::list(&format!("i64.store offset={}", 8 + 8 * i), vec![
+                        Wat::atom("(local.get $object)"), self.expr(value)
+                    ]));
+                }
+                items.push(Wat::atom("(i64.extend_i32_u (local.get $object))"));
+                Wat::list("block (result i64)", items)
+            },
+            Expr::Application(ref function, ref argument) =>
+                Wat::list("call $apply", vec![self.expr(function), self.expr(argument)])
+        }
+    }
+}
+
+/// Whether computing `expr` makes a closure, which needs a local.
+fn allocates(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Closure(_, _) => true,
+        Expr::Operation(_, ref first, ref second) => allocates(first) || allocates(second),
+        Expr::Application(ref function, ref argument) => allocates(function) || allocates(argument),
+        _ => false
+    }
+}
+
+/// A field of the closure in local 0.
+fn field(index: usize) -> Wat {
+    Wat::list(&format!("i64.load offset={}", 8 + 8 * index), vec![Wat::atom("(i32.wrap_i64 (local.get 0))")])
+}
+
+fn i32_const(val: usize) -> Wat {
+    Wat::list("i32.const", vec![Wat::Atom(val.to_string())])
+}
+
+fn i64_const(val: i64) -> Wat {
+    Wat::list("i64.const", vec![Wat::Atom(val.to_string())])
+}
+
+/// Operations in the order of the runtime's `br_table`.
+fn op(primitive: Primitive) -> usize {
+    match primitive {
+        Primitive::Add => 0,
+        Primitive::Sub => 1,
+        Primitive::Mul => 2,
+        Primitive::Div => 3,
+        Primitive::Eq => 4,
+        Primitive::Lt => 5
+    }
+}
+
+#[test]
+fn wat_golden(){
+    use lexer::Lexer;
+    use parser::Parser;
+
+    let program = Parser::new(Lexer::new("k = \\x. \\y. x\nmain = k (add 1 2) sub")).parse().unwrap();
+    let wat = compile_program(&program).unwrap();
+    assert!(wat.contains(RUNTIME));
+    // Only what is generated, not the runtime
+    assert_eq!(&wat[wat.find("  (table").unwrap()..], include_str!("golden/k.wat"));
+}
+
+/// Runs the corpus under `wasmtime`, if it is installed, and compares the
+/// integers `main` returns with evaluation by value.
+#[test]
+fn wat_runs_on_wasmtime(){
+    use std::{env, fs, process};
+    use std::process::Command;
+    use eval::{DbTerm, Strategy};
+    use machine::corpus;
+
+    if Command::new("wasmtime").arg("--version").output().is_err() {
+        eprintln!("skipped wat_runs_on_wasmtime: wasmtime isn't installed");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("lambda-wat-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for (i, &(definitions, input)) in corpus::CASES.iter().enumerate() {
+        let (env, term) = corpus::load(definitions, input);
+        let expected = match corpus::substitution(&env, &term, Strategy::CallByValue) {
+            Ok(DbTerm::Int(val)) => val,
+            _ => continue
+        };
+        let path = dir.join(format!("case{}.wat", i));
+        fs::write(&path, compile(&env, &term).unwrap()).unwrap();
+        let output = Command::new("wasmtime").args(["run", "--invoke", "main"]).arg(&path).output().unwrap();
+        assert!(output.status.success(), "running {}: {}", input, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), expected.to_string(), "running {}", input);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+
+use errors::error_index::Error;
+use eval::{DbTerm, Environment};
+
+/// Encodes a closed term in Binary Lambda Calculus: `00 M` for an
+/// abstraction, `01 M N` for an application, and a variable as one `1`
+/// per binder up to and including its own, then `0`.
+pub fn encode(term: &DbTerm) -> Result<Vec<bool>, Error> {
+    let mut bits = vec!();
+    encode_into(term, &mut bits)?;
+    Ok(bits)
+}
+
+fn encode_into(term: &DbTerm, bits: &mut Vec<bool>) -> Result<(), Error> {
+    match *term {
+        DbTerm::Abstraction(ref body) => {
+            bits.extend_from_slice(&[false, false]);
+            encode_into(body, bits)
+        },
+        DbTerm::Application(ref left, ref right) => {
+            bits.extend_from_slice(&[false, true]);
+            encode_into(left, bits)?;
+            encode_into(right, bits)
+        },
+        DbTerm::Bound(index) => {
+            bits.extend(std::iter::repeat_n(true, index + 1));
+            bits.push(false);
+            Ok(())
+        },
+        DbTerm::Free(ref name) => Err(Error::Unencodable(format!("'{}' is free", name))),
+        DbTerm::Int(val) => Err(Error::Unencodable(format!("the literal {} isn't a lambda term", val)))
+    }
+}
+
+/// Decodes exactly one term from `bits`. Returns the term and how many
+/// bits it took.
+pub fn decode(bits: &[bool]) -> Result<(DbTerm, usize), Error> {
+    let mut at = 0;
+    let term = decode_at(bits, &mut at, 0)?;
+    Ok((term, at))
+}
+
+fn decode_at(bits: &[bool], at: &mut usize, depth: usize) -> Result<DbTerm, Error> {
+    let mut next = || {
+        let bit = bits.get(*at).cloned().ok_or_else(|| Error::InvalidBlc("the bits end inside a term".to_string()));
+        *at += 1;
+        bit
+    };
+    match (next()?, next()?) {
+        (false, false) => Ok(DbTerm::Abstraction(Box::new(decode_at(bits, at, depth + 1)?))),
+        (false, true) => {
+            let left = decode_at(bits, at, depth)?;
+            let right = decode_at(bits, at, depth)?;
+            Ok(DbTerm::Application(Box::new(left), Box::new(right)))
+        },
+        (true, second) => {
+            let mut ones = 1;
+            if second {
+                ones += 1;
+                while next()? {
+                    ones += 1;
+                }
+            }
+            if ones > depth {
+                return Err(Error::InvalidBlc(format!("variable {} is free under {} binders", ones, depth)));
+            }
+            Ok(DbTerm::Bound(ones - 1))
+        }
+    }
+}
+
+/// Bits as text, `0` and `1`.
+pub fn to_text(bits: &[bool]) -> String {
+    bits.iter().map(|&bit| if bit { '1' } else { '0' }).collect()
+}
+
+/// Reads bits written as `0` and `1`, ignoring whitespace.
+pub fn from_text(text: &str) -> Result<Vec<bool>, Error> {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '0' => Ok(false),
+            '1' => Ok(true),
+            _ => Err(Error::InvalidBlc(format!("'{}' isn't a bit", c)))
+        })
+        .collect()
+}
+
+/// Packs bits into bytes, most significant bit first. The last byte is
+/// padded with zeros.
+pub fn pack(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| chunk.iter().enumerate().fold(0, |byte, (i, &bit)| byte | ((bit as u8) << (7 - i))))
+        .collect()
+}
+
+pub fn unpack(bytes: &[u8]) -> Vec<bool> {
+    bytes.iter()
+        .flat_map(|&byte| (0..8).rev().map(move |i| byte & (1 << i) != 0))
+        .collect()
+}
+
+/// `term` with every definition it uses from `env` put in its place, so
+/// that it can be encoded. Recursive definitions can't be, since they would
+/// never stop unfolding.
+pub fn close(env: &Environment, term: &DbTerm) -> Result<DbTerm, Error> {
+    close_in(env, term, &mut HashSet::new())
+}
+
+fn close_in(env: &Environment, term: &DbTerm, unfolding: &mut HashSet<String>) -> Result<DbTerm, Error> {
+    match *term {
+        DbTerm::Free(ref name) => match env.get(name) {
+            Some(value) => {
+                if !unfolding.insert(name.clone()) {
+                    return Err(Error::Unencodable(format!("'{}' is recursive", name)));
+                }
+                // Definitions are closed, so they need no shifting under binders
+                let closed = close_in(env, &DbTerm::from_node(value)?, unfolding);
+                unfolding.remove(name);
+                closed
+            },
+            None => Ok(term.clone())
+        },
+        DbTerm::Abstraction(ref body) => Ok(DbTerm::Abstraction(Box::new(close_in(env, body, unfolding)?))),
+        DbTerm::Application(ref left, ref right) => Ok(DbTerm::Application(
+            Box::new(close_in(env, left, unfolding)?),
+            Box::new(close_in(env, right, unfolding)?)
+        )),
+        _ => Ok(term.clone())
+    }
+}
+
+#[cfg(test)]
+fn db(input: &str) -> DbTerm {
+    use lexer::Lexer;
+    use parser::Parser;
+    DbTerm::from_node(&Parser::new(Lexer::new(input)).parse_expr().unwrap()).unwrap()
+}
+
+#[test]
+fn blc_encodes_terms(){
+    let text = |input| to_text(&encode(&db(input)).unwrap());
+    assert_eq!(text("\\x. x"), "0010");
+    assert_eq!(text("\\x. \\y. x"), "0000110");
+    assert_eq!(text("\\x. \\y. \\z. x z (y z)"), "00000001011110100111010");
+    assert_eq!(encode(&db("\\x. y")), Err(Error::Unencodable("'y' is free".to_string())));
+
+    let s = db("\\x. \\y. \\z. x z (y z)");
+    let bits = encode(&s).unwrap();
+    assert_eq!(decode(&bits), Ok((s, 23)));
+    assert_eq!(pack(&from_text("0010").unwrap()), vec![0x20]);
+    assert_eq!(decode(&unpack(&pack(&bits))).map(|(_, len)| len), Ok(23));
+    assert!(decode(&from_text("0110").unwrap()).is_err());
+    assert!(decode(&from_text("00").unwrap()).is_err());
+}
+
+#[test]
+fn blc_closes_over_definitions(){
+    use lexer::Lexer;
+    use parser::Parser;
+
+    let mut env = Environment::new();
+    env.load(&Parser::new(Lexer::new("id = \\x. x\nk = \\x. \\y. x\nloop = \\x. loop x")).parse().unwrap());
+    assert_eq!(close(&env, &db("k id")), Ok(db("(\\x. \\y. x) (\\x. x)")));
+    assert_eq!(close(&env, &db("id (loop id)")), Err(Error::Unencodable("'loop' is recursive".to_string())));
+}
@@ -0,0 +1,87 @@
+pub mod codec;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use errors::report::report;
+use parser::export::read_program;
+use parser::{PrettyPrinter, Visitor};
+use eval::{nbe, DbTerm, Environment};
+
+pub use self::codec::{close, decode, encode, pack, unpack};
+
+/// Runs `lambda blc ACTION FILE`. `encode` writes `main` of a program as
+/// bits and reports its size; `decode` prints the term in a BLC file and
+/// `run` prints its normal form. With `bytes`, BLC files are packed bytes
+/// rather than `0` and `1` text.
+pub fn blc_command(action: &str, path: &str, bytes: bool) -> bool {
+    report(match action {
+        "encode" => encode_file(path, bytes),
+        "decode" => read_term(path, bytes).map(|term| println!("{}", PrettyPrinter::new(80).visit(&term.to_node()))),
+        "run" => read_term(path, bytes).and_then(|term| {
+            nbe::normalize(&Environment::new(), &term.to_node(), usize::MAX)
+                .map(|normal| println!("{}", PrettyPrinter::new(80).visit(&normal.term)))
+                .map_err(|e| format!("can't run {}: {:?}", path, e))
+        }),
+        _ => Err(format!("unknown blc action '{}', expected encode, decode or run", action))
+    })
+}
+
+fn encode_file(path: &str, bytes: bool) -> Result<(), String> {
+    let program = read_program(Path::new(path))?;
+    let mut env = Environment::new();
+    env.load(&program);
+    if !env.contains("main") {
+        return Err(format!("{} has no 'main' definition", path));
+    }
+    let bits = close(&env, &DbTerm::Free("main".to_string()))
+        .and_then(|term| encode(&term))
+        .map_err(|e| format!("can't encode {}: {:?}", path, e))?;
+    if bytes {
+        io::stdout().write_all(&pack(&bits)).map_err(|e| format!("can't write bytes: {}", e))?;
+    } else {
+        println!("{}", codec::to_text(&bits));
+    }
+    eprintln!("{} bits", bits.len());
+    Ok(())
+}
+
+/// Reads one term from a BLC file, packed bytes if `bytes` is set and `0`
+/// and `1` text otherwise.
+fn read_term(path: &str, bytes: bool) -> Result<DbTerm, String> {
+    let contents = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let bits = if bytes {
+        Ok(unpack(&contents))
+    } else {
+        codec::from_text(&String::from_utf8_lossy(&contents))
+    };
+    let (term, rest) = bits.and_then(|bits| decode(&bits).map(|(term, used)| (term, bits.len() - used)))
+        .map_err(|e| format!("can't decode {}: {:?}", path, e))?;
+    // Packed files may only have the padding of their last byte left over
+    if (!bytes && rest > 0) || (bytes && rest >= 8) {
+        return Err(format!("{} has {} bits after its term", path, rest));
+    }
+    Ok(term)
+}
+
+#[test]
+fn blc_files_round_trip(){
+    use std::{env, process};
+    use lexer::Lexer;
+    use parser::Parser;
+
+    let dir = env::temp_dir().join(format!("lambda-blc-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (text, packed) = (dir.join("text.blc"), dir.join("packed.blc"));
+    // The first two pack to 0x20 and 0x0C, which are whitespace as text
+    for input in ["\\x. x", "\\x. \\y. x", "\\x. \\y. \\z. x z (y z)"] {
+        let term = DbTerm::from_node(&Parser::new(Lexer::new(input)).parse_expr().unwrap()).unwrap();
+        let bits = encode(&term).unwrap();
+        fs::write(&text, codec::to_text(&bits) + "\n").unwrap();
+        fs::write(&packed, pack(&bits)).unwrap();
+        assert_eq!(read_term(text.to_str().unwrap(), false), Ok(term.clone()), "reading {} as text", input);
+        assert_eq!(read_term(packed.to_str().unwrap(), true), Ok(term), "reading {} as bytes", input);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
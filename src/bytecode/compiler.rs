@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use parser::ParseNode;
+use errors::error_index::Error;
+use eval::{DbTerm, Environment, Primitive};
+
+use super::instruction::{Instruction, Module};
+
+/// Compiles `main` of `program`, along with every definition.
+pub fn compile_program(program: &ParseNode) -> Result<Module, Error> {
+    let mut env = Environment::new();
+    env.load(program);
+    if !env.contains("main") {
+        return Err(Error::InvalidAst("the program has no 'main' definition".to_string()));
+    }
+    compile(&env, &ParseNode::variable("main"))
+}
+
+/// Compiles `entry` and the definitions in `env` for call-by-value
+/// evaluation. Each abstraction becomes a block of code that computes
+/// its body, in the same order as `Strategy::CallByValue`.
+pub fn compile(env: &Environment, entry: &ParseNode) -> Result<Module, Error> {
+    let mut compiler = Compiler { module: Module::default(), strings: HashMap::new(), globals: HashMap::new() };
+    let definitions = env.iter()
+        .map(|(name, value)| Ok((name.as_str(), DbTerm::from_node(value)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    // Globals are numbered first so that definitions can refer to each other
+    for (index, &(name, _)) in definitions.iter().enumerate() {
+        let string = compiler.string(name);
+        compiler.module.globals.push((string, 0));
+        compiler.globals.insert(name.to_string(), index as u32);
+    }
+    for (index, (_, body)) in definitions.iter().enumerate() {
+        compiler.module.globals[index].1 = compiler.block(body);
+    }
+    let entry = DbTerm::from_node(entry)?;
+    compiler.module.entry = compiler.block(&entry);
+    Ok(compiler.module)
+}
+
+struct Compiler{
+    module: Module,
+    strings: HashMap<String, u32>,
+    globals: HashMap<String, u32>
+}
+
+impl Compiler{
+    /// Compiles `term` as a block of its own, after the code emitted so far.
+    /// Blocks of abstractions inside it come after it.
+    fn block(&mut self, term: &DbTerm) -> u32 {
+        let mut code = vec!();
+        let mut nested = vec!();
+        self.emit(term, true, &mut code, &mut nested);
+        if !code.last().is_some_and(|i: &Instruction| i.ends_block()) {
+            code.push(Instruction::Return);
+        }
+
+        let address = self.module.code.len() as u32;
+        self.module.code.extend(code);
+        for (at, body) in nested {
+            let block = self.block(body);
+            self.module.code[address as usize + at] = Instruction::Closure(block);
+        }
+        address
+    }
+
+    /// Emits the code for `term` into `code`. Abstractions are left as
+    /// placeholders in `nested`, to be compiled once this block is placed.
+    fn emit<'t>(&mut self, term: &'t DbTerm, tail: bool, code: &mut Vec<Instruction>, nested: &mut Vec<(usize, &'t DbTerm)>){
+        match *term {
+            DbTerm::Bound(index) => code.push(Instruction::Access(index as u32)),
+            DbTerm::Int(val) => code.push(Instruction::Int(val)),
+            DbTerm::Free(ref name) => {
+                let instruction = match (self.globals.get(name), Primitive::from_name(name)) {
+                    (Some(&global), _) => Instruction::Global(global),
+                    (None, Some(primitive)) => Instruction::Primitive(primitive),
+                    (None, None) => Instruction::Free(self.string(name))
+                };
+                code.push(instruction);
+            },
+            DbTerm::Abstraction(ref body) => {
+                nested.push((code.len(), body));
+                code.push(Instruction::Closure(0));
+            },
+            DbTerm::Application(ref left, ref right) => {
+                if let Some(primitive) = self.saturated_primitive(left) {
+                    if let DbTerm::Application(_, ref first) = **left {
+                        self.emit(first, false, code, nested);
+                        self.emit(right, false, code, nested);
+                        code.push(Instruction::Operation(primitive));
+                        return;
+                    }
+                }
+                self.emit(left, false, code, nested);
+                self.emit(right, false, code, nested);
+                code.push(if tail { Instruction::TailApply } else { Instruction::Apply });
+            }
+        }
+    }
+
+    /// The primitive `left` applies, if `left` is a primitive applied to
+    /// one argument, so that applying it again saturates it.
+    fn saturated_primitive(&self, left: &DbTerm) -> Option<Primitive> {
+        match *left {
+            DbTerm::Application(ref op, _) => match **op {
+                DbTerm::Free(ref name) if !self.globals.contains_key(name) => Primitive::from_name(name),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn string(&mut self, text: &str) -> u32 {
+        if let Some(&index) = self.strings.get(text) {
+            return index;
+        }
+        let index = self.module.strings.len() as u32;
+        self.module.strings.push(text.to_string());
+        self.strings.insert(text.to_string(), index);
+        index
+    }
+}
+
+/// The term a block computes, read back from its code. Used to show
+/// closures, which only carry the address of their code.
+pub fn decompile(module: &Module, address: u32) -> DbTerm {
+    let mut stack = vec!();
+    let mut pc = address as usize;
+    loop {
+        let instruction = module.code[pc];
+        pc += 1;
+        let term = match instruction {
+            Instruction::Access(index) => DbTerm::Bound(index as usize),
+            Instruction::Global(global) => DbTerm::Free(module.global_name(global).to_string()),
+            Instruction::Free(string) => DbTerm::Free(module.strings[string as usize].clone()),
+            Instruction::Int(val) => DbTerm::Int(val),
+            Instruction::Closure(block) => DbTerm::Abstraction(Box::new(decompile(module, block))),
+            Instruction::Primitive(primitive) => DbTerm::Free(primitive.name().to_string()),
+            Instruction::Apply | Instruction::TailApply => {
+                let right = stack.pop().expect("apply needs an argument");
+                let left = stack.pop().expect("apply needs a function");
+                DbTerm::Application(Box::new(left), Box::new(right))
+            },
+            Instruction::Operation(primitive) => {
+                let right = stack.pop().expect("operation needs two operands");
+                let left = stack.pop().expect("operation needs two operands");
+                let op = DbTerm::Free(primitive.name().to_string());
+                DbTerm::Application(Box::new(DbTerm::Application(Box::new(op), Box::new(left))), Box::new(right))
+            },
+            Instruction::Return => return stack.pop().expect("return needs a value")
+        };
+        stack.push(term);
+        if instruction.ends_block() {
+            return stack.pop().unwrap();
+        }
+    }
+}
+
+#[test]
+fn compile_blocks(){
+    let module = compile(&Environment::new(), &parse_expr("(\\x. add x 1) 2")).unwrap();
+    assert_eq!(module.code, vec![
+        Instruction::Closure(3), Instruction::Int(2), Instruction::TailApply,
+        Instruction::Access(0), Instruction::Int(1), Instruction::Operation(Primitive::Add), Instruction::Return
+    ]);
+    assert_eq!(decompile(&module, module.entry), DbTerm::from_node(&parse_expr("(\\x. add x 1) 2")).unwrap());
+}
+
+#[cfg(test)]
+fn parse_expr(input: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(input)).parse_expr().unwrap()
+}
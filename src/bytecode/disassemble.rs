@@ -0,0 +1,61 @@
+use super::instruction::{Instruction, Module};
+
+/// Lists the code of `module` one instruction per line, with its address.
+/// Blocks start with a label naming the global or closure they belong to.
+pub fn disassemble(module: &Module) -> String {
+    let mut labels = vec![vec!(); module.code.len()];
+    for (global, &(_, address)) in module.globals.iter().enumerate() {
+        labels[address as usize].push(module.global_name(global as u32).to_string());
+    }
+    labels[module.entry as usize].push("entry".to_string());
+    for &instruction in &module.code {
+        if let Instruction::Closure(address) = instruction {
+            labels[address as usize].push(format!("closure @{}", address));
+        }
+    }
+
+    let mut out = format!("; lcb version {}, {} instructions, {} globals\n",
+        super::file::VERSION, module.code.len(), module.globals.len());
+    for (address, &instruction) in module.code.iter().enumerate() {
+        for label in &labels[address] {
+            out.push_str(&format!("{}:\n", label));
+        }
+        let comment = match instruction {
+            Instruction::Global(global) => format!("  ; {}", module.global_name(global)),
+            Instruction::Free(string) => format!("  ; {}", module.strings[string as usize]),
+            _ => String::new()
+        };
+        out.push_str(&format!("{:>6}  {}{}\n", address, instruction, comment));
+    }
+    out
+}
+
+#[test]
+fn disassemble_labels_blocks(){
+    use lexer::Lexer;
+    use parser::Parser;
+    use super::compile_program;
+
+    let program = Parser::new(Lexer::new("main = k 1 y\nk = \\x. \\y. x")).parse().unwrap();
+    let listing = disassemble(&compile_program(&program).unwrap());
+    assert_eq!(listing, "; lcb version 1, 13 instructions, 2 globals
+k:
+     0  closure   @2
+     1  return
+closure @2:
+     2  closure   @4
+     3  return
+closure @4:
+     4  access    1
+     5  return
+main:
+     6  global    0  ; k
+     7  int       1
+     8  apply
+     9  free      2  ; y
+    10  tailapply
+entry:
+    11  global    1  ; main
+    12  return
+");
+}
@@ -0,0 +1,297 @@
+use eval::Primitive;
+use errors::error_index::Error;
+
+use super::instruction::{Instruction, Module};
+
+/// Start of every `.lcb` file.
+pub const MAGIC: &[u8; 4] = b"LCB\0";
+
+/// Bumped whenever the encoding changes. Files with another version are
+/// rejected rather than misread.
+pub const VERSION: u16 = 1;
+
+/// Encodes `module` as the bytes of an `.lcb` file: the magic number and
+/// version, then the string table, globals, entry address and code. Numbers
+/// are little endian, and counts and indices take four bytes.
+pub fn encode(module: &Module) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+
+    push_u32(&mut bytes, module.strings.len() as u32);
+    for string in &module.strings {
+        push_u32(&mut bytes, string.len() as u32);
+        bytes.extend_from_slice(string.as_bytes());
+    }
+    push_u32(&mut bytes, module.globals.len() as u32);
+    for &(name, address) in &module.globals {
+        push_u32(&mut bytes, name);
+        push_u32(&mut bytes, address);
+    }
+    push_u32(&mut bytes, module.entry);
+
+    push_u32(&mut bytes, module.code.len() as u32);
+    for &instruction in &module.code {
+        bytes.push(instruction.opcode());
+        match instruction {
+            Instruction::Access(operand) | Instruction::Global(operand) |
+            Instruction::Free(operand) | Instruction::Closure(operand) => push_u32(&mut bytes, operand),
+            Instruction::Int(val) => bytes.extend_from_slice(&val.to_le_bytes()),
+            Instruction::Primitive(primitive) | Instruction::Operation(primitive) =>
+                bytes.push(primitive_code(primitive)),
+            Instruction::Apply | Instruction::TailApply | Instruction::Return => ()
+        }
+    }
+    bytes
+}
+
+/// Decodes an `.lcb` file, checking that the names and addresses in it are
+/// in range and that its code can be run without going wrong.
+pub fn decode(bytes: &[u8]) -> Result<Module, Error> {
+    let mut reader = Reader { bytes, at: 0 };
+    if reader.take(4)? != MAGIC {
+        return Err(invalid("not a bytecode file"));
+    }
+    let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+    if version != VERSION {
+        return Err(invalid(&format!("version {} isn't supported, expected {}", version, VERSION)));
+    }
+
+    let mut module = Module::default();
+    for _ in 0..reader.u32()? {
+        let len = reader.u32()? as usize;
+        let string = String::from_utf8(reader.take(len)?.to_vec())
+            .map_err(|_| invalid("string isn't UTF-8"))?;
+        module.strings.push(string);
+    }
+    for _ in 0..reader.u32()? {
+        module.globals.push((reader.u32()?, reader.u32()?));
+    }
+    module.entry = reader.u32()?;
+    for _ in 0..reader.u32()? {
+        let instruction = match reader.byte()? {
+            0 => Instruction::Access(reader.u32()?),
+            1 => Instruction::Global(reader.u32()?),
+            2 => Instruction::Free(reader.u32()?),
+            3 => Instruction::Int(reader.u32()? as i32),
+            4 => Instruction::Closure(reader.u32()?),
+            5 => Instruction::Primitive(reader.primitive()?),
+            6 => Instruction::Apply,
+            7 => Instruction::TailApply,
+            8 => Instruction::Return,
+            9 => Instruction::Operation(reader.primitive()?),
+            opcode => return Err(invalid(&format!("unknown opcode {}", opcode)))
+        };
+        module.code.push(instruction);
+    }
+    if reader.at != bytes.len() {
+        return Err(invalid("unexpected bytes after the code"));
+    }
+    check(&module)?;
+    Ok(module)
+}
+
+/// Checks that tables are indexed in range, and that every block that can
+/// be run ends, keeps to its stack and scope, and isn't nested in itself.
+/// The VM and `decompile` rely on this.
+fn check(module: &Module) -> Result<(), Error> {
+    let strings = module.strings.len() as u32;
+    let code = module.code.len() as u32;
+    let globals = module.globals.len() as u32;
+    if module.globals.iter().any(|&(name, address)| name >= strings || address >= code) || module.entry >= code {
+        return Err(invalid("address or name out of range"));
+    }
+    for &instruction in &module.code {
+        let in_range = match instruction {
+            Instruction::Global(global) => global < globals,
+            Instruction::Free(string) => string < strings,
+            Instruction::Closure(address) => address < code,
+            _ => true
+        };
+        if !in_range {
+            return Err(invalid(&format!("operand of '{}' out of range", instruction)));
+        }
+    }
+    if !module.code.last().is_some_and(|i| i.ends_block()) {
+        return Err(invalid("code doesn't end with a return"));
+    }
+
+    // Blocks are checked under the fewest binders they can be entered with
+    let mut binders: Vec<Option<usize>> = vec![None; module.code.len()];
+    let mut nested = vec![vec!(); module.code.len()];
+    let mut pending: Vec<(usize, usize)> = module.globals.iter().map(|&(_, address)| (address as usize, 0)).collect();
+    pending.push((module.entry as usize, 0));
+    while let Some((block, depth)) = pending.pop() {
+        if binders[block].is_some_and(|checked| checked <= depth) {
+            continue;
+        }
+        binders[block] = Some(depth);
+        nested[block] = check_block(module, block, depth)?;
+        pending.extend(nested[block].iter().map(|&closure| (closure, depth + 1)));
+    }
+    check_nesting(&nested)
+}
+
+/// Checks the block at `start` under `depth` binders, returning the blocks of
+/// the closures it makes.
+fn check_block(module: &Module, start: usize, depth: usize) -> Result<Vec<usize>, Error> {
+    let mut closures = vec!();
+    let mut stack = 0;
+    for (address, &instruction) in module.code.iter().enumerate().skip(start) {
+        let (pops, pushes) = match instruction {
+            Instruction::Access(index) if index as usize >= depth =>
+                return Err(invalid(&format!("'access {}' at {} is out of scope", index, address))),
+            Instruction::Closure(block) => {
+                closures.push(block as usize);
+                (0, 1)
+            },
+            Instruction::Apply | Instruction::Operation(_) => (2, 1),
+            Instruction::TailApply => (2, 0),
+            Instruction::Return => (1, 0),
+            _ => (0, 1)
+        };
+        if stack < pops {
+            return Err(invalid(&format!("'{}' at {} needs {} values on the stack", instruction.mnemonic(), address, pops)));
+        }
+        stack = stack - pops + pushes;
+        if instruction.ends_block() {
+            if stack > 0 {
+                return Err(invalid(&format!("block at {} leaves values on the stack", start)));
+            }
+            return Ok(closures);
+        }
+    }
+    Err(invalid("code doesn't end with a return"))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit{
+    New,
+    Open,   // On the path being followed
+    Done
+}
+
+/// Checks that no closure's code makes that closure again, which would have
+/// `decompile` read it back forever.
+fn check_nesting(nested: &[Vec<usize>]) -> Result<(), Error> {
+    let mut visits = vec![Visit::New; nested.len()];
+    for root in 0..nested.len() {
+        if visits[root] != Visit::New {
+            continue;
+        }
+        visits[root] = Visit::Open;
+        let mut path = vec![(root, 0)];
+        while let Some((block, next)) = path.pop() {
+            let closure = match nested[block].get(next) {
+                Some(&closure) => closure,
+                None => {
+                    visits[block] = Visit::Done;
+                    continue;
+                }
+            };
+            path.push((block, next + 1));
+            match visits[closure] {
+                Visit::New => {
+                    visits[closure] = Visit::Open;
+                    path.push((closure, 0));
+                },
+                Visit::Open => return Err(invalid(&format!("closure at {} contains itself", closure))),
+                Visit::Done => ()
+            }
+        }
+    }
+    Ok(())
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidBytecode(reason.to_string())
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32){
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+/// The byte a primitive is written as, fixed like `Instruction::opcode` so
+/// that files keep their meaning as primitives are added.
+fn primitive_code(primitive: Primitive) -> u8 {
+    match primitive {
+        Primitive::Add => 0,
+        Primitive::Sub => 1,
+        Primitive::Mul => 2,
+        Primitive::Div => 3,
+        Primitive::Eq => 4,
+        Primitive::Lt => 5
+    }
+}
+
+struct Reader<'a>{
+    bytes: &'a [u8],
+    at: usize
+}
+
+impl<'a> Reader<'a>{
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() - self.at < len {
+            return Err(invalid("file ends early"));
+        }
+        self.at += len;
+        Ok(&self.bytes[self.at - len..self.at])
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn primitive(&mut self) -> Result<Primitive, Error> {
+        let code = self.byte()?;
+        Primitive::all().iter().cloned().find(|&p| primitive_code(p) == code)
+            .ok_or_else(|| invalid(&format!("unknown primitive {}", code)))
+    }
+}
+
+#[test]
+fn file_round_trip(){
+    use lexer::Lexer;
+    use parser::Parser;
+    use super::compile_program;
+
+    let program = Parser::new(Lexer::new("id = \\x. x\nmain = id (sub 2 3) x")).parse().unwrap();
+    let module = compile_program(&program).unwrap();
+    let bytes = encode(&module);
+    assert_eq!(&bytes[..6], b"LCB\0\x01\0");
+    assert_eq!(decode(&bytes), Ok(module));
+
+    let mut future = bytes.clone();
+    future[4] = 2;
+    assert_eq!(decode(&future), Err(Error::InvalidBytecode("version 2 isn't supported, expected 1".to_string())));
+    assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+    assert!(decode(b"#!/bin/sh").is_err());
+
+    let negative = Module { code: vec![Instruction::Int(-3), Instruction::Return], ..Module::default() };
+    assert_eq!(decode(&encode(&negative)), Ok(negative));
+}
+
+#[test]
+fn malformed_files(){
+    use self::Instruction::*;
+
+    let decode_code = |code: Vec<Instruction>| decode(&encode(&Module { code, ..Module::default() }));
+    let reason = |code: Vec<Instruction>| match decode_code(code) {
+        Err(Error::InvalidBytecode(reason)) => reason,
+        other => panic!("expected invalid bytecode, got {:?}", other)
+    };
+    assert_eq!(reason(vec![Access(5), Return]), "'access 5' at 0 is out of scope");
+    assert_eq!(reason(vec![Apply, Return]), "'apply' at 0 needs 2 values on the stack");
+    assert_eq!(reason(vec![Int(1), Int(2), Return]), "block at 0 leaves values on the stack");
+    assert_eq!(reason(vec![Closure(0), Return]), "closure at 0 contains itself");
+    assert_eq!(reason(vec![Closure(2), Return, Closure(4), Return, Closure(2), Return]), "closure at 2 contains itself");
+
+    // A closure's body may use the binder it adds, and no more
+    assert!(decode_code(vec![Closure(2), Return, Access(0), Return]).is_ok());
+    assert!(decode_code(vec![Closure(2), Return, Access(1), Return]).is_err());
+    assert!(decode_code(vec![Int(1), TailApply]).is_err());
+}
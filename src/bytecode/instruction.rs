@@ -0,0 +1,86 @@
+use std::fmt;
+
+use eval::Primitive;
+
+/// One instruction of the stack machine. Operands that name things index
+/// into the tables of the `Module` they belong to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Instruction{
+    Access(u32),            // Push the variable with this De Bruijn index
+    Global(u32),            // Push the value of a global, evaluating it the first time
+    Free(u32),              // Push a free variable, by its name in the string table
+    Int(i32),
+    Closure(u32),           // Push a closure over the current environment, with the code at this address
+    Primitive(Primitive),   // Push a primitive that hasn't been applied yet
+    Apply,                  // Call the function below the argument on top of the stack
+    TailApply,              // Apply in place of the current call, as its last instruction
+    Return,
+    Operation(Primitive)    // Apply a primitive to the top two values
+}
+
+impl Instruction{
+    pub fn opcode(self) -> u8 {
+        match self {
+            Instruction::Access(_) => 0,
+            Instruction::Global(_) => 1,
+            Instruction::Free(_) => 2,
+            Instruction::Int(_) => 3,
+            Instruction::Closure(_) => 4,
+            Instruction::Primitive(_) => 5,
+            Instruction::Apply => 6,
+            Instruction::TailApply => 7,
+            Instruction::Return => 8,
+            Instruction::Operation(_) => 9
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Instruction::Access(_) => "access",
+            Instruction::Global(_) => "global",
+            Instruction::Free(_) => "free",
+            Instruction::Int(_) => "int",
+            Instruction::Closure(_) => "closure",
+            Instruction::Primitive(_) => "prim",
+            Instruction::Apply => "apply",
+            Instruction::TailApply => "tailapply",
+            Instruction::Return => "return",
+            Instruction::Operation(_) => "op"
+        }
+    }
+
+    /// Whether the code of a block ends after this instruction.
+    pub fn ends_block(self) -> bool {
+        matches!(self, Instruction::TailApply | Instruction::Return)
+    }
+}
+
+impl fmt::Display for Instruction{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Access(operand) | Instruction::Global(operand) | Instruction::Free(operand) =>
+                write!(f, "{:<10}{}", self.mnemonic(), operand),
+            Instruction::Int(val) => write!(f, "{:<10}{}", self.mnemonic(), val),
+            Instruction::Closure(address) => write!(f, "{:<10}@{}", self.mnemonic(), address),
+            Instruction::Primitive(primitive) | Instruction::Operation(primitive) =>
+                write!(f, "{:<10}{}", self.mnemonic(), primitive.name()),
+            _ => write!(f, "{}", self.mnemonic())
+        }
+    }
+}
+
+/// A compiled program. The code of every abstraction, global and the entry
+/// point is a block that ends with `Return` or `TailApply`.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Module{
+    pub strings: Vec<String>,
+    pub globals: Vec<(u32, u32)>,   // Carries the name in the string table, and the address of the code
+    pub entry: u32,                 // Address of the code that computes the result
+    pub code: Vec<Instruction>
+}
+
+impl Module{
+    pub fn global_name(&self, global: u32) -> &str {
+        &self.strings[self.globals[global as usize].0 as usize]
+    }
+}
@@ -0,0 +1,45 @@
+pub mod compiler;
+pub mod disassemble;
+pub mod file;
+pub mod instruction;
+pub mod vm;
+
+use std::fs;
+use std::path::Path;
+
+use errors::report::report;
+use parser::export::read_program;
+use parser::{PrettyPrinter, Visitor};
+
+pub use self::compiler::{compile, compile_program};
+pub use self::disassemble::disassemble;
+pub use self::instruction::{Instruction, Module};
+
+/// Compiles the program in `path` and writes it next to it with the `.lcb`
+/// extension. Returns whether that worked.
+pub fn make_file(path: &str) -> bool {
+    let target = Path::new(path).with_extension("lcb");
+    let result = read_program(Path::new(path))
+        .and_then(|program| compile_program(&program).map_err(|e| format!("can't compile {}: {:?}", path, e)))
+        .and_then(|module| fs::write(&target, file::encode(&module))
+            .map_err(|e| format!("can't write {}: {}", target.display(), e)));
+    report(result.map(|()| println!("wrote {}", target.display())))
+}
+
+/// Runs an `.lcb` file on the VM and prints the value of its `main`.
+pub fn exec_file(path: &str) -> bool {
+    let result = read_module(path).and_then(|module| {
+        vm::run(&module, usize::MAX).map_err(|e| format!("can't run {}: {:?}", path, e))
+    });
+    report(result.map(|(value, _)| println!("{}", PrettyPrinter::new(80).visit(&value.to_node()))))
+}
+
+/// Prints the disassembly of an `.lcb` file.
+pub fn disassemble_file(path: &str) -> bool {
+    report(read_module(path).map(|module| print!("{}", disassemble(&module))))
+}
+
+fn read_module(path: &str) -> Result<Module, String> {
+    let bytes = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    file::decode(&bytes).map_err(|e| format!("can't load {}: {:?}", path, e))
+}
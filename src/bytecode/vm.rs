@@ -0,0 +1,267 @@
+use std::rc::Rc;
+
+use errors::error_index::Error;
+use eval::{DbTerm, Primitive, PrimValue};
+
+use super::compiler::decompile;
+use super::instruction::{Instruction, Module};
+
+/// Runs `module` from its entry point and reads back the value it computes.
+/// Steps count calls, primitive operations and globals evaluated, and fail
+/// with `OutOfFuel` past `fuel`.
+pub fn run(module: &Module, fuel: usize) -> Result<(DbTerm, usize), Error> {
+    let mut vm = Vm {
+        module,
+        globals: vec![GlobalState::Unevaluated; module.globals.len()],
+        stack: vec!(),
+        frames: vec!(),
+        steps: 0,
+        fuel
+    };
+    let value = vm.execute()?;
+    Ok((vm.quote(&value), vm.steps))
+}
+
+#[derive(Clone)]
+enum Value{
+    Int(i32),
+    Closure(u32, Env),                  // Carries the address of the abstraction's code
+    Boolean(bool, Option<Rc<Value>>),   // Carries a Church boolean, and its first argument once applied
+    Primitive(Primitive, Vec<Value>),   // Carries the arguments so far
+    Stuck(Rc<Value>, Vec<Value>),       // Carries a head that can't be applied, and its arguments
+    Free(u32)                           // Carries the name in the string table
+}
+
+#[derive(Clone)]
+enum GlobalState{
+    Unevaluated,
+    Evaluating,
+    Done(Value)
+}
+
+/// Values of the variables in scope, innermost first.
+#[derive(Clone, Default)]
+struct Env(Option<Rc<(Value, Env)>>);
+
+impl Env{
+    fn push(&self, value: Value) -> Env {
+        Env(Some(Rc::new((value, self.clone()))))
+    }
+
+    fn lookup(&self, index: u32) -> &Value {
+        let mut env = self;
+        for _ in 0..index {
+            env = &env.0.as_ref().expect("index out of scope").1;
+        }
+        &env.0.as_ref().expect("index out of scope").0
+    }
+}
+
+/// Where to continue once a call returns.
+struct Frame{
+    pc: usize,
+    env: Env,
+    global: Option<u32>     // Set when the call evaluates a global, to remember its value
+}
+
+struct Vm<'a>{
+    module: &'a Module,
+    globals: Vec<GlobalState>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    steps: usize,
+    fuel: usize
+}
+
+impl<'a> Vm<'a>{
+    fn execute(&mut self) -> Result<Value, Error> {
+        let mut pc = self.module.entry as usize;
+        let mut env = Env::default();
+        loop {
+            let instruction = self.module.code[pc];
+            pc += 1;
+            match instruction {
+                Instruction::Access(index) => self.stack.push(env.lookup(index).clone()),
+                Instruction::Int(val) => self.stack.push(Value::Int(val)),
+                Instruction::Free(name) => self.stack.push(Value::Free(name)),
+                Instruction::Closure(address) => self.stack.push(Value::Closure(address, env.clone())),
+                Instruction::Primitive(primitive) => self.stack.push(Value::Primitive(primitive, vec!())),
+                Instruction::Global(global) => match self.globals[global as usize] {
+                    GlobalState::Done(ref value) => self.stack.push(value.clone()),
+                    GlobalState::Evaluating => return Err(Error::InfiniteLoop),
+                    GlobalState::Unevaluated => {
+                        self.tick()?;
+                        self.globals[global as usize] = GlobalState::Evaluating;
+                        self.frames.push(Frame { pc, env, global: Some(global) });
+                        pc = self.module.globals[global as usize].1 as usize;
+                        env = Env::default();
+                    }
+                },
+                Instruction::Apply | Instruction::TailApply => {
+                    let argument = self.stack.pop().expect("apply needs an argument");
+                    let function = self.stack.pop().expect("apply needs a function");
+                    if let Value::Closure(address, ref captured) = function {
+                        self.tick()?;
+                        if instruction == Instruction::Apply {
+                            self.frames.push(Frame { pc, env, global: None });
+                        }
+                        pc = address as usize;
+                        env = captured.push(argument);
+                        continue;
+                    }
+                    let value = self.apply(function, argument)?;
+                    self.stack.push(value);
+                    if instruction == Instruction::TailApply {
+                        match self.ret() {
+                            Some(frame) => { pc = frame.pc; env = frame.env; },
+                            None => return Ok(self.stack.pop().unwrap())
+                        }
+                    }
+                },
+                Instruction::Operation(primitive) => {
+                    let right = self.stack.pop().expect("operation needs two operands");
+                    let left = self.stack.pop().expect("operation needs two operands");
+                    let value = self.operate(primitive, vec!(left, right))?;
+                    self.stack.push(value);
+                },
+                Instruction::Return => match self.ret() {
+                    Some(frame) => { pc = frame.pc; env = frame.env; },
+                    None => return Ok(self.stack.pop().expect("return needs a value"))
+                }
+            }
+        }
+    }
+
+    /// Pops the frame to return to, remembering the value of a global that
+    /// was just evaluated. `None` once the entry point returns.
+    fn ret(&mut self) -> Option<Frame> {
+        let frame = self.frames.pop()?;
+        if let Some(global) = frame.global {
+            let value = self.stack.last().expect("return needs a value").clone();
+            self.globals[global as usize] = GlobalState::Done(value);
+        }
+        Some(frame)
+    }
+
+    /// Applies anything but a closure.
+    fn apply(&mut self, function: Value, argument: Value) -> Result<Value, Error> {
+        Ok(match function {
+            Value::Boolean(val, None) => {
+                self.tick()?;
+                Value::Boolean(val, Some(Rc::new(argument)))
+            },
+            Value::Boolean(val, Some(first)) => {
+                self.tick()?;
+                if val { (*first).clone() } else { argument }
+            },
+            Value::Primitive(primitive, mut args) => {
+                args.push(argument);
+                if args.len() < 2 {
+                    Value::Primitive(primitive, args)
+                } else {
+                    return self.operate(primitive, args);
+                }
+            },
+            Value::Stuck(head, mut args) => {
+                args.push(argument);
+                Value::Stuck(head, args)
+            },
+            head => Value::Stuck(Rc::new(head), vec!(argument))
+        })
+    }
+
+    fn operate(&mut self, primitive: Primitive, args: Vec<Value>) -> Result<Value, Error> {
+        let result = match (&args[0], &args[1]) {
+            (&Value::Int(a), &Value::Int(b)) => primitive.apply(a, b),
+            _ => None
+        };
+        Ok(match result {
+            Some(PrimValue::Int(val)) => {
+                self.tick()?;
+                Value::Int(val)
+            },
+            Some(PrimValue::Bool(val)) => {
+                self.tick()?;
+                Value::Boolean(val, None)
+            },
+            None => Value::Stuck(Rc::new(Value::Primitive(primitive, vec!())), args)
+        })
+    }
+
+    fn tick(&mut self) -> Result<(), Error> {
+        if self.steps == self.fuel {
+            return Err(Error::OutOfFuel(self.fuel));
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    fn quote(&self, value: &Value) -> DbTerm {
+        match *value {
+            Value::Int(val) => DbTerm::Int(val),
+            Value::Free(name) => DbTerm::Free(self.module.strings[name as usize].clone()),
+            Value::Closure(address, ref env) => {
+                let body = decompile(self.module, address);
+                DbTerm::Abstraction(Box::new(self.quote_body(&body, env, 1)))
+            },
+            Value::Boolean(val, None) => DbTerm::Abstraction(Box::new(DbTerm::Abstraction(
+                Box::new(DbTerm::Bound(if val { 1 } else { 0 }))
+            ))),
+            Value::Boolean(val, Some(ref first)) => DbTerm::Abstraction(Box::new(
+                if val { self.quote(first) } else { DbTerm::Bound(0) }
+            )),
+            Value::Primitive(primitive, ref args) => args.iter().fold(
+                DbTerm::Free(primitive.name().to_string()),
+                |function, arg| DbTerm::Application(Box::new(function), Box::new(self.quote(arg)))
+            ),
+            Value::Stuck(ref head, ref args) => args.iter().fold(
+                self.quote(head),
+                |function, arg| DbTerm::Application(Box::new(function), Box::new(self.quote(arg)))
+            )
+        }
+    }
+
+    /// `term` with the variables that point past its `local` binders replaced
+    /// by their values in `env`.
+    fn quote_body(&self, term: &DbTerm, env: &Env, local: usize) -> DbTerm {
+        match *term {
+            DbTerm::Bound(index) if index < local => DbTerm::Bound(index),
+            DbTerm::Bound(index) => self.quote(env.lookup((index - local) as u32)),
+            DbTerm::Abstraction(ref body) => DbTerm::Abstraction(Box::new(self.quote_body(body, env, local + 1))),
+            DbTerm::Application(ref left, ref right) => DbTerm::Application(
+                Box::new(self.quote_body(left, env, local)),
+                Box::new(self.quote_body(right, env, local))
+            ),
+            _ => term.clone()
+        }
+    }
+}
+
+#[test]
+fn vm_matches_call_by_value(){
+    use eval::Strategy;
+    use machine::corpus;
+    use super::compile;
+
+    for &(definitions, input) in corpus::CASES {
+        let (env, term) = corpus::load(definitions, input);
+        let expected = corpus::substitution(&env, &term, Strategy::CallByValue);
+        let actual = compile(&env, &term).and_then(|module| run(&module, corpus::FUEL)).map(|(value, _)| value);
+        assert_eq!(actual, expected, "evaluating {}", input);
+    }
+}
+
+#[test]
+fn vm_runs_recursive_programs(){
+    use lexer::Lexer;
+    use parser::Parser;
+    use machine::corpus;
+    use super::compile_program;
+
+    let program = format!("{}\nmain = add (fact 10) (fib 20)", corpus::ARITHMETIC);
+    let module = compile_program(&Parser::new(Lexer::new(&program)).parse().unwrap()).unwrap();
+    assert_eq!(run(&module, 10_000_000).map(|(value, _)| value), Ok(DbTerm::Int(3628800 + 6765)));
+
+    let module = compile_program(&Parser::new(Lexer::new("main = loop\nloop = loop")).parse().unwrap()).unwrap();
+    assert_eq!(run(&module, 100).map(|(value, _)| value), Err(Error::InfiniteLoop));
+}
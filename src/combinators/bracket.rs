@@ -0,0 +1,101 @@
+use parser::{ParseNode, GrammarItem};
+use errors::error_index::Error;
+
+use super::term::{CTerm, Combinator};
+
+/// Ways to abstract a variable out of a term.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Algorithm{
+    Ski,        // Only S, K and I
+    Turner      // B, C, W and S' as well, and eta reduction
+}
+
+static ALGORITHMS: &[Algorithm] = &[Algorithm::Ski, Algorithm::Turner];
+
+impl Algorithm{
+    pub fn all() -> &'static [Algorithm] {
+        ALGORITHMS
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Ski => "ski",
+            Algorithm::Turner => "turner"
+        }
+    }
+}
+
+/// Compiles an expression to combinators, abstracting the parameter of
+/// every abstraction, innermost first.
+pub fn compile(node: &ParseNode, algorithm: Algorithm) -> Result<CTerm, Error> {
+    match node.entry {
+        GrammarItem::Variable(ref name) => Ok(CTerm::Variable(name.clone())),
+        GrammarItem::LiteralInt(val) => Ok(CTerm::Int(val)),
+        GrammarItem::Application(ref left, ref right) =>
+            Ok(CTerm::app(compile(left, algorithm)?, compile(right, algorithm)?)),
+        GrammarItem::Abstraction(ref param, ref body) => Ok(abstract_var(param, &compile(body, algorithm)?, algorithm)),
+        _ => Err(Error::InvalidAst("only expressions can be compiled to combinators".to_string()))
+    }
+}
+
+/// `[x] term`, a term without `x` that gives `term` when applied to `x`.
+pub fn abstract_var(x: &str, term: &CTerm, algorithm: Algorithm) -> CTerm {
+    use self::Combinator::*;
+
+    if !term.occurs(x) {
+        return CTerm::app(CTerm::comb(K), term.clone());
+    }
+    let (left, right) = match *term {
+        CTerm::Application(ref left, ref right) => (left, right),
+        // The only term that contains `x` without being an application
+        _ => return CTerm::comb(I)
+    };
+    match algorithm {
+        Algorithm::Ski => CTerm::app(
+            CTerm::app(CTerm::comb(S), abstract_var(x, left, algorithm)),
+            abstract_var(x, right, algorithm)
+        ),
+        Algorithm::Turner => {
+            let is_x = **right == CTerm::Variable(x.to_string());
+            match (left.occurs(x), right.occurs(x)) {
+                (false, _) if is_x => (**left).clone(),
+                (false, _) => CTerm::app(CTerm::app(CTerm::comb(B), (**left).clone()), abstract_var(x, right, algorithm)),
+                (true, false) => CTerm::app(CTerm::app(CTerm::comb(C), abstract_var(x, left, algorithm)), (**right).clone()),
+                (true, true) if is_x => CTerm::app(CTerm::comb(W), abstract_var(x, left, algorithm)),
+                (true, true) => {
+                    let (left, right) = (abstract_var(x, left, algorithm), abstract_var(x, right, algorithm));
+                    // S (B c f) g = S' c f g, which keeps c from being
+                    // passed x it doesn't use
+                    if let CTerm::Application(ref bc, ref f) = left {
+                        if let CTerm::Application(ref b, ref c) = **bc {
+                            if **b == CTerm::comb(B) {
+                                return CTerm::app(CTerm::app(CTerm::app(CTerm::comb(SPrime), (**c).clone()), (**f).clone()), right);
+                            }
+                        }
+                    }
+                    CTerm::app(CTerm::app(CTerm::comb(S), left), right)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse(input: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(input)).parse_expr().unwrap()
+}
+
+#[test]
+fn bracket_abstraction(){
+    let compiled = |input, algorithm| compile(&parse(input), algorithm).unwrap().to_string();
+    assert_eq!(compiled("\\x. x", Algorithm::Ski), "I");
+    assert_eq!(compiled("\\x. \\y. x", Algorithm::Ski), "S (K K) I");
+    assert_eq!(compiled("\\x. \\y. y x", Algorithm::Ski), "S (K (S I)) (S (K K) I)");
+    assert_eq!(compiled("\\x. \\y. y x", Algorithm::Turner), "C I");
+    assert_eq!(compiled("\\x. \\y. x", Algorithm::Turner), "K");
+    assert_eq!(compiled("\\f. \\x. f x", Algorithm::Turner), "I");
+    assert_eq!(compiled("\\x. f x x", Algorithm::Turner), "W f");
+    assert_eq!(compiled("\\x. add (f x) (g x)", Algorithm::Turner), "S' add f g");
+}
@@ -0,0 +1,7 @@
+pub mod bracket;
+pub mod reduce;
+pub mod term;
+
+pub use self::bracket::{Algorithm, compile};
+pub use self::reduce::CombinatorReducer;
+pub use self::term::{CTerm, Combinator};
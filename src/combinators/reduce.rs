@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use errors::error_index::Error;
+use eval::{Primitive, PrimValue};
+
+use super::term::{CTerm, Combinator};
+
+/// Reduces combinator terms in normal order: the combinator at the head of
+/// the term is contracted once it has all its arguments, and otherwise the
+/// arguments are reduced, leftmost first. Variables with a definition are
+/// unfolded at the head, and primitives fire once applied to two integers.
+pub struct CombinatorReducer<'a>{
+    definitions: &'a HashMap<String, CTerm>
+}
+
+impl<'a> CombinatorReducer<'a>{
+    pub fn new(definitions: &'a HashMap<String, CTerm>) -> CombinatorReducer<'a> {
+        CombinatorReducer { definitions }
+    }
+
+    /// Reduces `term` until it is normal, or fails after `fuel` steps.
+    /// Returns the normal form and how many steps it took.
+    pub fn normalize(&self, term: &CTerm, fuel: usize) -> Result<(CTerm, usize), Error> {
+        let mut term = term.clone();
+        let mut steps = 0;
+        while let Some(next) = self.step(&term) {
+            if steps == fuel {
+                return Err(Error::OutOfFuel(fuel));
+            }
+            term = next;
+            steps += 1;
+        }
+        Ok((term, steps))
+    }
+
+    pub fn step(&self, term: &CTerm) -> Option<CTerm> {
+        let mut args = vec!();
+        let mut head = term;
+        while let CTerm::Application(ref left, ref right) = *head {
+            args.push((**right).clone());
+            head = left;
+        }
+        args.reverse();
+
+        if let Some(result) = self.contract(head, &args) {
+            return Some(result);
+        }
+        for i in 0..args.len() {
+            if let Some(arg) = self.step(&args[i]) {
+                args[i] = arg;
+                return Some(args.into_iter().fold(head.clone(), CTerm::app));
+            }
+        }
+        None
+    }
+
+    /// Contracts the redex at the head of `head args`, if there is one.
+    fn contract(&self, head: &CTerm, args: &[CTerm]) -> Option<CTerm> {
+        use self::Combinator::*;
+
+        let (result, used) = match *head {
+            CTerm::Combinator(combinator) if args.len() >= combinator.arity() => {
+                let a = |i: usize| args[i].clone();
+                let result = match combinator {
+                    I => a(0),
+                    K => a(0),
+                    S => CTerm::app(CTerm::app(a(0), a(2)), CTerm::app(a(1), a(2))),
+                    B => CTerm::app(a(0), CTerm::app(a(1), a(2))),
+                    C => CTerm::app(CTerm::app(a(0), a(2)), a(1)),
+                    W => CTerm::app(CTerm::app(a(0), a(1)), a(1)),
+                    SPrime => CTerm::app(CTerm::app(a(0), CTerm::app(a(1), a(3))), CTerm::app(a(2), a(3)))
+                };
+                (result, combinator.arity())
+            },
+            CTerm::Variable(ref name) => match self.definitions.get(name) {
+                Some(definition) => (definition.clone(), 0),
+                None => match (Primitive::from_name(name), args) {
+                    (Some(primitive), &[CTerm::Int(a), CTerm::Int(b), ..]) => match primitive.apply(a, b)? {
+                        PrimValue::Int(val) => (CTerm::Int(val), 2),
+                        // Church booleans, \t. \f. t and \t. \f. f
+                        PrimValue::Bool(true) => (CTerm::comb(K), 2),
+                        PrimValue::Bool(false) => (CTerm::app(CTerm::comb(K), CTerm::comb(I)), 2)
+                    },
+                    _ => return None
+                }
+            },
+            _ => return None
+        };
+        Some(args[used..].iter().cloned().fold(result, CTerm::app))
+    }
+}
+
+#[test]
+fn combinators_reduce_like_their_terms(){
+    use lexer::Lexer;
+    use parser::Parser;
+    use super::bracket::{Algorithm, compile};
+
+    let definitions = HashMap::new();
+    let reducer = CombinatorReducer::new(&definitions);
+    let cases = [
+        ("(\\x. \\y. y x) a b", "b a"),
+        ("(\\f. \\g. \\x. f (g x) x) a b c", "a (b c) c"),
+        ("(\\x. x x) (\\y. y) z", "z"),
+        ("(\\n. \\f. \\x. f (n f x)) (\\f. \\x. f x) (add 1) 0", "2"),
+        ("(\\x. \\y. lt x y 10 20) 1 2", "10"),
+    ];
+    for &algorithm in Algorithm::all() {
+        for &(input, expected) in &cases {
+            let term = compile(&Parser::new(Lexer::new(input)).parse_expr().unwrap(), algorithm).unwrap();
+            let (normal, _) = reducer.normalize(&term, 1000).unwrap();
+            assert_eq!(normal.to_string(), expected, "reducing {} with {}", input, algorithm.name());
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+/// Combinators that bracket abstraction compiles to, with the rule each
+/// one reduces by.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Combinator{
+    S,          // S f g x = f x (g x)
+    K,          // K x y = x
+    I,          // I x = x
+    B,          // B f g x = f (g x)
+    C,          // C f g x = f x g
+    W,          // W f x = f x x
+    SPrime      // S' c f g x = c (f x) (g x)
+}
+
+impl Combinator{
+    pub fn name(self) -> &'static str {
+        match self {
+            Combinator::S => "S",
+            Combinator::K => "K",
+            Combinator::I => "I",
+            Combinator::B => "B",
+            Combinator::C => "C",
+            Combinator::W => "W",
+            Combinator::SPrime => "S'"
+        }
+    }
+
+    /// How many arguments it takes to reduce.
+    pub fn arity(self) -> usize {
+        match self {
+            Combinator::I => 1,
+            Combinator::K | Combinator::W => 2,
+            Combinator::S | Combinator::B | Combinator::C => 3,
+            Combinator::SPrime => 4
+        }
+    }
+}
+
+/// A term of combinatory logic. Variables are the names left free, which
+/// are definitions, primitives or unbound names once abstraction is done.
+#[derive(Debug, PartialEq, Clone)]
+pub enum CTerm{
+    Combinator(Combinator),
+    Variable(String),
+    Int(i32),
+    Application(Box<CTerm>, Box<CTerm>)
+}
+
+impl CTerm{
+    pub fn app(left: CTerm, right: CTerm) -> CTerm {
+        CTerm::Application(Box::new(left), Box::new(right))
+    }
+
+    pub fn comb(combinator: Combinator) -> CTerm {
+        CTerm::Combinator(combinator)
+    }
+
+    /// Number of combinators, variables and literals in the term.
+    pub fn size(&self) -> usize {
+        match *self {
+            CTerm::Application(ref left, ref right) => left.size() + right.size(),
+            _ => 1
+        }
+    }
+
+    pub fn occurs(&self, name: &str) -> bool {
+        match *self {
+            CTerm::Variable(ref var) => var == name,
+            CTerm::Application(ref left, ref right) => left.occurs(name) || right.occurs(name),
+            _ => false
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CTerm::Application(_, _) => write!(f, "({})", self),
+            _ => write!(f, "{}", self)
+        }
+    }
+}
+
+impl fmt::Display for CTerm{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CTerm::Combinator(combinator) => write!(f, "{}", combinator.name()),
+            CTerm::Variable(ref name) => write!(f, "{}", name),
+            CTerm::Int(val) => write!(f, "{}", val),
+            CTerm::Application(ref left, ref right) => {
+                write!(f, "{} ", left)?;
+                right.fmt_operand(f)
+            }
+        }
+    }
+}
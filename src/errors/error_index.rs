@@ -1,4 +1,4 @@
-use lexer::Token;
+use lexer::{Token, Span};
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
@@ -6,5 +6,13 @@ pub enum Error {
   UnexpectedEOF,
   IntegerParseError,
   IllegalToken(Token),                  // Carries illegal token
-  ExpectedToken(Token, Token)           // Carries expected, actual
-}
\ No newline at end of file
+  ExpectedToken(Token, Token),          // Carries expected, actual
+  OutOfFuel(usize),                     // Carries the exhausted step budget
+  InfiniteLoop,
+  TooDeep(usize),                       // Carries the nesting limit that was reached
+  InvalidAst(String),                   // Carries what was wrong with the tree
+  InvalidBytecode(String),              // Carries what was wrong with the file
+  InvalidBlc(String),                   // Carries why the bits aren't a term
+  Unencodable(String),                  // Carries what has no BLC encoding
+  UnboundVariable(String, Span, Option<String>) // Carries name, use, closest name in scope
+}
@@ -1 +1,3 @@
-pub mod error_index;
\ No newline at end of file
+pub mod error_index;
+pub mod report;
+pub mod suggest;
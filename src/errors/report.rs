@@ -0,0 +1,11 @@
+/// Prints the error of a command run from the command line, if it failed.
+/// Returns whether it succeeded, for the exit status.
+pub fn report(result: Result<(), String>) -> bool {
+  match result {
+    Ok(()) => true,
+    Err(e) => {
+      eprintln!("Error: {}", e);
+      false
+    }
+  }
+}
@@ -0,0 +1,66 @@
+/// Edit distance between two strings, counting insertions, deletions,
+/// substitutions and transpositions of adjacent characters as one edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+  let a: Vec<char> = a.chars().collect();
+  let b: Vec<char> = b.chars().collect();
+  let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+  for (i, row) in table.iter_mut().enumerate() {
+    row[0] = i;
+  }
+  for (j, cell) in table[0].iter_mut().enumerate() {
+    *cell = j;
+  }
+
+  for i in 1..=a.len() {
+    for j in 1..=b.len() {
+      let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+      let mut best = (table[i - 1][j] + 1)
+        .min(table[i][j - 1] + 1)
+        .min(table[i - 1][j - 1] + cost);
+      if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+        best = best.min(table[i - 2][j - 2] + 1);
+      }
+      table[i][j] = best;
+    }
+  }
+  table[a.len()][b.len()]
+}
+
+/// The candidate closest to `name`, if any is close enough to be a
+/// plausible typo. Ties go to the earliest candidate.
+pub fn closest_match<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+  where I: IntoIterator<Item = &'a str>
+{
+  let limit = (name.chars().count() / 3).max(1);
+  let mut best: Option<(usize, &'a str)> = None;
+  for candidate in candidates {
+    let distance = edit_distance(name, candidate);
+    let better = match best {
+      Some((d, _)) => distance < d,
+      None => true
+    };
+    if distance <= limit && better {
+      best = Some((distance, candidate));
+    }
+  }
+  best.map(|(_, candidate)| candidate)
+}
+
+#[test]
+fn edit_distance_test(){
+  assert_eq!(edit_distance("", ""), 0);
+  assert_eq!(edit_distance("help", "help"), 0);
+  assert_eq!(edit_distance("help", "hlep"), 1);
+  assert_eq!(edit_distance("quit", "quiet"), 1);
+  assert_eq!(edit_distance("kitten", "sitting"), 3);
+  assert_eq!(edit_distance("", "abc"), 3);
+}
+
+#[test]
+fn closest_match_test(){
+  let names = vec!["help", "type", "options", "quit"];
+  assert_eq!(closest_match("hlep", names.clone()), Some("help"));
+  assert_eq!(closest_match("optoins", names.clone()), Some("options"));
+  assert_eq!(closest_match("xyz", names.clone()), None);
+}
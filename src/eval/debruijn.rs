@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use parser::{ParseNode, GrammarItem};
+use errors::error_index::Error;
+
+use super::substitution::readable_name;
+
+/// A term without binder names. A bound variable is replaced by the number
+/// of abstractions between it and its binder, so alpha-equivalent terms
+/// are equal.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum DbTerm{
+    Bound(usize),                   // Carries the index, 0 for the nearest binder
+    Free(String),                   // Carries the name of an unbound variable
+    Int(i32),
+    Abstraction(Box<DbTerm>),
+    Application(Box<DbTerm>, Box<DbTerm>)
+}
+
+impl DbTerm{
+    /// Converts an expression. Definitions and programs have no nameless form.
+    pub fn from_node(node: &ParseNode) -> Result<DbTerm, Error> {
+        from_node_in(node, &mut vec!())
+    }
+
+    /// Converts back to a named term. Binders get fresh names that can't
+    /// capture the free variables or each other.
+    pub fn to_node(&self) -> ParseNode {
+        let free = self.free_variables().into_iter().collect();
+        self.to_node_in(&mut vec!(), &free)
+    }
+
+    fn to_node_in(&self, scope: &mut Vec<String>, free: &HashSet<String>) -> ParseNode {
+        match *self {
+            DbTerm::Bound(index) => ParseNode::variable(&scope[scope.len() - 1 - index]),
+            DbTerm::Free(ref name) => ParseNode::variable(name),
+            DbTerm::Int(val) => ParseNode::literal_int(val),
+            DbTerm::Abstraction(ref body) => {
+                let avoid = free.iter().chain(scope.iter()).cloned().collect();
+                let name = readable_name(&avoid);
+                scope.push(name.clone());
+                let body = body.to_node_in(scope, free);
+                scope.pop();
+                ParseNode::abstraction(&name, body)
+            },
+            DbTerm::Application(ref left, ref right) =>
+                ParseNode::application(left.to_node_in(scope, free), right.to_node_in(scope, free))
+        }
+    }
+
+    /// Unbound names, in order of first occurrence.
+    pub fn free_variables(&self) -> Vec<String> {
+        let mut free = vec!();
+        self.collect_free(&mut free);
+        free
+    }
+
+    fn collect_free(&self, free: &mut Vec<String>){
+        match *self {
+            DbTerm::Free(ref name) if !free.contains(name) => free.push(name.clone()),
+            DbTerm::Abstraction(ref body) => body.collect_free(free),
+            DbTerm::Application(ref left, ref right) => {
+                left.collect_free(free);
+                right.collect_free(free);
+            },
+            _ => ()
+        }
+    }
+
+    /// Eta reduces every `λ M 0` where `M` doesn't use the abstraction's own
+    /// variable, innermost first.
+    pub fn eta_normal(&self) -> DbTerm {
+        match *self {
+            DbTerm::Abstraction(ref body) => {
+                let body = body.eta_normal();
+                if let DbTerm::Application(ref function, ref argument) = body {
+                    if **argument == DbTerm::Bound(0) && !function.uses(0) {
+                        return function.unshift(0);
+                    }
+                }
+                DbTerm::Abstraction(Box::new(body))
+            },
+            DbTerm::Application(ref left, ref right) =>
+                DbTerm::Application(Box::new(left.eta_normal()), Box::new(right.eta_normal())),
+            _ => self.clone()
+        }
+    }
+
+    /// Whether the binder `depth` abstractions out is used.
+    fn uses(&self, depth: usize) -> bool {
+        match *self {
+            DbTerm::Bound(index) => index == depth,
+            DbTerm::Abstraction(ref body) => body.uses(depth + 1),
+            DbTerm::Application(ref left, ref right) => left.uses(depth) || right.uses(depth),
+            _ => false
+        }
+    }
+
+    /// Removes the unused binder `depth` abstractions out, so indices that
+    /// point past it shrink by one.
+    fn unshift(&self, depth: usize) -> DbTerm {
+        match *self {
+            DbTerm::Bound(index) if index > depth => DbTerm::Bound(index - 1),
+            DbTerm::Abstraction(ref body) => DbTerm::Abstraction(Box::new(body.unshift(depth + 1))),
+            DbTerm::Application(ref left, ref right) =>
+                DbTerm::Application(Box::new(left.unshift(depth)), Box::new(right.unshift(depth))),
+            _ => self.clone()
+        }
+    }
+
+    /// Writes an application without parentheses around its spine. Only
+    /// the argument at the right end can be an abstraction without them.
+    fn fmt_application(&self, f: &mut fmt::Formatter, rightmost: bool) -> fmt::Result {
+        match *self {
+            DbTerm::Application(ref left, ref right) => {
+                match **left {
+                    DbTerm::Application(_, _) => left.fmt_application(f, false)?,
+                    _ => left.fmt_operand(f, false)?
+                }
+                write!(f, " ")?;
+                right.fmt_operand(f, rightmost)
+            },
+            _ => self.fmt_operand(f, rightmost)
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter, rightmost: bool) -> fmt::Result {
+        match *self {
+            DbTerm::Application(_, _) => write!(f, "({})", self),
+            DbTerm::Abstraction(_) if !rightmost => write!(f, "({})", self),
+            _ => write!(f, "{}", self)
+        }
+    }
+}
+
+fn from_node_in(node: &ParseNode, scope: &mut Vec<String>) -> Result<DbTerm, Error> {
+    match node.entry {
+        GrammarItem::Variable(ref name) => Ok(match scope.iter().rev().position(|bound| bound == name) {
+            Some(index) => DbTerm::Bound(index),
+            None => DbTerm::Free(name.clone())
+        }),
+        GrammarItem::LiteralInt(val) => Ok(DbTerm::Int(val)),
+        GrammarItem::Abstraction(ref param, ref body) => {
+            scope.push(param.clone());
+            let body = from_node_in(body, scope);
+            scope.pop();
+            Ok(DbTerm::Abstraction(Box::new(body?)))
+        },
+        GrammarItem::Application(ref left, ref right) => Ok(DbTerm::Application(
+            Box::new(from_node_in(left, scope)?),
+            Box::new(from_node_in(right, scope)?)
+        )),
+        GrammarItem::Assignment(_, _) | GrammarItem::Program(_) =>
+            Err(Error::InvalidAst("only expressions have a De Bruijn form".to_string()))
+    }
+}
+
+/// Written like source, with indices for bound variables and a bare `λ`
+/// for each abstraction, for example `λ λ 1 (0 y)`. Integers are marked
+/// with `#` so they can't be mistaken for indices.
+impl fmt::Display for DbTerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DbTerm::Bound(index) => write!(f, "{}", index),
+            DbTerm::Free(ref name) => write!(f, "{}", name),
+            DbTerm::Int(val) => write!(f, "#{}", val),
+            DbTerm::Abstraction(ref body) => write!(f, "λ {}", body),
+            DbTerm::Application(_, _) => self.fmt_application(f, true)
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse(input: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(input)).parse_expr().unwrap()
+}
+
+#[test]
+fn debruijn_indices(){
+    let term = DbTerm::from_node(&parse(r#"\f. \x. f (f x) y 3"#)).unwrap();
+    assert_eq!(term.to_string(), "λ λ 1 (1 0) y #3");
+    assert_eq!(term.free_variables(), vec!["y".to_string()]);
+    assert_eq!(DbTerm::from_node(&parse(r#"\a. a"#)), DbTerm::from_node(&parse(r#"\b. b"#)));
+    assert_eq!(DbTerm::from_node(&parse(r#"\x. \x. x"#)).unwrap().to_string(), "λ λ 0");
+    assert_eq!(DbTerm::from_node(&parse(r#"f (\x. x) (\y. y)"#)).unwrap().to_string(), "f (λ 0) λ 0");
+}
+
+#[test]
+fn debruijn_eta_normal(){
+    let eta = |input| DbTerm::from_node(&parse(input)).unwrap().eta_normal().to_node();
+    assert_eq!(eta(r#"\x. f x"#), parse("f"));
+    assert_eq!(eta(r#"\x. \y. f x y"#), parse("f"));
+    assert_eq!(eta(r#"\x. \y. y x"#), parse(r#"\x. \y. y x"#));
+    assert_eq!(eta(r#"\g. \x. g x"#), parse(r#"\x. x"#));
+}
+
+#[test]
+fn debruijn_names_avoid_capture(){
+    let term = DbTerm::from_node(&parse(r#"\a. \b. x a b"#)).unwrap();
+    assert_eq!(term.to_node(), parse(r#"\y. \z. x y z"#));
+    assert_eq!(DbTerm::from_node(&term.to_node()).unwrap(), term);
+}
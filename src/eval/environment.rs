@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+
+use parser::ParseNode;
+use parser::GrammarItem;
+
+/// Top level definitions that are visible to the evaluators.
+#[derive(Default, Clone)]
+pub struct Environment{
+    definitions: BTreeMap<String, ParseNode>
+}
+
+impl Environment{
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    pub fn define(&mut self, name: &str, value: ParseNode){
+        self.definitions.insert(name.to_string(), value);
+    }
+
+    /// Defines every assignment in `node`, which may be either a single
+    /// `Assignment` or a whole `Program`.
+    pub fn load(&mut self, node: &ParseNode){
+        match node.entry {
+            GrammarItem::Assignment(ref name, ref expr) => self.define(name, (**expr).clone()),
+            GrammarItem::Program(ref children) => {
+                for child in children {
+                    self.load(child);
+                }
+            },
+            _ => ()
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ParseNode> {
+        self.definitions.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.definitions.contains_key(name)
+    }
+
+    /// Definitions in alphabetical order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &ParseNode)> {
+        self.definitions.iter()
+    }
+}
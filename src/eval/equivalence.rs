@@ -0,0 +1,78 @@
+use parser::ParseNode;
+use errors::error_index::Error;
+
+use super::{Branch, DbTerm, Position, Reducer, Strategy, Environment};
+
+/// How two terms relate.
+#[derive(Debug, PartialEq)]
+pub enum Equivalence{
+    Alpha,                                  // Equal up to the names of binders
+    Convertible(ParseNode),                 // Carries the beta-eta normal form both reach
+    Different(Position, ParseNode, ParseNode)   // Carries where the normal forms differ, and their subterms there
+}
+
+/// The position of the first subterm, in preorder, at which `a` and `b`
+/// differ, or `None` if they are equal.
+pub fn first_difference(a: &DbTerm, b: &DbTerm) -> Option<Position> {
+    match (a, b) {
+        (DbTerm::Abstraction(a), DbTerm::Abstraction(b)) =>
+            first_difference(a, b).map(|position| position.under(Branch::Body)),
+        (DbTerm::Application(f, x), DbTerm::Application(g, y)) =>
+            first_difference(f, g).map(|position| position.under(Branch::Function))
+                .or_else(|| first_difference(x, y).map(|position| position.under(Branch::Argument))),
+        _ if a == b => None,
+        _ => Some(Position::root())
+    }
+}
+
+/// Decides whether `a` and `b` are alpha-equivalent, or failing that whether
+/// they are beta-eta convertible. Convertibility is decided by comparing
+/// normal forms, found by normal order reduction in `env`, so it fails
+/// with `OutOfFuel` when either term takes more than `fuel` steps.
+pub fn equivalence(env: &Environment, a: &ParseNode, b: &ParseNode, fuel: usize) -> Result<Equivalence, Error> {
+    if DbTerm::from_node(a)? == DbTerm::from_node(b)? {
+        return Ok(Equivalence::Alpha);
+    }
+
+    let reducer = Reducer::new(env, Strategy::Normal);
+    let a = DbTerm::from_node(&reducer.normalize(a, fuel)?.term)?.eta_normal();
+    let b = DbTerm::from_node(&reducer.normalize(b, fuel)?.term)?.eta_normal();
+    match first_difference(&a, &b) {
+        None => Ok(Equivalence::Convertible(a.to_node())),
+        Some(position) => {
+            let (a, b) = (a.to_node(), b.to_node());
+            let left = position.subterm(&a).unwrap().clone();
+            let right = position.subterm(&b).unwrap().clone();
+            Ok(Equivalence::Different(position, left, right))
+        }
+    }
+}
+
+#[cfg(test)]
+fn check(a: &str, b: &str) -> Result<Equivalence, Error> {
+    use lexer::Lexer;
+    use parser::Parser;
+    let parse = |input| Parser::new(Lexer::new(input)).parse_expr().unwrap();
+    let mut env = Environment::new();
+    env.define("id", parse(r#"\x. x"#));
+    env.define("omega", parse(r#"(\x. x x) (\x. x x)"#));
+    equivalence(&env, &parse(a), &parse(b), 100)
+}
+
+#[test]
+fn equivalence_alpha_and_convertible(){
+    use parser::GrammarItem;
+
+    assert_eq!(check(r#"\a. \b. a b"#, r#"\x. \y. x y"#), Ok(Equivalence::Alpha));
+    assert_eq!(check(r#"id (\f. \x. f x)"#, r#"\g. g"#), Ok(Equivalence::Convertible(ParseNode::abstraction("x", ParseNode::variable("x")))));
+    assert_eq!(check("add 1 2", "3"), Ok(Equivalence::Convertible(ParseNode::literal_int(3))));
+    match check(r#"\x. \y. x"#, r#"\x. \y. y"#) {
+        Ok(Equivalence::Different(position, left, right)) => {
+            assert_eq!(position.to_string(), "body.body");
+            assert_eq!((left.entry, right.entry),
+                (GrammarItem::Variable("x".to_string()), GrammarItem::Variable("y".to_string())));
+        },
+        other => panic!("expected a difference, got {:?}", other)
+    }
+    assert_eq!(check("omega", "id"), Err(Error::OutOfFuel(100)));
+}
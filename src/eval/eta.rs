@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+use parser::{ParseNode, GrammarItem, Type};
+
+use super::substitution::{free_variables, readable_name};
+
+/// Contracts `term` if it is an eta redex, `\x. f x` with `x` not free in
+/// `f`, giving `f`.
+pub fn eta_contract(term: &ParseNode) -> Option<ParseNode> {
+    if let GrammarItem::Abstraction(ref param, ref body) = term.entry {
+        if let GrammarItem::Application(ref function, ref argument) = body.entry {
+            if let GrammarItem::Variable(ref name) = argument.entry {
+                if name == param && !free_variables(function).contains(param) {
+                    return Some((**function).clone());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Eta reduces every redex in `term`, innermost first, so that no eta
+/// redex is left.
+pub fn eta_reduce(term: &ParseNode) -> ParseNode {
+    let entry = match term.entry {
+        GrammarItem::Abstraction(ref param, ref body) => {
+            let reduced = ParseNode::new(
+                GrammarItem::Abstraction(param.clone(), Box::new(eta_reduce(body))),
+                term.node_type.clone()
+            );
+            return eta_contract(&reduced).unwrap_or(reduced);
+        },
+        GrammarItem::Application(ref left, ref right) =>
+            GrammarItem::Application(Box::new(eta_reduce(left)), Box::new(eta_reduce(right))),
+        GrammarItem::Assignment(ref name, ref expr) =>
+            GrammarItem::Assignment(name.clone(), Box::new(eta_reduce(expr))),
+        GrammarItem::Program(ref children) =>
+            GrammarItem::Program(children.iter().map(eta_reduce).collect()),
+        GrammarItem::Variable(_) | GrammarItem::LiteralInt(_) => return term.clone()
+    };
+    ParseNode::new(entry, term.node_type.clone())
+}
+
+/// Eta expands `term`, taken to have type `ty`, until it takes one
+/// abstraction per argument of the type. New arguments are expanded by
+/// their own types, so `f : (A -> B) -> C` becomes `\x. f (\y. x y)`.
+/// Existing abstractions are kept and only their bodies are expanded.
+pub fn eta_expand(term: &ParseNode, ty: &Type) -> ParseNode {
+    let mut avoid = free_variables(term);
+    expand(term, ty, &mut avoid)
+}
+
+fn expand(term: &ParseNode, ty: &Type, avoid: &mut HashSet<String>) -> ParseNode {
+    match *ty {
+        Type::Abstraction(ref from, ref to) => match term.entry {
+            GrammarItem::Abstraction(ref param, ref body) => {
+                avoid.insert(param.clone());
+                ParseNode::abstraction(param, expand(body, to, avoid))
+            },
+            _ => {
+                let name = readable_name(avoid);
+                avoid.insert(name.clone());
+                let argument = expand(&ParseNode::variable(&name), from, avoid);
+                let body = expand(&ParseNode::application(term.clone(), argument), to, avoid);
+                ParseNode::abstraction(&name, body)
+            }
+        },
+        _ => term.clone()
+    }
+}
+
+#[cfg(test)]
+fn parse(input: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(input)).parse_expr().unwrap()
+}
+
+#[test]
+fn eta_reduce_innermost_first(){
+    assert_eq!(eta_reduce(&parse(r#"\x. f x"#)), parse("f"));
+    assert_eq!(eta_reduce(&parse(r#"\x. \y. f x y"#)), parse("f"));
+    assert_eq!(eta_reduce(&parse(r#"\x. x x"#)), parse(r#"\x. x x"#));
+    assert_eq!(eta_reduce(&parse(r#"g (\y. (\x. h x) y)"#)), parse("g h"));
+}
+
+#[test]
+fn eta_expand_follows_type(){
+    use lexer::Lexer;
+    use parser::Parser;
+    let ty = |input| Parser::new(Lexer::new(input)).parse_type_expr().unwrap();
+
+    assert_eq!(eta_expand(&parse("f"), &ty("A -> B -> C")), parse(r#"\x. \y. f x y"#));
+    assert_eq!(eta_expand(&parse("x"), &ty("(A -> B) -> C")), parse(r#"\y. x (\z. y z)"#));
+    assert_eq!(eta_expand(&parse(r#"\x. g x"#), &ty("A -> B -> C")), parse(r#"\x. \y. g x y"#));
+    assert_eq!(eta_expand(&parse("f"), &ty("A")), parse("f"));
+    assert_eq!(eta_reduce(&eta_expand(&parse("f"), &ty("(A -> B) -> A -> B"))), parse("f"));
+}
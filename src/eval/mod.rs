@@ -0,0 +1,19 @@
+pub mod debruijn;
+pub mod environment;
+pub mod equivalence;
+pub mod eta;
+pub mod nbe;
+pub mod position;
+pub mod primitives;
+pub mod reduce;
+pub mod reduction_graph;
+pub mod substitution;
+
+pub use self::debruijn::DbTerm;
+pub use self::environment::Environment;
+pub use self::equivalence::{Equivalence, equivalence};
+pub use self::eta::{eta_reduce, eta_expand};
+pub use self::position::{Branch, Position};
+pub use self::primitives::{Primitive, PrimValue};
+pub use self::reduce::{Reducer, Reduction, Strategy};
+pub use self::reduction_graph::ReductionGraph;
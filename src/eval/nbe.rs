@@ -0,0 +1,296 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::thread;
+
+use parser::ParseNode;
+use errors::error_index::Error;
+
+use super::{DbTerm, Environment, Primitive, PrimValue, Reduction};
+
+/// Normalizes `term` by evaluation: it is evaluated into Rust closures and
+/// neutral values, which are read back into a beta normal term, going under
+/// binders by applying closures to fresh variables. Arguments are evaluated
+/// by need, so a normal form is found whenever normal order would find one.
+/// Steps count beta reductions and primitive applications, and fail with
+/// `OutOfFuel` past `fuel`.
+pub fn normalize(env: &Environment, term: &ParseNode, fuel: usize) -> Result<Reduction, Error> {
+    // Evaluation and read back recurse on the Rust stack, once per level of
+    // nesting, so they get a stack that fits `MAX_DEPTH` levels
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || normalize_here(env, term, fuel))
+            .expect("can't start a thread to normalize on")
+            .join()
+            .expect("normalizing panicked")
+    })
+}
+
+/// How deeply evaluation and read back may nest before giving up.
+const MAX_DEPTH: usize = 100_000;
+
+const STACK_SIZE: usize = 1 << 30;
+
+fn normalize_here(env: &Environment, term: &ParseNode, fuel: usize) -> Result<Reduction, Error> {
+    let mut globals = HashMap::new();
+    for (name, value) in env.iter() {
+        let code = Code::new(&DbTerm::from_node(value)?);
+        globals.insert(name.clone(), Thunk::delayed(code, Env::default()));
+    }
+    let context = Context { globals, steps: Cell::new(0), fuel, depth: Cell::new(0) };
+
+    let code = Code::new(&DbTerm::from_node(term)?);
+    let value = eval(&context, &code, &Env::default())?;
+    let normal = quote(&context, &value, 0)?;
+    Ok(Reduction { term: normal.to_node(), steps: context.steps.get() })
+}
+
+/// The terms that are evaluated, shared so that closures can hold on to
+/// their bodies without copying them.
+enum Code{
+    Bound(usize),
+    Free(String),
+    Int(i32),
+    Abstraction(Rc<Code>),
+    Application(Rc<Code>, Rc<Code>)
+}
+
+impl Code{
+    fn new(term: &DbTerm) -> Rc<Code> {
+        Rc::new(match *term {
+            DbTerm::Bound(index) => Code::Bound(index),
+            DbTerm::Free(ref name) => Code::Free(name.clone()),
+            DbTerm::Int(val) => Code::Int(val),
+            DbTerm::Abstraction(ref body) => Code::Abstraction(Code::new(body)),
+            DbTerm::Application(ref left, ref right) => Code::Application(Code::new(left), Code::new(right))
+        })
+    }
+}
+
+type Function = Rc<dyn Fn(&Context, Thunk) -> Result<Value, Error>>;
+
+#[derive(Clone)]
+enum Value{
+    Function(Function),
+    Int(i32),
+    Neutral(Head, Vec<Thunk>)       // Carries a head that can't be applied, and its arguments
+}
+
+#[derive(Clone)]
+enum Head{
+    Level(usize),                   // Carries the variable's binder, counting from the outside in
+    Free(String),
+    Primitive(Primitive),
+    Int(i32)
+}
+
+#[derive(Clone)]
+struct Thunk(Rc<RefCell<ThunkState>>);
+
+enum ThunkState{
+    Delayed(Rc<Code>, Env),
+    Forcing,
+    Done(Value)
+}
+
+impl Thunk{
+    fn delayed(code: Rc<Code>, env: Env) -> Thunk {
+        Thunk(Rc::new(RefCell::new(ThunkState::Delayed(code, env))))
+    }
+
+    fn done(value: Value) -> Thunk {
+        Thunk(Rc::new(RefCell::new(ThunkState::Done(value))))
+    }
+
+    fn force(&self, context: &Context) -> Result<Value, Error> {
+        let state = self.0.replace(ThunkState::Forcing);
+        let value = match state {
+            ThunkState::Done(value) => value,
+            ThunkState::Forcing => return Err(Error::InfiniteLoop),
+            ThunkState::Delayed(code, env) => eval(context, &code, &env)?
+        };
+        *self.0.borrow_mut() = ThunkState::Done(value.clone());
+        Ok(value)
+    }
+}
+
+/// Arguments of the enclosing closures, innermost first.
+#[derive(Clone, Default)]
+struct Env(Option<Rc<(Thunk, Env)>>);
+
+impl Env{
+    fn push(&self, thunk: Thunk) -> Env {
+        Env(Some(Rc::new((thunk, self.clone()))))
+    }
+
+    fn lookup(&self, index: usize) -> &Thunk {
+        let mut env = self;
+        for _ in 0..index {
+            env = &env.0.as_ref().expect("index out of scope").1;
+        }
+        &env.0.as_ref().expect("index out of scope").0
+    }
+}
+
+struct Context{
+    globals: HashMap<String, Thunk>,
+    steps: Cell<usize>,
+    fuel: usize,
+    depth: Cell<usize>              // Calls of `eval` and `quote` in progress
+}
+
+impl Context{
+    /// Runs `f` one level deeper, failing instead of running out of stack.
+    fn nested<T, F: FnOnce() -> Result<T, Error>>(&self, f: F) -> Result<T, Error> {
+        if self.depth.get() == MAX_DEPTH {
+            return Err(Error::TooDeep(MAX_DEPTH));
+        }
+        self.depth.set(self.depth.get() + 1);
+        let result = f();
+        self.depth.set(self.depth.get() - 1);
+        result
+    }
+
+    fn tick(&self) -> Result<(), Error> {
+        if self.steps.get() == self.fuel {
+            return Err(Error::OutOfFuel(self.fuel));
+        }
+        self.steps.set(self.steps.get() + 1);
+        Ok(())
+    }
+}
+
+fn eval(context: &Context, code: &Rc<Code>, env: &Env) -> Result<Value, Error> {
+    context.nested(|| eval_code(context, code, env))
+}
+
+fn eval_code(context: &Context, code: &Rc<Code>, env: &Env) -> Result<Value, Error> {
+    match **code {
+        Code::Bound(index) => env.lookup(index).force(context),
+        Code::Int(val) => Ok(Value::Int(val)),
+        Code::Free(ref name) => match context.globals.get(name) {
+            Some(thunk) => thunk.force(context),
+            None => Ok(Value::Neutral(match Primitive::from_name(name) {
+                Some(primitive) => Head::Primitive(primitive),
+                None => Head::Free(name.clone())
+            }, vec!()))
+        },
+        Code::Abstraction(ref body) => {
+            let (body, env) = (body.clone(), env.clone());
+            Ok(Value::Function(Rc::new(move |context, argument| eval(context, &body, &env.push(argument)))))
+        },
+        Code::Application(ref left, ref right) => {
+            let function = eval(context, left, env)?;
+            // A variable is passed on as it is, so its value stays shared
+            let argument = match **right {
+                Code::Bound(index) => env.lookup(index).clone(),
+                _ => Thunk::delayed(right.clone(), env.clone())
+            };
+            apply(context, function, argument)
+        }
+    }
+}
+
+fn apply(context: &Context, function: Value, argument: Thunk) -> Result<Value, Error> {
+    match function {
+        Value::Function(function) => {
+            context.tick()?;
+            function(context, argument)
+        },
+        Value::Int(val) => Ok(Value::Neutral(Head::Int(val), vec!(argument))),
+        Value::Neutral(head, mut args) => {
+            args.push(argument);
+            if let Head::Primitive(primitive) = head {
+                if args.len() == 2 {
+                    if let Some(result) = operate(context, primitive, &args)? {
+                        return Ok(result);
+                    }
+                }
+            }
+            Ok(Value::Neutral(head, args))
+        }
+    }
+}
+
+/// Applies a saturated primitive, or `None` if it is stuck.
+fn operate(context: &Context, primitive: Primitive, args: &[Thunk]) -> Result<Option<Value>, Error> {
+    let result = match (args[0].force(context)?, args[1].force(context)?) {
+        (Value::Int(a), Value::Int(b)) => primitive.apply(a, b),
+        _ => None
+    };
+    Ok(match result {
+        Some(result) => {
+            context.tick()?;
+            Some(match result {
+                PrimValue::Int(val) => Value::Int(val),
+                PrimValue::Bool(val) => boolean(val)
+            })
+        },
+        None => None
+    })
+}
+
+/// The Church boolean `\t. \f. t` or `\t. \f. f`.
+fn boolean(val: bool) -> Value {
+    Value::Function(Rc::new(move |_, first: Thunk| {
+        Ok(Value::Function(Rc::new(move |context, second: Thunk| {
+            if val { first.force(context) } else { second.force(context) }
+        })))
+    }))
+}
+
+/// Reads `value` back as a normal term, under `depth` binders.
+fn quote(context: &Context, value: &Value, depth: usize) -> Result<DbTerm, Error> {
+    context.nested(|| quote_value(context, value, depth))
+}
+
+fn quote_value(context: &Context, value: &Value, depth: usize) -> Result<DbTerm, Error> {
+    match *value {
+        Value::Int(val) => Ok(DbTerm::Int(val)),
+        Value::Function(ref function) => {
+            let variable = Thunk::done(Value::Neutral(Head::Level(depth), vec!()));
+            let body = function(context, variable)?;
+            Ok(DbTerm::Abstraction(Box::new(quote(context, &body, depth + 1)?)))
+        },
+        Value::Neutral(ref head, ref args) => {
+            let mut term = match *head {
+                Head::Level(level) => DbTerm::Bound(depth - 1 - level),
+                Head::Free(ref name) => DbTerm::Free(name.clone()),
+                Head::Primitive(primitive) => DbTerm::Free(primitive.name().to_string()),
+                Head::Int(val) => DbTerm::Int(val)
+            };
+            for arg in args {
+                let arg = quote(context, &arg.force(context)?, depth)?;
+                term = DbTerm::Application(Box::new(term), Box::new(arg));
+            }
+            Ok(term)
+        }
+    }
+}
+
+#[test]
+fn nbe_agrees_with_normal_order(){
+    use machine::corpus;
+
+    for &(definitions, input) in corpus::CASES {
+        let (env, term) = corpus::load(definitions, input);
+        let expected = corpus::normal_form(&env, &term);
+        let actual = normalize(&env, &term, corpus::FUEL).and_then(|r| DbTerm::from_node(&r.term));
+        assert_eq!(actual, expected, "normalizing {}", input);
+    }
+}
+
+#[test]
+fn nbe_church_factorial(){
+    use machine::corpus;
+
+    let (env, term) = corpus::load(corpus::CHURCH_RECURSION, "fact (succ (succ (succ three)))");
+    let normal = normalize(&env, &term, 10_000_000).map(|r| DbTerm::from_node(&r.term).unwrap());
+    let numeral = (0..720).fold(ParseNode::variable("x"), |x, _| ParseNode::application(ParseNode::variable("f"), x));
+    let expected = ParseNode::abstraction("f", ParseNode::abstraction("x", numeral));
+    assert_eq!(normal, DbTerm::from_node(&expected));
+
+    let (env, term) = corpus::load(corpus::CHURCH_RECURSION, "y");
+    assert!(normalize(&env, &term, 10_000_000).is_err());
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+use parser::{ParseNode, GrammarItem};
+
+/// A step from a node to one of its children.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Branch{
+    Function,           // Left side of an application
+    Argument,           // Right side of an application
+    Body                // Body of an abstraction
+}
+
+/// Where a subterm sits in a term, as the path taken from the root.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct Position(pub Vec<Branch>);
+
+impl Position{
+    pub fn root() -> Position {
+        Position(vec!())
+    }
+
+    /// This position, seen from the parent that `branch` leads out of.
+    pub fn under(mut self, branch: Branch) -> Position {
+        self.0.insert(0, branch);
+        self
+    }
+
+    /// The subterm of `term` at this position, if there is one.
+    pub fn subterm<'a>(&self, term: &'a ParseNode) -> Option<&'a ParseNode> {
+        self.0.iter().try_fold(term, |node, branch| match (&node.entry, branch) {
+            (GrammarItem::Application(left, _), Branch::Function) => Some(&**left),
+            (GrammarItem::Application(_, right), Branch::Argument) => Some(&**right),
+            (GrammarItem::Abstraction(_, body), Branch::Body) => Some(&**body),
+            _ => None
+        })
+    }
+}
+
+/// Positions are written as their branches joined by `.`, for example
+/// `arg.body`, and the root as `ε`.
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "ε");
+        }
+        let names: Vec<&str> = self.0.iter().map(|branch| match *branch {
+            Branch::Function => "fun",
+            Branch::Argument => "arg",
+            Branch::Body => "body"
+        }).collect();
+        write!(f, "{}", names.join("."))
+    }
+}
@@ -0,0 +1,90 @@
+use parser::ParseNode;
+
+/// Built in binary operations over `LiteralInt`s. A primitive only fires
+/// once both of its arguments are integers, and only when its name hasn't
+/// been shadowed by a definition.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Primitive{
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Lt
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PrimValue{
+    Int(i32),
+    Bool(bool)
+}
+
+static PRIMITIVES: &[Primitive] = &[
+    Primitive::Add, Primitive::Sub, Primitive::Mul,
+    Primitive::Div, Primitive::Eq, Primitive::Lt
+];
+
+impl Primitive{
+    pub fn all() -> &'static [Primitive] {
+        PRIMITIVES
+    }
+
+    pub fn from_name(name: &str) -> Option<Primitive> {
+        PRIMITIVES.iter().cloned().find(|p| p.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Primitive::Add => "add",
+            Primitive::Sub => "sub",
+            Primitive::Mul => "mul",
+            Primitive::Div => "div",
+            Primitive::Eq => "eq",
+            Primitive::Lt => "lt"
+        }
+    }
+
+    /// Applies the primitive, or `None` on overflow and division by zero,
+    /// which leaves the application stuck.
+    pub fn apply(self, a: i32, b: i32) -> Option<PrimValue> {
+        match self {
+            Primitive::Add => a.checked_add(b).map(PrimValue::Int),
+            Primitive::Sub => a.checked_sub(b).map(PrimValue::Int),
+            Primitive::Mul => a.checked_mul(b).map(PrimValue::Int),
+            Primitive::Div => a.checked_div(b).map(PrimValue::Int),
+            Primitive::Eq => Some(PrimValue::Bool(a == b)),
+            Primitive::Lt => Some(PrimValue::Bool(a < b))
+        }
+    }
+}
+
+impl PrimValue{
+    /// The value as a term. Booleans are Church encoded, `\t. \f. t` for
+    /// true and `\t. \f. f` for false.
+    pub fn to_node(self) -> ParseNode {
+        match self {
+            PrimValue::Int(val) => ParseNode::literal_int(val),
+            PrimValue::Bool(val) => ParseNode::abstraction("t",
+                ParseNode::abstraction("f",
+                    ParseNode::variable(if val { "t" } else { "f" })))
+        }
+    }
+}
+
+#[test]
+fn primitive_apply(){
+    assert_eq!(Primitive::Add.apply(2, 3), Some(PrimValue::Int(5)));
+    assert_eq!(Primitive::Sub.apply(2, 3), Some(PrimValue::Int(-1)));
+    assert_eq!(Primitive::Div.apply(7, 2), Some(PrimValue::Int(3)));
+    assert_eq!(Primitive::Div.apply(7, 0), None);
+    assert_eq!(Primitive::Mul.apply(i32::MAX, 2), None);
+    assert_eq!(Primitive::Lt.apply(1, 2), Some(PrimValue::Bool(true)));
+}
+
+#[test]
+fn primitive_names(){
+    for &p in Primitive::all() {
+        assert_eq!(Primitive::from_name(p.name()), Some(p));
+    }
+    assert_eq!(Primitive::from_name("pow"), None);
+}
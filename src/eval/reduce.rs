@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+
+use parser::ParseNode;
+use parser::GrammarItem;
+use errors::error_index::Error;
+
+use super::Environment;
+use super::Primitive;
+use super::{Branch, Position};
+use super::substitution::{free_variables, rename_binders, substitute};
+use super::eta::eta_contract;
+
+/// The order in which redexes are chosen.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Strategy{
+    #[default]
+    Normal,             // Leftmost outermost, under abstractions
+    Applicative,        // Leftmost innermost, under abstractions
+    CallByName,         // Leftmost outermost, stops at weak head normal form
+    CallByValue         // Arguments first, stops at abstractions
+}
+
+static STRATEGIES: &[Strategy] = &[
+    Strategy::Normal, Strategy::Applicative,
+    Strategy::CallByName, Strategy::CallByValue
+];
+
+impl Strategy{
+    pub fn all() -> &'static [Strategy] {
+        STRATEGIES
+    }
+
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        STRATEGIES.iter().cloned().find(|s| s.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Normal => "normal",
+            Strategy::Applicative => "applicative",
+            Strategy::CallByName => "cbn",
+            Strategy::CallByValue => "cbv"
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Reduction{
+    pub term: ParseNode,
+    pub steps: usize
+}
+
+/// Substitution based small step reducer. Besides beta reduction, a step
+/// may unfold a definition from the environment, apply a `Primitive`, or
+/// when enabled, eta reduce.
+pub struct Reducer<'a>{
+    env: &'a Environment,
+    strategy: Strategy,
+    reserved: HashSet<String>,
+    eta: bool
+}
+
+impl<'a> Reducer<'a>{
+    pub fn new(env: &'a Environment, strategy: Strategy) -> Reducer<'a> {
+        // Binders are kept apart from every global name so that unfolding
+        // a definition can never be captured.
+        let mut reserved: HashSet<String> = Primitive::all().iter()
+            .map(|p| p.name().to_string())
+            .collect();
+        for (name, value) in env.iter() {
+            reserved.insert(name.clone());
+            reserved.extend(free_variables(value));
+        }
+        Reducer { env, strategy, reserved, eta: false }
+    }
+
+    /// Also treats `\x. f x` as a redex, so that normal forms are beta-eta
+    /// normal.
+    pub fn with_eta(mut self, eta: bool) -> Reducer<'a> {
+        self.eta = eta;
+        self
+    }
+
+    /// Reduces `term` until no redex is left, or fails after `fuel` steps.
+    pub fn normalize(&self, term: &ParseNode, fuel: usize) -> Result<Reduction, Error> {
+        let mut term = self.prepare(term);
+        let mut steps = 0;
+        while let Some(next) = self.step(&term) {
+            if steps == fuel {
+                return Err(Error::OutOfFuel(fuel));
+            }
+            term = next;
+            steps += 1;
+        }
+        Ok(Reduction { term, steps })
+    }
+
+    /// Renames binders of `term` that clash with global names. Terms must
+    /// be prepared before being passed to `step`.
+    pub fn prepare(&self, term: &ParseNode) -> ParseNode {
+        rename_binders(term, &self.reserved)
+    }
+
+    /// Performs a single reduction step, or `None` if `term` is already
+    /// normal for the chosen strategy.
+    pub fn step(&self, term: &ParseNode) -> Option<ParseNode> {
+        match self.strategy {
+            Strategy::Normal => self.step_normal(term),
+            Strategy::Applicative => self.step_applicative(term),
+            Strategy::CallByName => self.step_by_name(term),
+            Strategy::CallByValue => self.step_by_value(term)
+        }
+    }
+
+    /// Every term that `term` reduces to in one step, whichever redex is
+    /// contracted, along with the position of that redex.
+    pub fn successors(&self, term: &ParseNode) -> Vec<(Position, ParseNode)> {
+        let mut successors = vec!();
+        if let Some(next) = self.contract(term) {
+            successors.push((Position::root(), next));
+        }
+        match term.entry {
+            GrammarItem::Application(ref left, ref right) => {
+                for (position, left) in self.successors(left) {
+                    successors.push((position.under(Branch::Function),
+                        rebuild_application(term, left, (**right).clone())));
+                }
+                for (position, right) in self.successors(right) {
+                    successors.push((position.under(Branch::Argument),
+                        rebuild_application(term, (**left).clone(), right)));
+                }
+            },
+            GrammarItem::Abstraction(ref param, ref body) => {
+                for (position, body) in self.successors(body) {
+                    successors.push((position.under(Branch::Body), ParseNode::new(
+                        GrammarItem::Abstraction(param.clone(), Box::new(body)),
+                        term.node_type.clone()
+                    )));
+                }
+            },
+            _ => ()
+        }
+        successors
+    }
+
+    fn step_normal(&self, term: &ParseNode) -> Option<ParseNode> {
+        self.contract(term).or_else(|| self.step_children(term, |t| self.step_normal(t)))
+    }
+
+    fn step_applicative(&self, term: &ParseNode) -> Option<ParseNode> {
+        self.step_children(term, |t| self.step_applicative(t)).or_else(|| self.contract(term))
+    }
+
+    fn step_by_name(&self, term: &ParseNode) -> Option<ParseNode> {
+        if let Some(next) = self.contract(term) {
+            return Some(next);
+        }
+        match term.entry {
+            GrammarItem::Application(ref left, ref right) => {
+                if let Some(left) = self.step_by_name(left) {
+                    return Some(rebuild_application(term, left, (**right).clone()));
+                }
+                // Primitives are strict, so their arguments are forced
+                if self.head_is_primitive(term) {
+                    if let Some(right) = self.step_by_name(right) {
+                        return Some(rebuild_application(term, (**left).clone(), right));
+                    }
+                }
+                None
+            },
+            _ => None
+        }
+    }
+
+    fn step_by_value(&self, term: &ParseNode) -> Option<ParseNode> {
+        match term.entry {
+            GrammarItem::Application(ref left, ref right) => {
+                if let Some(left) = self.step_by_value(left) {
+                    return Some(rebuild_application(term, left, (**right).clone()));
+                }
+                if let Some(right) = self.step_by_value(right) {
+                    return Some(rebuild_application(term, (**left).clone(), right));
+                }
+                self.contract(term)
+            },
+            _ => self.contract(term)
+        }
+    }
+
+    /// Steps the first child of `term` that can be stepped.
+    fn step_children<F>(&self, term: &ParseNode, step: F) -> Option<ParseNode>
+        where F: Fn(&ParseNode) -> Option<ParseNode>
+    {
+        match term.entry {
+            GrammarItem::Application(ref left, ref right) => {
+                if let Some(left) = step(left) {
+                    Some(rebuild_application(term, left, (**right).clone()))
+                } else {
+                    step(right).map(|right| rebuild_application(term, (**left).clone(), right))
+                }
+            },
+            GrammarItem::Abstraction(ref param, ref body) => step(body).map(|body| ParseNode::new(
+                GrammarItem::Abstraction(param.clone(), Box::new(body)),
+                term.node_type.clone()
+            )),
+            _ => None
+        }
+    }
+
+    /// Contracts `term` if it is itself a redex.
+    fn contract(&self, term: &ParseNode) -> Option<ParseNode> {
+        match term.entry {
+            GrammarItem::Variable(ref name) =>
+                self.env.get(name).map(|value| self.prepare(value)),
+            GrammarItem::Abstraction(_, _) if self.eta => eta_contract(term),
+            GrammarItem::Application(ref left, ref right) => match left.entry {
+                GrammarItem::Abstraction(ref param, ref body) =>
+                    Some(substitute(body, param, right, &self.reserved)),
+                GrammarItem::Application(ref op, ref arg) => {
+                    match (&op.entry, &arg.entry, &right.entry) {
+                        (GrammarItem::Variable(name), &GrammarItem::LiteralInt(a), &GrammarItem::LiteralInt(b)) =>
+                            self.primitive(name)?.apply(a, b).map(|v| v.to_node()),
+                        _ => None
+                    }
+                },
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn primitive(&self, name: &str) -> Option<Primitive> {
+        if self.env.contains(name) { None } else { Primitive::from_name(name) }
+    }
+
+    fn head_is_primitive(&self, term: &ParseNode) -> bool {
+        match term.entry {
+            GrammarItem::Application(ref left, _) => self.head_is_primitive(left),
+            GrammarItem::Variable(ref name) => self.primitive(name).is_some(),
+            _ => false
+        }
+    }
+}
+
+fn rebuild_application(original: &ParseNode, left: ParseNode, right: ParseNode) -> ParseNode {
+    ParseNode::new(
+        GrammarItem::Application(Box::new(left), Box::new(right)),
+        original.node_type.clone()
+    )
+}
+
+#[cfg(test)]
+fn parse(input: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(input)).parse_expr().unwrap()
+}
+
+#[cfg(test)]
+fn normalize(input: &str, strategy: Strategy) -> Result<ParseNode, Error> {
+    let mut env = Environment::new();
+    env.define("id", parse(r#"\x. x"#));
+    env.define("omega", parse(r#"(\x. x x) (\x. x x)"#));
+    env.define("const", parse(r#"\a. \b. a"#));
+    Reducer::new(&env, strategy).normalize(&parse(input), 1000).map(|r| r.term)
+}
+
+#[test]
+fn reduce_beta(){
+    assert_eq!(normalize(r#"(\x. \y. x) a b"#, Strategy::Normal), Ok(parse("a")));
+    assert_eq!(normalize(r#"(\x. \y. x) a b"#, Strategy::Applicative), Ok(parse("a")));
+}
+
+#[test]
+fn reduce_definitions(){
+    assert_eq!(normalize("const id omega 5", Strategy::Normal), Ok(parse("5")));
+    assert_eq!(normalize("const id omega 5", Strategy::CallByName), Ok(parse("5")));
+    assert_eq!(normalize("const id omega 5", Strategy::CallByValue), Err(Error::OutOfFuel(1000)));
+}
+
+#[test]
+fn reduce_weak_strategies_stop_at_abstractions(){
+    assert_eq!(normalize(r#"\x. id x"#, Strategy::CallByName), Ok(parse(r#"\x. id x"#)));
+    assert_eq!(normalize(r#"\x. id x"#, Strategy::CallByValue), Ok(parse(r#"\x. id x"#)));
+    assert_eq!(normalize(r#"\x. id x"#, Strategy::Normal), Ok(parse(r#"\x. x"#)));
+}
+
+#[test]
+fn reduce_primitives(){
+    for &strategy in Strategy::all() {
+        assert_eq!(normalize("add (mul 3 4) (id 5)", strategy), Ok(parse("17")));
+        assert_eq!(normalize("lt 1 2 a b", strategy), Ok(parse("a")));
+    }
+    assert_eq!(normalize("div 1 0", Strategy::Normal), Ok(parse("div 1 0")));
+}
+
+#[test]
+fn reduce_with_eta(){
+    let env = Environment::new();
+    let term = parse(r#"\x. (\y. f y) x"#);
+    let beta = Reducer::new(&env, Strategy::Normal).normalize(&term, 100).unwrap();
+    let beta_eta = Reducer::new(&env, Strategy::Normal).with_eta(true).normalize(&term, 100).unwrap();
+    assert_eq!(beta.term, parse(r#"\x. f x"#));
+    assert_eq!(beta_eta, Reduction { term: parse("f"), steps: 2 });
+}
+
+#[test]
+fn reduce_successors_cover_every_redex(){
+    let env = Environment::new();
+    let reducer = Reducer::new(&env, Strategy::Normal);
+    let successors: Vec<(String, ParseNode)> = reducer.successors(&parse(r#"(\x. x x) ((\y. y) z)"#))
+        .into_iter()
+        .map(|(position, term)| (position.to_string(), term))
+        .collect();
+    assert_eq!(successors, vec![
+        ("ε".to_string(), parse(r#"(\y. y) z ((\y. y) z)"#)),
+        ("arg".to_string(), parse(r#"(\x. x x) z"#)),
+    ]);
+}
+
+#[test]
+fn reduce_avoids_capture_of_globals(){
+    assert_eq!(normalize(r#"(\id. id) 3"#, Strategy::Normal), Ok(parse("3")));
+    assert_eq!(normalize(r#"(\x. \y. x) y"#, Strategy::Normal), Ok(parse(r#"\y_. y"#)));
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+use parser::ParseNode;
+use parser::dot::escape;
+
+use super::{Position, Reducer};
+
+/// Every term reachable from a starting term, under any choice of redex.
+/// Terms are identified by how they print, so terms that only differ in
+/// the names picked for renamed binders are kept apart.
+pub struct ReductionGraph{
+    pub terms: Vec<ParseNode>,
+    pub edges: Vec<(usize, usize, Position)>,   // Carries from, to, redex contracted
+    pub normal_forms: Vec<usize>,
+    pub complete: bool                          // False if exploring stopped at the limit
+}
+
+impl ReductionGraph{
+    /// Explores the terms reachable from `term` breadth first, stopping once
+    /// `limit` terms have been found.
+    pub fn explore(reducer: &Reducer, term: &ParseNode, limit: usize) -> ReductionGraph {
+        let mut graph = ReductionGraph { terms: vec!(), edges: vec!(), normal_forms: vec!(), complete: true };
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut queue = VecDeque::new();
+
+        let start = reducer.prepare(term);
+        seen.insert(start.to_string(), 0);
+        graph.terms.push(start);
+        queue.push_back(0);
+
+        while let Some(from) = queue.pop_front() {
+            let successors = reducer.successors(&graph.terms[from]);
+            if successors.is_empty() {
+                graph.normal_forms.push(from);
+            }
+            for (position, next) in successors {
+                let key = next.to_string();
+                let to = match seen.get(&key) {
+                    Some(&to) => to,
+                    None if graph.terms.len() < limit => {
+                        let to = graph.terms.len();
+                        seen.insert(key, to);
+                        graph.terms.push(next);
+                        queue.push_back(to);
+                        to
+                    },
+                    None => {
+                        graph.complete = false;
+                        continue;
+                    }
+                };
+                graph.edges.push((from, to, position));
+            }
+        }
+        graph
+    }
+
+    /// The graph as DOT source. The starting term is drawn bold and normal
+    /// forms are drawn with a double border.
+    pub fn to_dot(&self) -> String {
+        let mut lines = vec!(
+            "digraph reductions {".to_string(),
+            "  node [shape=box, fontname=\"monospace\"];".to_string()
+        );
+        for (id, term) in self.terms.iter().enumerate() {
+            let normal = self.normal_forms.contains(&id);
+            let style = match (id, normal) {
+                (0, true) => ", style=bold, peripheries=2",
+                (0, false) => ", style=bold",
+                (_, true) => ", peripheries=2",
+                _ => ""
+            };
+            lines.push(format!("  t{} [label=\"{}\"{}];", id, escape(&term.to_string()), style));
+        }
+        for &(from, to, ref position) in &self.edges {
+            lines.push(format!("  t{} -> t{} [label=\"{}\"];", from, to, position));
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+}
+
+#[test]
+fn explore_finds_every_path(){
+    use lexer::Lexer;
+    use parser::Parser;
+    use super::{Environment, Strategy};
+
+    let env = Environment::new();
+    let reducer = Reducer::new(&env, Strategy::Normal);
+    let term = Parser::new(Lexer::new(r#"(\x. x) ((\y. y) z)"#)).parse_expr().unwrap();
+    let graph = ReductionGraph::explore(&reducer, &term, 100);
+
+    let terms: Vec<String> = graph.terms.iter().map(|t| t.to_string()).collect();
+    assert_eq!(terms, vec![r#"(\x. x) ((\y. y) z)"#, r#"(\y. y) z"#, r#"(\x. x) z"#, "z"]);
+    let edges: Vec<(usize, usize, String)> = graph.edges.iter()
+        .map(|&(from, to, ref position)| (from, to, position.to_string()))
+        .collect();
+    assert_eq!(edges, vec![
+        (0, 1, "ε".to_string()), (0, 2, "arg".to_string()),
+        (1, 3, "ε".to_string()), (2, 3, "ε".to_string())
+    ]);
+    assert_eq!(graph.normal_forms, vec![3]);
+    assert!(graph.complete);
+
+    let omega = Parser::new(Lexer::new(r#"(\x. x x x) (\x. x x x)"#)).parse_expr().unwrap();
+    assert!(!ReductionGraph::explore(&reducer, &omega, 5).complete);
+}
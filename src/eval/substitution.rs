@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+
+use parser::ParseNode;
+use parser::GrammarItem;
+
+/// Names that occur in `term` without an enclosing abstraction binding them.
+pub fn free_variables(term: &ParseNode) -> HashSet<String> {
+    let mut free = HashSet::new();
+    collect_free(term, &mut vec!(), &mut free);
+    free
+}
+
+fn collect_free(term: &ParseNode, bound: &mut Vec<String>, free: &mut HashSet<String>){
+    match term.entry {
+        GrammarItem::Variable(ref name) => {
+            if !bound.contains(name) {
+                free.insert(name.clone());
+            }
+        },
+        GrammarItem::Abstraction(ref param, ref body) => {
+            bound.push(param.clone());
+            collect_free(body, bound, free);
+            bound.pop();
+        },
+        GrammarItem::Application(ref left, ref right) => {
+            collect_free(left, bound, free);
+            collect_free(right, bound, free);
+        },
+        GrammarItem::Assignment(_, ref expr) => collect_free(expr, bound, free),
+        GrammarItem::Program(ref children) => {
+            for child in children {
+                collect_free(child, bound, free);
+            }
+        },
+        GrammarItem::LiteralInt(_) => ()
+    }
+}
+
+/// A variant of `base` that isn't in `avoid`. Identifiers can't contain
+/// digits, so variants are made by appending underscores.
+pub fn fresh_name(base: &str, avoid: &HashSet<String>) -> String {
+    let mut name = format!("{}_", base);
+    while avoid.contains(&name) {
+        name.push('_');
+    }
+    name
+}
+
+/// Names tried, in order, by `readable_name`.
+static NAMES: &[&str] = &["x", "y", "z", "u", "v", "w"];
+
+/// A short name for a new binder that isn't in `avoid`, falling back to a
+/// variant of `x` once the usual names are used up.
+pub fn readable_name(avoid: &HashSet<String>) -> String {
+    match NAMES.iter().find(|&&name| !avoid.contains(name)) {
+        Some(name) => name.to_string(),
+        None => fresh_name("x", avoid)
+    }
+}
+
+/// Capture avoiding substitution of `value` for the free occurrences of
+/// `name` in `term`. Binders that would capture a free variable of `value`
+/// are renamed, and the new names also avoid everything in `reserved`.
+pub fn substitute(term: &ParseNode, name: &str, value: &ParseNode, reserved: &HashSet<String>) -> ParseNode {
+    let value_free = free_variables(value);
+    substitute_with(term, name, value, &value_free, reserved)
+}
+
+fn substitute_with(term: &ParseNode, name: &str, value: &ParseNode,
+                   value_free: &HashSet<String>, reserved: &HashSet<String>) -> ParseNode {
+    let entry = match term.entry {
+        GrammarItem::Variable(ref var) if var == name => return value.clone(),
+        GrammarItem::Variable(_) | GrammarItem::LiteralInt(_) => return term.clone(),
+        GrammarItem::Abstraction(ref param, _) if param == name => return term.clone(),
+        GrammarItem::Abstraction(ref param, ref body) => {
+            let body_free = free_variables(body);
+            if !body_free.contains(name) {
+                return term.clone();
+            }
+            if value_free.contains(param) {
+                let avoid = value_free.iter().chain(body_free.iter()).chain(reserved.iter())
+                    .cloned()
+                    .collect();
+                let renamed = fresh_name(param, &avoid);
+                let body = substitute(body, param, &ParseNode::variable(&renamed), reserved);
+                GrammarItem::Abstraction(renamed,
+                    Box::new(substitute_with(&body, name, value, value_free, reserved)))
+            } else {
+                GrammarItem::Abstraction(param.clone(),
+                    Box::new(substitute_with(body, name, value, value_free, reserved)))
+            }
+        },
+        GrammarItem::Application(ref left, ref right) => GrammarItem::Application(
+            Box::new(substitute_with(left, name, value, value_free, reserved)),
+            Box::new(substitute_with(right, name, value, value_free, reserved))
+        ),
+        GrammarItem::Assignment(ref var, ref expr) => GrammarItem::Assignment(var.clone(),
+            Box::new(substitute_with(expr, name, value, value_free, reserved))),
+        GrammarItem::Program(ref children) => GrammarItem::Program(
+            children.iter()
+                .map(|c| substitute_with(c, name, value, value_free, reserved))
+                .collect())
+    };
+    ParseNode::new(entry, term.node_type.clone())
+}
+
+/// Renames every binder in `term` whose name is in `reserved`, so that
+/// later substitutions of reserved names can't be captured.
+pub fn rename_binders(term: &ParseNode, reserved: &HashSet<String>) -> ParseNode {
+    let entry = match term.entry {
+        GrammarItem::Variable(_) | GrammarItem::LiteralInt(_) => return term.clone(),
+        GrammarItem::Abstraction(ref param, ref body) => {
+            if reserved.contains(param) {
+                let avoid = free_variables(body).union(reserved).cloned().collect();
+                let renamed = fresh_name(param, &avoid);
+                let body = substitute(body, param, &ParseNode::variable(&renamed), reserved);
+                GrammarItem::Abstraction(renamed, Box::new(rename_binders(&body, reserved)))
+            } else {
+                GrammarItem::Abstraction(param.clone(), Box::new(rename_binders(body, reserved)))
+            }
+        },
+        GrammarItem::Application(ref left, ref right) => GrammarItem::Application(
+            Box::new(rename_binders(left, reserved)),
+            Box::new(rename_binders(right, reserved))
+        ),
+        GrammarItem::Assignment(ref var, ref expr) =>
+            GrammarItem::Assignment(var.clone(), Box::new(rename_binders(expr, reserved))),
+        GrammarItem::Program(ref children) => GrammarItem::Program(
+            children.iter().map(|c| rename_binders(c, reserved)).collect())
+    };
+    ParseNode::new(entry, term.node_type.clone())
+}
+
+#[cfg(test)]
+fn parse(input: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(input)).parse_expr().unwrap()
+}
+
+#[test]
+fn free_variables_test(){
+    let free = free_variables(&parse(r#"\x. x y (\y. z y)"#));
+    let expected: HashSet<String> = vec!["y", "z"].into_iter().map(String::from).collect();
+    assert_eq!(free, expected);
+}
+
+#[test]
+fn substitute_simple(){
+    let result = substitute(&parse(r#"x (\x. x) y"#), "x", &parse("a b"), &HashSet::new());
+    assert_eq!(result, parse(r#"a b (\x. x) y"#));
+}
+
+#[test]
+fn substitute_avoids_capture(){
+    let result = substitute(&parse(r#"\y. x y"#), "x", &parse("y"), &HashSet::new());
+    assert_eq!(result, parse(r#"\y_. y y_"#));
+}
+
+#[test]
+fn rename_binders_test(){
+    let reserved = vec!["f".to_string()].into_iter().collect();
+    let result = rename_binders(&parse(r#"\f. \g. f g"#), &reserved);
+    assert_eq!(result, parse(r#"\f_. \g. f_ g"#));
+}
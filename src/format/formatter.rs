@@ -0,0 +1,187 @@
+use std::fs;
+
+use lexer::{Lexer, Token, Span};
+use parser::{Parser, ParseNode, GrammarItem, PrettyPrinter, Visitor};
+use errors::error_index::Error;
+
+/// A line of formatted output, before `=` signs are aligned.
+enum Entry<'a>{
+  Comment(&'a str),
+  Definition(&'a ParseNode, Option<&'a str>)    // Carries a trailing comment
+}
+
+/// Rewrites a program in canonical layout. Definitions keep their order and
+/// are grouped the way they were in the source, with groups separated by a
+/// single blank line and the `=` of each group aligned. Comments stay with
+/// the definition they precede, or at the end of its line if they followed
+/// it there.
+pub fn format_source(source: &str, width: usize) -> Result<String, Error> {
+  let program = Parser::new(Lexer::new(source)).parse()?;
+  let definitions = match program.entry {
+    GrammarItem::Program(ref definitions) => definitions,
+    _ => unreachable!()
+  };
+  let comments = comments(source);
+  let mut comments = comments.iter().peekable();
+
+  let mut groups: Vec<Vec<Entry>> = vec!(vec!());
+  let mut last_end = 0;
+  {
+    // Starts a new group when a blank line separates `span` from what came before
+    let mut push = |entry, span: Span| {
+      let blank = source.get(last_end..span.start)
+        .is_some_and(|gap| gap.matches('\n').count() > 1);
+      if blank && !groups.last().unwrap().is_empty() {
+        groups.push(vec!());
+      }
+      groups.last_mut().unwrap().push(entry);
+      last_end = last_end.max(span.end);
+    };
+
+    for definition in definitions {
+      // Comments inside a definition are moved above it
+      while let Some(&&comment) = comments.peek() {
+        if comment.start > definition.span.end {
+          break;
+        }
+        let start = comment.start.min(definition.span.start);
+        push(Entry::Comment(text(source, comment)), Span::new(start, comment.end));
+        comments.next();
+      }
+
+      let mut span = definition.span;
+      let trailing = match comments.peek() {
+        Some(&&comment) if !source[span.end..comment.start].contains('\n') => {
+          comments.next();
+          span = span.to(comment);
+          Some(text(source, comment))
+        },
+        _ => None
+      };
+      push(Entry::Definition(definition, trailing), span);
+    }
+    for &comment in comments {
+      push(Entry::Comment(text(source, comment)), comment);
+    }
+  }
+
+  let groups: Vec<String> = groups.iter()
+    .filter(|group| !group.is_empty())
+    .map(|group| format_group(group, width))
+    .collect();
+  if groups.is_empty() {
+    Ok(String::new())
+  } else {
+    Ok(groups.join("\n\n") + "\n")
+  }
+}
+
+/// Formats each file in place, or with `check` only reports the files that
+/// aren't formatted. Returns whether every file was already formatted (when
+/// checking) or could be formatted.
+pub fn format_files(paths: &[&str], check: bool, width: usize) -> bool {
+  let mut success = true;
+  for path in paths {
+    let result = fs::read_to_string(path)
+      .map_err(|e| format!("can't read {}: {}", path, e))
+      .and_then(|source| format_source(&source, width)
+        .map(|formatted| (source, formatted))
+        .map_err(|e| format!("can't parse {}: {:?}", path, e)));
+
+    match result {
+      Ok((ref source, ref formatted)) if source == formatted => {},
+      Ok(_) if check => {
+        println!("{} would be reformatted", path);
+        success = false;
+      },
+      Ok((_, formatted)) => {
+        if let Err(e) = fs::write(path, formatted) {
+          eprintln!("Error: can't write {}: {}", path, e);
+          success = false;
+        }
+      },
+      Err(e) => {
+        eprintln!("Error: {}", e);
+        success = false;
+      }
+    }
+  }
+  success
+}
+
+fn comments(source: &str) -> Vec<Span> {
+  let mut lexer = Lexer::new(source);
+  while lexer.next_token() != Token::EOF {}
+  lexer.comments().to_vec()
+}
+
+fn text(source: &str, span: Span) -> &str {
+  source[span.start..span.end].trim_end()
+}
+
+fn format_group(group: &[Entry], width: usize) -> String {
+  let name_width = group.iter()
+    .filter_map(|entry| match *entry {
+      Entry::Definition(definition, _) => name(definition).map(|n| n.chars().count()),
+      Entry::Comment(_) => None
+    })
+    .max()
+    .unwrap_or(0);
+
+  group.iter()
+    .map(|entry| match *entry {
+      Entry::Comment(comment) => comment.to_string(),
+      Entry::Definition(definition, trailing) => {
+        let line = format_definition(definition, name_width, width);
+        match trailing {
+          Some(comment) => format!("{} {}", line, comment),
+          None => line
+        }
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Pads the name so the `=` lines up with the rest of the group. Definitions
+/// that don't fit on one line are broken after the `=` instead.
+fn format_definition(definition: &ParseNode, name_width: usize, width: usize) -> String {
+  if let GrammarItem::Assignment(ref name, ref expr) = definition.entry {
+    let header = format!("{:<w$} = ", name, w = name_width);
+    let body = PrettyPrinter::flat().visit(expr);
+    if header.chars().count() + body.chars().count() <= width {
+      return header + &body;
+    }
+  }
+  PrettyPrinter::new(width).visit(definition)
+}
+
+fn name(definition: &ParseNode) -> Option<&str> {
+  match definition.entry {
+    GrammarItem::Assignment(ref name, _) => Some(name),
+    _ => None
+  }
+}
+
+#[test]
+fn format_aligns_groups(){
+  let source = "id=\\x.x\nconst = \\x.\\y. x\n\n\n\napply = \\f.\\x.(f x)\n";
+  let expected = "id    = \\x. x\nconst = \\x. \\y. x\n\napply = \\f. \\x. f x\n";
+  assert_eq!(format_source(source, 80), Ok(expected.to_string()));
+}
+
+#[test]
+fn format_preserves_comments(){
+  let source = "-- Church booleans\ntrue = \\t. \\f. t   -- first\nfalse = \\t. (\n  -- second\n  \\f. f)\n\n-- end\n";
+  let expected = "-- Church booleans\ntrue  = \\t. \\f. t -- first\n-- second\nfalse = \\t. \\f. f\n\n-- end\n";
+  assert_eq!(format_source(source, 80), Ok(expected.to_string()));
+}
+
+#[test]
+fn format_is_idempotent(){
+  let source = "-- combinators\ns=\\x.\\y.\\z. x z (y z)\nk = \\x. \\y. x -- const\n\nomega = (\\x. x x) (\\x. x x)\n";
+  for &width in &[80, 20] {
+    let once = format_source(source, width).unwrap();
+    assert_eq!(format_source(&once, width), Ok(once.clone()));
+  }
+}
@@ -0,0 +1,3 @@
+mod formatter;
+
+pub use self::formatter::{format_source, format_files};
@@ -0,0 +1,255 @@
+use std::fmt;
+
+use parser::{ParseNode, GrammarItem};
+use errors::error_index::Error;
+use eval::{DbTerm, Environment, Reduction};
+use eval::substitution::substitute;
+
+use super::value::{self, Definitions, Env, Interpreter, IrClosure, Quote};
+#[cfg(test)]
+use super::value::parse;
+
+/// A value that needs no evaluation.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Atom{
+    Variable(String),
+    Int(i32),
+    Lambda(String, Box<Anf>)
+}
+
+/// A term in A-normal form: every application is of atoms, and every
+/// application that isn't in tail position has its result named.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Anf{
+    Atom(Atom),
+    Apply(Atom, Atom),
+    Let(String, Atom, Atom, Box<Anf>)      // Carries the name, the function and argument, and the rest
+}
+
+/// Converts an expression to A-normal form, naming the result of each
+/// application `t0`, `t1`, ... in the order call-by-value evaluates them.
+/// Source names have no digits, so they never clash.
+pub fn convert(node: &ParseNode) -> Result<Anf, Error> {
+    Converter { next: 0 }.term(node)
+}
+
+struct Converter{
+    next: usize
+}
+
+impl Converter{
+    fn term(&mut self, node: &ParseNode) -> Result<Anf, Error> {
+        let mut lets = vec!();
+        let tail = match node.entry {
+            GrammarItem::Application(ref function, ref argument) => {
+                let function = self.atom(function, &mut lets)?;
+                Anf::Apply(function, self.atom(argument, &mut lets)?)
+            },
+            _ => Anf::Atom(self.atom(node, &mut lets)?)
+        };
+        Ok(lets.into_iter().rev().fold(tail, |rest, (name, function, argument)|
+            Anf::Let(name, function, argument, Box::new(rest))))
+    }
+
+    /// An atom for `node`, adding the applications it needs to `lets`.
+    fn atom(&mut self, node: &ParseNode, lets: &mut Vec<(String, Atom, Atom)>) -> Result<Atom, Error> {
+        match node.entry {
+            GrammarItem::Variable(ref name) => Ok(Atom::Variable(name.clone())),
+            GrammarItem::LiteralInt(val) => Ok(Atom::Int(val)),
+            GrammarItem::Abstraction(ref param, ref body) => Ok(Atom::Lambda(param.clone(), Box::new(self.term(body)?))),
+            GrammarItem::Application(ref function, ref argument) => {
+                let function = self.atom(function, lets)?;
+                let argument = self.atom(argument, lets)?;
+                let name = format!("t{}", self.next);
+                self.next += 1;
+                lets.push((name.clone(), function, argument));
+                Ok(Atom::Variable(name))
+            },
+            _ => Err(Error::InvalidAst("only expressions can be converted to A-normal form".to_string()))
+        }
+    }
+}
+
+impl Atom{
+    pub fn to_node(&self) -> ParseNode {
+        match *self {
+            Atom::Variable(ref name) => ParseNode::variable(name),
+            Atom::Int(val) => ParseNode::literal_int(val),
+            Atom::Lambda(ref param, ref body) => ParseNode::abstraction(param, body.to_node())
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Atom::Lambda(_, _) => write!(f, "({})", self),
+            _ => write!(f, "{}", self)
+        }
+    }
+}
+
+impl Anf{
+    /// The expression this was converted from, with every name bound by a
+    /// `let` put back where it is used.
+    pub fn to_node(&self) -> ParseNode {
+        match *self {
+            Anf::Atom(ref atom) => atom.to_node(),
+            Anf::Apply(ref function, ref argument) => ParseNode::application(function.to_node(), argument.to_node()),
+            Anf::Let(ref name, ref function, ref argument, ref rest) => {
+                let application = ParseNode::application(function.to_node(), argument.to_node());
+                substitute(&rest.to_node(), name, &application, &Default::default())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Atom{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Atom::Variable(ref name) => write!(f, "{}", name),
+            Atom::Int(val) => write!(f, "{}", val),
+            Atom::Lambda(ref param, ref body) => write!(f, "\\{}. {}", param, body)
+        }
+    }
+}
+
+/// Written as `let t0 = f x in g t0`.
+impl fmt::Display for Anf{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Anf::Atom(ref atom) => write!(f, "{}", atom),
+            Anf::Apply(ref function, ref argument) => {
+                function.fmt_operand(f)?;
+                write!(f, " ")?;
+                argument.fmt_operand(f)
+            },
+            Anf::Let(ref name, ref function, ref argument, ref rest) => {
+                write!(f, "let {} = ", name)?;
+                function.fmt_operand(f)?;
+                write!(f, " ")?;
+                argument.fmt_operand(f)?;
+                write!(f, " in {}", rest)
+            }
+        }
+    }
+}
+
+type AnfValue<'a> = value::Value<AnfClosure<'a>>;
+
+#[derive(Clone)]
+pub struct AnfClosure<'a>{
+    param: &'a str,
+    body: &'a Anf,
+    env: Env<AnfValue<'a>>
+}
+
+impl<'a> Quote for AnfClosure<'a>{
+    fn quote(&self) -> DbTerm {
+        value::quote_closure(self.param, self.body.to_node(), &self.env, |value| Some(value.quote()))
+    }
+}
+
+impl<'a> IrClosure<'a> for AnfClosure<'a>{
+    type Term = Anf;
+
+    fn run(interpreter: &mut Interpreter<'a, Self>, anf: &'a Anf) -> Result<AnfValue<'a>, Error> {
+        interpreter.run(anf, Env::default())
+    }
+}
+
+/// Evaluates `term` call-by-value by interpreting its A-normal form, with
+/// definitions in `env` converted the same way, evaluated once and shared.
+/// Steps count applications, and fail with `OutOfFuel` past `fuel`.
+pub fn evaluate(env: &Environment, term: &ParseNode, fuel: usize) -> Result<Reduction, Error> {
+    let definitions = Definitions::convert(env, convert)?;
+    value::evaluate::<AnfClosure>(&definitions, &convert(term)?, fuel)
+}
+
+/// What applying a value leads to.
+enum Applied<'a>{
+    Enter(&'a Anf, Env<AnfValue<'a>>),
+    Done(AnfValue<'a>)
+}
+
+impl<'a> Interpreter<'a, AnfClosure<'a>>{
+    fn run(&mut self, anf: &'a Anf, env: Env<AnfValue<'a>>) -> Result<AnfValue<'a>, Error> {
+        let (mut anf, mut env) = (anf, env);
+        loop {
+            match *anf {
+                Anf::Atom(ref atom) => return self.atom(atom, &env),
+                Anf::Apply(ref function, ref argument) => {
+                    let (function, argument) = (self.atom(function, &env)?, self.atom(argument, &env)?);
+                    match self.apply(function, argument)? {
+                        Applied::Enter(body, inner) => {
+                            anf = body;
+                            env = inner;
+                        },
+                        Applied::Done(value) => return Ok(value)
+                    }
+                },
+                Anf::Let(ref name, ref function, ref argument, ref rest) => {
+                    let (function, argument) = (self.atom(function, &env)?, self.atom(argument, &env)?);
+                    let value = match self.apply(function, argument)? {
+                        Applied::Enter(body, inner) => self.nested(|interpreter| interpreter.run(body, inner))?,
+                        Applied::Done(value) => value
+                    };
+                    env = env.push(name, value);
+                    anf = rest;
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, function: AnfValue<'a>, argument: AnfValue<'a>) -> Result<Applied<'a>, Error> {
+        self.tick()?;
+        Ok(match function {
+            value::Value::Closure(closure) => Applied::Enter(closure.body, closure.env.push(closure.param, argument)),
+            head => Applied::Done(self.apply_head(head, argument))
+        })
+    }
+
+    fn atom(&mut self, atom: &'a Atom, env: &Env<AnfValue<'a>>) -> Result<AnfValue<'a>, Error> {
+        match *atom {
+            Atom::Int(val) => Ok(value::Value::Int(val)),
+            Atom::Lambda(ref param, ref body) =>
+                Ok(value::Value::Closure(AnfClosure { param, body, env: env.clone() })),
+            Atom::Variable(ref name) => match env.lookup(name) {
+                Some(value) => Ok(value.clone()),
+                None => self.global(name)
+            }
+        }
+    }
+}
+
+#[test]
+fn anf_names_applications(){
+    assert_eq!(convert(&parse("f (g x) (h 1)")).unwrap().to_string(), "let t0 = g x in let t1 = f t0 in let t2 = h 1 in t1 t2");
+    assert_eq!(convert(&parse("\\x. f (x x)")).unwrap().to_string(), "\\x. let t0 = x x in f t0");
+    assert_eq!(convert(&parse("(\\x. x) 5")).unwrap().to_string(), "(\\x. x) 5");
+    for &(_, input) in ::machine::corpus::CASES {
+        assert_eq!(convert(&parse(input)).unwrap().to_node(), parse(input), "converting {}", input);
+    }
+}
+
+#[test]
+fn anf_agrees_with_call_by_value(){
+    use eval::Strategy;
+    use machine::corpus;
+
+    for &(definitions, input) in corpus::CASES {
+        let (env, term) = corpus::load(definitions, input);
+        let expected = corpus::substitution(&env, &term, Strategy::CallByValue);
+        let actual = evaluate(&env, &term, corpus::FUEL).and_then(|r| DbTerm::from_node(&r.term));
+        assert_eq!(actual, expected, "evaluating {}", input);
+    }
+}
+
+#[test]
+fn anf_fails_on_deep_recursion(){
+    use machine::corpus;
+
+    let definitions = format!("{}\ndown = \\n. if (eq n 0) (\\u. 0) (\\u. add 1 (down (sub n 1)))", corpus::ARITHMETIC);
+    let (env, term) = corpus::load(&definitions, "down 120000");
+    assert_eq!(evaluate(&env, &term, 10_000_000).map(|r| r.term), Err(Error::TooDeep(100_000)));
+    // Continuations don't use the Rust stack
+    assert_eq!(super::cps::evaluate(&env, &term, 10_000_000).map(|r| r.term), Ok(ParseNode::literal_int(120000)));
+}
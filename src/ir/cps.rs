@@ -0,0 +1,300 @@
+use std::fmt;
+
+use parser::{ParseNode, GrammarItem};
+use errors::error_index::Error;
+use eval::{DbTerm, Environment, Reduction};
+use eval::substitution::substitute;
+
+use super::value::{self, Definitions, Env, Interpreter, IrClosure, Quote};
+#[cfg(test)]
+use super::value::parse;
+
+/// A value that needs no evaluation. Lambdas take their continuation as a
+/// second parameter.
+#[derive(Debug, PartialEq, Clone)]
+pub enum CpsValue{
+    Variable(String),
+    Int(i32),
+    Lambda(String, String, Box<Cps>)       // Carries the parameter, the continuation and the body
+}
+
+/// Where a result goes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Cont{
+    Variable(String),
+    Lambda(String, Box<Cps>),
+    Halt
+}
+
+/// A term in continuation passing style, where no call returns: every
+/// application passes on its continuation, and values are passed to one.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Cps{
+    Apply(CpsValue, CpsValue, Cont),
+    Continue(Cont, CpsValue)
+}
+
+/// Converts an expression to continuation passing style, for call-by-value
+/// evaluation from left to right. Results are named `v0`, `v1`, ... and
+/// continuations `k0`, `k1`, ..., which never clash with source names as
+/// those have no digits. Continuations are built only where they are
+/// needed, so the result has no administrative redexes.
+pub fn convert(node: &ParseNode) -> Result<Cps, Error> {
+    Converter { next_value: 0, next_cont: 0 }.term(node, Cont::Halt)
+}
+
+struct Converter{
+    next_value: usize,
+    next_cont: usize
+}
+
+impl Converter{
+    /// `node` passing its value to `cont`.
+    fn term(&mut self, node: &ParseNode, cont: Cont) -> Result<Cps, Error> {
+        let mut calls = vec!();
+        let tail = match node.entry {
+            GrammarItem::Application(ref function, ref argument) => {
+                let function = self.value(function, &mut calls)?;
+                Cps::Apply(function, self.value(argument, &mut calls)?, cont)
+            },
+            _ => Cps::Continue(cont, self.value(node, &mut calls)?)
+        };
+        Ok(calls.into_iter().rev().fold(tail, |rest, (name, function, argument)|
+            Cps::Apply(function, argument, Cont::Lambda(name, Box::new(rest)))))
+    }
+
+    /// A value for `node`, adding the calls it needs to `calls`.
+    fn value(&mut self, node: &ParseNode, calls: &mut Vec<(String, CpsValue, CpsValue)>) -> Result<CpsValue, Error> {
+        match node.entry {
+            GrammarItem::Variable(ref name) => Ok(CpsValue::Variable(name.clone())),
+            GrammarItem::LiteralInt(val) => Ok(CpsValue::Int(val)),
+            GrammarItem::Abstraction(ref param, ref body) => {
+                let cont = format!("k{}", self.next_cont);
+                self.next_cont += 1;
+                let body = self.term(body, Cont::Variable(cont.clone()))?;
+                Ok(CpsValue::Lambda(param.clone(), cont, Box::new(body)))
+            },
+            GrammarItem::Application(ref function, ref argument) => {
+                let function = self.value(function, calls)?;
+                let argument = self.value(argument, calls)?;
+                let name = format!("v{}", self.next_value);
+                self.next_value += 1;
+                calls.push((name.clone(), function, argument));
+                Ok(CpsValue::Variable(name))
+            },
+            _ => Err(Error::InvalidAst("only expressions can be converted to continuation passing style".to_string()))
+        }
+    }
+}
+
+impl CpsValue{
+    pub fn to_node(&self) -> ParseNode {
+        match *self {
+            CpsValue::Variable(ref name) => ParseNode::variable(name),
+            CpsValue::Int(val) => ParseNode::literal_int(val),
+            CpsValue::Lambda(ref param, _, ref body) => ParseNode::abstraction(param, body.to_node())
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CpsValue::Lambda(_, _, _) => write!(f, "({})", self),
+            _ => write!(f, "{}", self)
+        }
+    }
+}
+
+impl Cps{
+    /// The expression this was converted from: a call returns to where its
+    /// continuation's parameter is used.
+    pub fn to_node(&self) -> ParseNode {
+        let (cont, result) = match *self {
+            Cps::Apply(ref function, ref argument, ref cont) =>
+                (cont, ParseNode::application(function.to_node(), argument.to_node())),
+            Cps::Continue(ref cont, ref value) => (cont, value.to_node())
+        };
+        match *cont {
+            Cont::Lambda(ref name, ref rest) => substitute(&rest.to_node(), name, &result, &Default::default()),
+            _ => result
+        }
+    }
+}
+
+impl fmt::Display for CpsValue{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CpsValue::Variable(ref name) => write!(f, "{}", name),
+            CpsValue::Int(val) => write!(f, "{}", val),
+            CpsValue::Lambda(ref param, ref cont, ref body) => write!(f, "\\{} {}. {}", param, cont, body)
+        }
+    }
+}
+
+impl fmt::Display for Cont{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cont::Variable(ref name) => write!(f, "{}", name),
+            Cont::Lambda(ref name, ref body) => write!(f, "(\\{}. {})", name, body),
+            Cont::Halt => write!(f, "halt")
+        }
+    }
+}
+
+/// Written as `f x (\v0. g v0 k0)`.
+impl fmt::Display for Cps{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cps::Apply(ref function, ref argument, ref cont) => {
+                function.fmt_operand(f)?;
+                write!(f, " ")?;
+                argument.fmt_operand(f)?;
+                write!(f, " {}", cont)
+            },
+            Cps::Continue(ref cont, ref value) => {
+                write!(f, "{} ", cont)?;
+                value.fmt_operand(f)
+            }
+        }
+    }
+}
+
+type Value<'a> = value::Value<CpsClosure<'a>>;
+
+#[derive(Clone)]
+pub struct CpsClosure<'a>{
+    param: &'a str,
+    cont: &'a str,
+    body: &'a Cps,
+    env: Env<Binding<'a>>
+}
+
+impl<'a> Quote for CpsClosure<'a>{
+    fn quote(&self) -> DbTerm {
+        value::quote_closure(self.param, self.body.to_node(), &self.env, |binding| match *binding {
+            Binding::Value(ref value) => Some(value.quote()),
+            Binding::Continuation(_) => None
+        })
+    }
+}
+
+impl<'a> IrClosure<'a> for CpsClosure<'a>{
+    type Term = Cps;
+
+    fn run(interpreter: &mut Interpreter<'a, Self>, cps: &'a Cps) -> Result<Value<'a>, Error> {
+        interpreter.run(cps, Env::default())
+    }
+}
+
+#[derive(Clone)]
+pub enum Continuation<'a>{
+    Halt,
+    Closure(&'a str, &'a Cps, Env<Binding<'a>>)
+}
+
+/// Names are bound to values or, for a lambda's continuation parameter, to
+/// continuations.
+#[derive(Clone)]
+pub enum Binding<'a>{
+    Value(Value<'a>),
+    Continuation(Continuation<'a>)
+}
+
+/// Evaluates `term` call-by-value by interpreting its continuation passing
+/// form. Continuations are data, so evaluation is a loop that never grows
+/// the Rust stack, except to evaluate a definition the first time it is
+/// used. Steps count applications, and fail with `OutOfFuel` past `fuel`.
+pub fn evaluate(env: &Environment, term: &ParseNode, fuel: usize) -> Result<Reduction, Error> {
+    let definitions = Definitions::convert(env, convert)?;
+    value::evaluate::<CpsClosure>(&definitions, &convert(term)?, fuel)
+}
+
+impl<'a> Interpreter<'a, CpsClosure<'a>>{
+    fn run(&mut self, cps: &'a Cps, env: Env<Binding<'a>>) -> Result<Value<'a>, Error> {
+        let (mut cps, mut env) = (cps, env);
+        loop {
+            let (cont, result) = match *cps {
+                Cps::Continue(ref cont, ref value) => (self.cont(cont, &env)?, self.value(value, &env)?),
+                Cps::Apply(ref function, ref argument, ref cont) => {
+                    let (function, argument) = (self.value(function, &env)?, self.value(argument, &env)?);
+                    let cont = self.cont(cont, &env)?;
+                    self.tick()?;
+                    match function {
+                        value::Value::Closure(closure) => {
+                            env = closure.env
+                                .push(closure.param, Binding::Value(argument))
+                                .push(closure.cont, Binding::Continuation(cont));
+                            cps = closure.body;
+                            continue;
+                        },
+                        head => (cont, self.apply_head(head, argument))
+                    }
+                }
+            };
+            match cont {
+                Continuation::Halt => return Ok(result),
+                Continuation::Closure(name, body, inner) => {
+                    env = inner.push(name, Binding::Value(result));
+                    cps = body;
+                }
+            }
+        }
+    }
+
+    fn cont(&self, cont: &'a Cont, env: &Env<Binding<'a>>) -> Result<Continuation<'a>, Error> {
+        match *cont {
+            Cont::Halt => Ok(Continuation::Halt),
+            Cont::Lambda(ref name, ref body) => Ok(Continuation::Closure(name, body, env.clone())),
+            Cont::Variable(ref name) => match env.lookup(name) {
+                Some(Binding::Continuation(cont)) => Ok(cont.clone()),
+                _ => Err(Error::InvalidAst(format!("continuation {} isn't bound", name)))
+            }
+        }
+    }
+
+    fn value(&mut self, value: &'a CpsValue, env: &Env<Binding<'a>>) -> Result<Value<'a>, Error> {
+        match *value {
+            CpsValue::Int(val) => Ok(value::Value::Int(val)),
+            CpsValue::Lambda(ref param, ref cont, ref body) =>
+                Ok(value::Value::Closure(CpsClosure { param, cont, body, env: env.clone() })),
+            CpsValue::Variable(ref name) => match env.lookup(name) {
+                Some(Binding::Value(value)) => Ok(value.clone()),
+                _ => self.global(name)
+            }
+        }
+    }
+}
+
+#[test]
+fn cps_passes_continuations(){
+    assert_eq!(convert(&parse("f (g x) 1")).unwrap().to_string(), "g x (\\v0. f v0 (\\v1. v1 1 halt))");
+    assert_eq!(convert(&parse("\\x. f (x x)")).unwrap().to_string(), "halt (\\x k0. x x (\\v0. f v0 k0))");
+    assert_eq!(convert(&parse("(\\x. x) 5")).unwrap().to_string(), "(\\x k0. k0 x) 5 halt");
+    for &(_, input) in ::machine::corpus::CASES {
+        assert_eq!(convert(&parse(input)).unwrap().to_node(), parse(input), "converting {}", input);
+    }
+}
+
+#[test]
+fn cps_agrees_with_call_by_value(){
+    use eval::Strategy;
+    use machine::corpus;
+
+    for &(definitions, input) in corpus::CASES {
+        let (env, term) = corpus::load(definitions, input);
+        let expected = corpus::substitution(&env, &term, Strategy::CallByValue);
+        let actual = evaluate(&env, &term, corpus::FUEL).and_then(|r| DbTerm::from_node(&r.term));
+        assert_eq!(actual, expected, "evaluating {}", input);
+    }
+
+    // Recursion that is deep on the Rust stack of a direct interpreter
+    let (env, term) = corpus::load(corpus::ARITHMETIC, "fact 12");
+    assert_eq!(evaluate(&env, &term, 1_000_000).map(|r| r.term), Ok(ParseNode::literal_int(479001600)));
+}
+
+#[test]
+fn cps_rejects_unbound_continuations(){
+    let definitions = Definitions::convert(&Environment::new(), convert).unwrap();
+    let term = Cps::Continue(Cont::Variable("k".to_string()), CpsValue::Int(1));
+    assert_eq!(value::evaluate::<CpsClosure>(&definitions, &term, 10).map(|r| r.term),
+        Err(Error::InvalidAst("continuation k isn't bound".to_string())));
+}
@@ -0,0 +1,6 @@
+pub mod anf;
+pub mod cps;
+pub mod value;
+
+pub use self::anf::{Anf, Atom};
+pub use self::cps::{Cont, Cps, CpsValue};
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::thread;
+
+use parser::ParseNode;
+use errors::error_index::Error;
+use eval::{DbTerm, Environment, Primitive, PrimValue, Reduction};
+
+/// What the IR interpreters evaluate to. `C` is how each IR represents a
+/// closure.
+#[derive(Clone)]
+pub enum Value<C>{
+    Int(i32),
+    Closure(C),
+    Primitive(Primitive, Vec<Value<C>>),        // Carries the arguments so far
+    Stuck(Rc<Value<C>>, Vec<Value<C>>),         // Carries a head that can't be applied, and its arguments
+    Free(String)
+}
+
+/// Closures that can be read back as the abstraction they evaluate.
+pub trait Quote{
+    fn quote(&self) -> DbTerm;
+}
+
+impl<C: Quote + Clone> Value<C>{
+    pub fn quote(&self) -> DbTerm {
+        match *self {
+            Value::Int(val) => DbTerm::Int(val),
+            Value::Closure(ref closure) => closure.quote(),
+            Value::Free(ref name) => DbTerm::Free(name.clone()),
+            Value::Primitive(primitive, ref args) => quote_application(DbTerm::Free(primitive.name().to_string()), args),
+            Value::Stuck(ref head, ref args) => quote_application(head.quote(), args)
+        }
+    }
+
+    /// Applies a value that isn't a closure. Primitives fire once they have
+    /// two integers, with `boolean` giving the result of a comparison, and
+    /// everything else is stuck.
+    pub fn apply_head<B: Fn(bool) -> Value<C>>(self, argument: Value<C>, boolean: B) -> Value<C> {
+        match self {
+            Value::Primitive(primitive, mut args) => {
+                args.push(argument);
+                if args.len() < 2 {
+                    return Value::Primitive(primitive, args);
+                }
+                let result = match (&args[0], &args[1]) {
+                    (&Value::Int(a), &Value::Int(b)) => primitive.apply(a, b),
+                    _ => None
+                };
+                match result {
+                    Some(PrimValue::Int(val)) => Value::Int(val),
+                    Some(PrimValue::Bool(val)) => boolean(val),
+                    None => Value::Stuck(Rc::new(Value::Free(primitive.name().to_string())), args)
+                }
+            },
+            Value::Stuck(head, mut args) => {
+                args.push(argument);
+                Value::Stuck(head, args)
+            },
+            head => Value::Stuck(Rc::new(head), vec!(argument))
+        }
+    }
+}
+
+fn quote_application<C: Quote + Clone>(head: DbTerm, args: &[Value<C>]) -> DbTerm {
+    args.iter().fold(head, |function, arg| DbTerm::Application(Box::new(function), Box::new(arg.quote())))
+}
+
+/// Bindings of the enclosing scopes, innermost first.
+#[derive(Clone)]
+pub struct Env<B>(Option<Rc<(String, B, Env<B>)>>);
+
+impl<B> Default for Env<B>{
+    fn default() -> Env<B> {
+        Env(None)
+    }
+}
+
+impl<B> Env<B>{
+    pub fn push(&self, name: &str, binding: B) -> Env<B> {
+        Env(Some(Rc::new((name.to_string(), binding, Env(self.0.clone())))))
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&B> {
+        let mut env = self;
+        while let Some(ref link) = env.0 {
+            if link.0 == name {
+                return Some(&link.1);
+            }
+            env = &link.2;
+        }
+        None
+    }
+}
+
+/// Reads back a closure as `\param. body`, with the free names of the
+/// body that `env` binds replaced by what `value` quotes them as. Values
+/// are whole terms, so nothing needs shifting under binders.
+pub fn quote_closure<B, F: Fn(&B) -> Option<DbTerm>>(param: &str, body: ParseNode, env: &Env<B>, value: F) -> DbTerm {
+    let term = DbTerm::from_node(&ParseNode::abstraction(param, body)).expect("IRs only hold expressions");
+    replace_free(&term, &|name| env.lookup(name).and_then(&value))
+}
+
+fn replace_free(term: &DbTerm, value: &dyn Fn(&str) -> Option<DbTerm>) -> DbTerm {
+    match *term {
+        DbTerm::Free(ref name) => value(name).unwrap_or_else(|| term.clone()),
+        DbTerm::Abstraction(ref body) => DbTerm::Abstraction(Box::new(replace_free(body, value))),
+        DbTerm::Application(ref left, ref right) =>
+            DbTerm::Application(Box::new(replace_free(left, value)), Box::new(replace_free(right, value))),
+        _ => term.clone()
+    }
+}
+
+/// The closures of an IR, which tell the shared interpreter how to run its
+/// terms.
+pub trait IrClosure<'a>: Quote + Clone{
+    type Term: 'a;
+
+    /// Evaluates `term` with nothing in scope.
+    fn run(interpreter: &mut Interpreter<'a, Self>, term: &'a Self::Term) -> Result<Value<Self>, Error>;
+}
+
+/// Definitions converted to an IR, along with the booleans comparisons
+/// return.
+pub struct Definitions<T>{
+    terms: HashMap<String, T>,
+    booleans: [T; 2]
+}
+
+impl<T> Definitions<T>{
+    pub fn convert(env: &Environment, convert: fn(&ParseNode) -> Result<T, Error>) -> Result<Definitions<T>, Error> {
+        let terms = env.iter()
+            .map(|(name, value)| Ok((name.clone(), convert(value)?)))
+            .collect::<Result<_, Error>>()?;
+        let booleans = [convert(&PrimValue::Bool(false).to_node())?, convert(&PrimValue::Bool(true).to_node())?];
+        Ok(Definitions { terms, booleans })
+    }
+}
+
+/// How deeply the interpreters may recurse before giving up.
+const MAX_DEPTH: usize = 100_000;
+
+const STACK_SIZE: usize = 1 << 30;
+
+/// Evaluates `term` and reads back its value, with `definitions` evaluated
+/// once and shared. Steps count applications, and fail with `OutOfFuel`
+/// past `fuel`.
+pub fn evaluate<'a, C: IrClosure<'a>>(definitions: &'a Definitions<C::Term>, term: &'a C::Term, fuel: usize) -> Result<Reduction, Error>
+    where C::Term: Sync {
+    // Non-tail calls and definitions recurse on the Rust stack, so they get
+    // a stack that fits `MAX_DEPTH` levels
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || evaluate_here::<C>(definitions, term, fuel))
+            .expect("can't start a thread to evaluate on")
+            .join()
+            .expect("evaluating panicked")
+    })
+}
+
+fn evaluate_here<'a, C: IrClosure<'a>>(definitions: &'a Definitions<C::Term>, term: &'a C::Term, fuel: usize) -> Result<Reduction, Error> {
+    let mut interpreter = Interpreter { definitions, globals: HashMap::new(), booleans: vec!(), steps: 0, fuel, depth: 0 };
+    let booleans = definitions.booleans.iter().map(|b| C::run(&mut interpreter, b)).collect::<Result<_, _>>()?;
+    interpreter.booleans = booleans;
+    let value = C::run(&mut interpreter, term)?;
+    Ok(Reduction { term: value.quote().to_node(), steps: interpreter.steps })
+}
+
+/// What the interpreters of the IRs share: the definitions, their values
+/// so far, and the steps taken.
+pub struct Interpreter<'a, C: IrClosure<'a>>{
+    definitions: &'a Definitions<C::Term>,
+    globals: HashMap<String, Option<Value<C>>>,     // None while a definition is evaluated
+    booleans: Vec<Value<C>>,
+    steps: usize,
+    fuel: usize,
+    depth: usize                                    // Calls of `nested` in progress
+}
+
+impl<'a, C: IrClosure<'a>> Interpreter<'a, C>{
+    /// Runs `f` one level deeper, failing instead of running out of stack.
+    pub fn nested<T, F: FnOnce(&mut Self) -> Result<T, Error>>(&mut self, f: F) -> Result<T, Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::TooDeep(MAX_DEPTH));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Counts an application.
+    pub fn tick(&mut self) -> Result<(), Error> {
+        if self.steps == self.fuel {
+            return Err(Error::OutOfFuel(self.fuel));
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// Applies a value that isn't a closure.
+    pub fn apply_head(&self, head: Value<C>, argument: Value<C>) -> Value<C> {
+        head.apply_head(argument, |b| self.booleans[b as usize].clone())
+    }
+
+    /// The value of a name the term doesn't bind: a definition, evaluated
+    /// the first time it is used, a primitive or a free name.
+    pub fn global(&mut self, name: &str) -> Result<Value<C>, Error> {
+        let definitions = self.definitions;
+        let definition = match definitions.terms.get(name) {
+            Some(definition) => definition,
+            None => return Ok(match Primitive::from_name(name) {
+                Some(primitive) => Value::Primitive(primitive, vec!()),
+                None => Value::Free(name.to_string())
+            })
+        };
+        match self.globals.get(name) {
+            Some(Some(value)) => return Ok(value.clone()),
+            Some(None) => return Err(Error::InfiniteLoop),
+            None => ()
+        }
+        self.globals.insert(name.to_string(), None);
+        let value = self.nested(|interpreter| C::run(interpreter, definition))?;
+        self.globals.insert(name.to_string(), Some(value.clone()));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+pub fn parse(input: &str) -> ParseNode {
+    use lexer::Lexer;
+    use parser::Parser;
+    Parser::new(Lexer::new(input)).parse_expr().unwrap()
+}
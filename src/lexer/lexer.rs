@@ -1,4 +1,4 @@
-use super::Token;
+use super::{Token, Span};
 
 use std::str::Chars;
 use std::iter::Peekable;
@@ -6,36 +6,84 @@ use std::iter::Peekable;
 pub struct Lexer<'a>{
     cached_str: &'a str,
     input: Peekable<Chars<'a>>,
-    cache: Vec<Token>
+    cache: Vec<(Token, Span)>,
+    position: usize,
+    spans: Vec<Span>,                   // Of the tokens returned and not put back
+    comments: Vec<Span>
 }
 
 impl<'a> Lexer<'a>{
     pub fn new(input: &str) -> Lexer{
-        Lexer {input: input.chars().peekable(), cache: Vec::new(), cached_str: input}
+        Lexer {
+            input: input.chars().peekable(),
+            cache: Vec::new(),
+            cached_str: input,
+            position: 0,
+            spans: Vec::new(),
+            comments: Vec::new()
+        }
     }
 
     pub fn reset(&mut self){
         self.input = self.cached_str.chars().peekable();
+        self.position = 0;
+        self.spans.clear();
+        self.comments.clear();
     }
 
+    /// Returns the token most recently read to the input, so it is read
+    /// again next, with its own span.
     pub fn put_back(&mut self, token: Token){
-        self.cache.push(token);
+        let span = self.spans.pop().unwrap_or_default();
+        self.cache.push((token, span));
+    }
+
+    /// Looks at the next token without consuming it.
+    pub fn peek_token(&mut self) -> Token{
+        let token = self.next_token();
+        self.put_back(token.clone());
+        token
+    }
+
+    /// Span of the token most recently returned by `next_token` and not
+    /// put back.
+    pub fn span(&self) -> Span{
+        self.spans.last().copied().unwrap_or_default()
+    }
+
+    /// Spans of the `--` comments skipped so far, in source order.
+    pub fn comments(&self) -> &[Span]{
+        &self.comments
+    }
+
+    /// Reads the next token along with its byte span in the input.
+    pub fn next_spanned(&mut self) -> (Token, Span){
+        let token = self.next_token();
+        (token, self.span())
     }
 
     pub fn next_token(&mut self) -> Token{
-        if let Some(top) = self.cache.pop() {
+        if let Some((top, span)) = self.cache.pop() {
+            self.spans.push(span);
             return top;
         }
 
         self.skip_whitespace();
+        let start = self.position;
 
-        match self.read_char(){
+        let token = match self.read_char(){
             Some('=') => Token::Assign,
             Some('.') => Token::Dot,
             Some('(') => Token::LParen,
             Some(')') => Token::RParen,
             Some('\\') => Token::Backslash,
             Some(':') => Token::Colon,
+            Some('-') if self.peek_char() == Some(&'>') => {
+                self.read_char();
+                Token::Arrow
+            },
+            // A negative literal, so every integer prints as something that reads back
+            Some('-') if self.peek_char().is_some_and(|c| c.is_ascii_digit()) => Token::Integer(self.read_number('-')),
 
             Some(ch @ _) => {
                 match ch {
@@ -46,19 +94,44 @@ impl<'a> Lexer<'a>{
                 }
             }
             None => Token::EOF
-        }
+        };
+        self.spans.push(Span::new(start, self.position));
+        token
     }
 
+    /// Whether only whitespace and comments are left, counting tokens that
+    /// were put back.
     pub fn is_empty(&mut self) -> bool{
-        self.peek_char().is_none()
+        match self.cache.last() {
+            Some(&(Token::EOF, _)) => true,
+            Some(_) => false,
+            None => {
+                self.skip_whitespace();
+                self.peek_char().is_none()
+            }
+        }
     }
 
+    /// Skips whitespace and `--` comments, which run to the end of the line.
     fn skip_whitespace(&mut self){
-        while let Some(&c) = self.peek_char(){
-            if !c.is_whitespace(){
+        loop {
+            while let Some(&c) = self.peek_char(){
+                if !c.is_whitespace(){
+                    break;
+                }
+                self.read_char();
+            }
+            if !self.cached_str[self.position..].starts_with("--"){
                 break;
             }
-            self.read_char();
+            let start = self.position;
+            while let Some(&c) = self.peek_char(){
+                if c == '\n'{
+                    break;
+                }
+                self.read_char();
+            }
+            self.comments.push(Span::new(start, self.position));
         }
     }
 
@@ -92,8 +165,12 @@ impl<'a> Lexer<'a>{
     }
 
     fn read_char(&mut self) -> Option<char>{
-        self.input.next()
-    } 
+        let ch = self.input.next();
+        if let Some(c) = ch {
+            self.position += c.len_utf8();
+        }
+        ch
+    }
 
     fn peek_char(&mut self) -> Option<&char>{
         self.input.peek()
@@ -184,3 +261,89 @@ fn next_token_cache(){
 
 }
 
+#[test]
+fn next_token_spans(){
+    let input = "  ab (\\x. 12)";
+
+    let expected = vec![
+        (Token::LIdent("ab".to_string()), Span::new(2, 4)),
+        (Token::LParen, Span::new(5, 6)),
+        (Token::Backslash, Span::new(6, 7)),
+        (Token::LIdent("x".to_string()), Span::new(7, 8)),
+        (Token::Dot, Span::new(8, 9)),
+        (Token::Integer("12".to_string()), Span::new(10, 12)),
+        (Token::RParen, Span::new(12, 13)),
+        (Token::EOF, Span::new(13, 13)),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for e in expected{
+        assert_eq!(lexer.next_spanned(), e)
+    }
+}
+
+#[test]
+fn put_back_keeps_spans(){
+    let mut lexer = Lexer::new("(a b)");
+
+    lexer.next_token();
+    lexer.next_token();
+    let b = lexer.next_token();
+    let rparen = lexer.next_token();
+    lexer.put_back(rparen);
+    lexer.put_back(b);
+    assert_eq!(lexer.span(), Span::new(1, 2));
+    assert_eq!(lexer.next_spanned(), (Token::LIdent("b".to_string()), Span::new(3, 4)));
+    assert_eq!(lexer.next_spanned(), (Token::RParen, Span::new(4, 5)));
+}
+
+#[test]
+fn span_line_column(){
+    let input = "id = \\x. x\n\nmain = id é y";
+    assert_eq!(Span::new(0, 2).line_column(input), (1, 1));
+    assert_eq!(Span::new(8, 9).line_column(input), (1, 9));
+    assert_eq!(Span::new(25, 26).line_column(input), (3, 13));
+}
+
+#[test]
+fn next_token_arrow(){
+    let mut lexer = Lexer::new("A->B - C");
+
+    assert_eq!(lexer.next_token(), Token::UIdent("A".to_string()));
+    assert_eq!(lexer.next_spanned(), (Token::Arrow, Span::new(1, 3)));
+    assert_eq!(lexer.next_token(), Token::UIdent("B".to_string()));
+    assert_eq!(lexer.next_token(), Token::Illegal);
+}
+
+#[test]
+fn next_token_negative_integers(){
+    let mut lexer = Lexer::new("sub -12 - 3 --4");
+
+    assert_eq!(lexer.next_token(), Token::LIdent("sub".to_string()));
+    assert_eq!(lexer.next_spanned(), (Token::Integer("-12".to_string()), Span::new(4, 7)));
+    assert_eq!(lexer.next_token(), Token::Illegal);
+    assert_eq!(lexer.next_token(), Token::Integer("3".to_string()));
+    assert_eq!(lexer.next_token(), Token::EOF);
+}
+
+#[test]
+fn next_token_skips_comments(){
+    let input = "-- identity\nid = \\x. x -- trailing\n";
+    let mut lexer = Lexer::new(input);
+
+    assert_eq!(lexer.next_spanned(), (Token::LIdent("id".to_string()), Span::new(12, 14)));
+    while lexer.next_token() != Token::EOF {}
+    assert_eq!(lexer.comments(), &[Span::new(0, 11), Span::new(23, 34)]);
+}
+
+#[test]
+fn is_empty_counts_put_back_tokens(){
+    let mut lexer = Lexer::new("a ) ");
+    assert_eq!(lexer.next_token(), Token::LIdent("a".to_string()));
+    assert!(!lexer.is_empty());
+    assert_eq!(lexer.peek_token(), Token::RParen);
+    assert!(!lexer.is_empty());
+    assert_eq!(lexer.next_token(), Token::RParen);
+    assert!(lexer.is_empty());
+}
@@ -2,4 +2,5 @@ pub mod lexer;
 pub mod token;
 
 pub use self::token::Token;
+pub use self::token::Span;
 pub use self::lexer::Lexer;
@@ -15,5 +15,32 @@ pub enum Token{
     LParen,
     RParen,
     Colon,
-    Assign
+    Assign,
+    Arrow
+}
+
+/// Byte range of a token within the lexer's input.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span{
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span{
+    pub fn new(start: usize, end: usize) -> Span{
+        Span { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span{
+        Span::new(self.start, other.end)
+    }
+
+    /// The line and column the span starts at in `source`, both counted
+    /// from 1.
+    pub fn line_column(&self, source: &str) -> (usize, usize){
+        let before = source.get(..self.start).unwrap_or(source);
+        let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+        (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+    }
 }
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use parser::ParseNode;
+use errors::error_index::Error;
+use eval::{DbTerm, Environment, Primitive, PrimValue, Reduction};
+
+/// Runs `term` to a value on a CEK machine, with call-by-value evaluation
+/// in the same order as `Strategy::CallByValue`. Definitions in `env` are
+/// unfolded once and their values shared. Steps count applications and
+/// unfoldings, and fail with `OutOfFuel` past `fuel`.
+pub fn evaluate(env: &Environment, term: &ParseNode, fuel: usize) -> Result<Reduction, Error> {
+    let definitions = env.iter()
+        .map(|(name, value)| Ok((name.clone(), DbTerm::from_node(value)?)))
+        .collect::<Result<HashMap<_, _>, Error>>()?;
+    let term = DbTerm::from_node(term)?;
+    // Bodies of the Church booleans that comparisons return
+    let booleans = [
+        DbTerm::Abstraction(Box::new(DbTerm::Bound(1))),
+        DbTerm::Abstraction(Box::new(DbTerm::Bound(0)))
+    ];
+
+    let mut machine = Cek { definitions: &definitions, booleans: &booleans, cache: HashMap::new(), steps: 0, fuel };
+    let value = machine.run(&term)?;
+    Ok(Reduction { term: value.quote().to_node(), steps: machine.steps })
+}
+
+#[derive(Clone)]
+enum Value<'a>{
+    Int(i32),
+    Closure(&'a DbTerm, Env<'a>),               // Carries the abstraction's body
+    Primitive(Primitive, Vec<Value<'a>>),       // Carries the arguments so far
+    Stuck(Rc<Value<'a>>, Vec<Value<'a>>),       // Carries a head that can't be applied, and its arguments
+    Free(String)
+}
+
+/// Values of the variables in scope, innermost first.
+#[derive(Clone, Default)]
+struct Env<'a>(Option<Rc<(Value<'a>, Env<'a>)>>);
+
+impl<'a> Env<'a>{
+    fn push(&self, value: Value<'a>) -> Env<'a> {
+        Env(Some(Rc::new((value, self.clone()))))
+    }
+
+    fn lookup(&self, index: usize) -> &Value<'a> {
+        let mut env = self;
+        for _ in 0..index {
+            env = &env.0.as_ref().expect("index out of scope").1;
+        }
+        &env.0.as_ref().expect("index out of scope").0
+    }
+}
+
+/// What to do with the value being computed.
+enum Frame<'a>{
+    Argument(&'a DbTerm, Env<'a>),      // Evaluate the argument of an application
+    Apply(Value<'a>),                   // Apply the function value to it
+    Share(&'a str)                      // Remember it as the value of a definition
+}
+
+enum State<'a>{
+    Eval(&'a DbTerm, Env<'a>),
+    Return(Value<'a>)
+}
+
+struct Cek<'a>{
+    definitions: &'a HashMap<String, DbTerm>,
+    booleans: &'a [DbTerm; 2],
+    cache: HashMap<&'a str, Value<'a>>,
+    steps: usize,
+    fuel: usize
+}
+
+impl<'a> Cek<'a>{
+    fn run(&mut self, term: &'a DbTerm) -> Result<Value<'a>, Error> {
+        let mut stack: Vec<Frame<'a>> = vec!();
+        let mut state = State::Eval(term, Env::default());
+        loop {
+            state = match state {
+                State::Eval(term, env) => match *term {
+                    DbTerm::Bound(index) => State::Return(env.lookup(index).clone()),
+                    DbTerm::Int(val) => State::Return(Value::Int(val)),
+                    DbTerm::Abstraction(ref body) => State::Return(Value::Closure(body, env)),
+                    DbTerm::Application(ref left, ref right) => {
+                        stack.push(Frame::Argument(right, env.clone()));
+                        State::Eval(left, env)
+                    },
+                    DbTerm::Free(ref name) => self.global(name, &mut stack)?
+                },
+                State::Return(value) => match stack.pop() {
+                    None => return Ok(value),
+                    Some(Frame::Argument(term, env)) => {
+                        stack.push(Frame::Apply(value));
+                        State::Eval(term, env)
+                    },
+                    Some(Frame::Apply(function)) => self.apply(function, value)?,
+                    Some(Frame::Share(name)) => {
+                        self.cache.insert(name, value.clone());
+                        State::Return(value)
+                    }
+                }
+            }
+        }
+    }
+
+    /// A definition's value, a primitive, or a variable that is free.
+    fn global(&mut self, name: &'a str, stack: &mut Vec<Frame<'a>>) -> Result<State<'a>, Error> {
+        if let Some(value) = self.cache.get(name) {
+            return Ok(State::Return(value.clone()));
+        }
+        if let Some(body) = self.definitions.get(name) {
+            self.tick()?;
+            stack.push(Frame::Share(name));
+            return Ok(State::Eval(body, Env::default()));
+        }
+        Ok(State::Return(match Primitive::from_name(name) {
+            Some(primitive) => Value::Primitive(primitive, vec!()),
+            None => Value::Free(name.to_string())
+        }))
+    }
+
+    fn apply(&mut self, function: Value<'a>, argument: Value<'a>) -> Result<State<'a>, Error> {
+        let value = match function {
+            Value::Closure(body, env) => {
+                self.tick()?;
+                return Ok(State::Eval(body, env.push(argument)));
+            },
+            Value::Primitive(primitive, mut args) => {
+                args.push(argument);
+                if args.len() < 2 {
+                    Value::Primitive(primitive, args)
+                } else {
+                    let result = match (&args[0], &args[1]) {
+                        (&Value::Int(a), &Value::Int(b)) => primitive.apply(a, b),
+                        _ => None
+                    };
+                    match result {
+                        Some(result) => {
+                            self.tick()?;
+                            self.prim_value(result)
+                        },
+                        None => Value::Stuck(Rc::new(Value::Free(primitive.name().to_string())), args)
+                    }
+                }
+            },
+            Value::Stuck(head, mut args) => {
+                args.push(argument);
+                Value::Stuck(head, args)
+            },
+            head => Value::Stuck(Rc::new(head), vec!(argument))
+        };
+        Ok(State::Return(value))
+    }
+
+    fn prim_value(&self, value: PrimValue) -> Value<'a> {
+        match value {
+            PrimValue::Int(val) => Value::Int(val),
+            PrimValue::Bool(val) => Value::Closure(&self.booleans[if val { 0 } else { 1 }], Env::default())
+        }
+    }
+
+    fn tick(&mut self) -> Result<(), Error> {
+        if self.steps == self.fuel {
+            return Err(Error::OutOfFuel(self.fuel));
+        }
+        self.steps += 1;
+        Ok(())
+    }
+}
+
+impl<'a> Value<'a>{
+    /// The value as a closed term. Closures are read back by putting the
+    /// values of their environment in place of the variables they capture.
+    fn quote(&self) -> DbTerm {
+        match *self {
+            Value::Int(val) => DbTerm::Int(val),
+            Value::Free(ref name) => DbTerm::Free(name.clone()),
+            Value::Closure(body, ref env) => DbTerm::Abstraction(Box::new(quote_body(body, env, 1))),
+            Value::Primitive(primitive, ref args) =>
+                quote_application(DbTerm::Free(primitive.name().to_string()), args),
+            Value::Stuck(ref head, ref args) => quote_application(head.quote(), args)
+        }
+    }
+}
+
+fn quote_application(head: DbTerm, args: &[Value]) -> DbTerm {
+    args.iter().fold(head, |function, arg| DbTerm::Application(Box::new(function), Box::new(arg.quote())))
+}
+
+/// `term` with the variables that point past its `local` binders replaced
+/// by their values in `env`. Values quote to closed terms, so they need no
+/// shifting.
+fn quote_body(term: &DbTerm, env: &Env, local: usize) -> DbTerm {
+    match *term {
+        DbTerm::Bound(index) if index < local => DbTerm::Bound(index),
+        DbTerm::Bound(index) => env.lookup(index - local).quote(),
+        DbTerm::Abstraction(ref body) => DbTerm::Abstraction(Box::new(quote_body(body, env, local + 1))),
+        DbTerm::Application(ref left, ref right) =>
+            DbTerm::Application(Box::new(quote_body(left, env, local)), Box::new(quote_body(right, env, local))),
+        _ => term.clone()
+    }
+}
+
+#[test]
+fn cek_matches_call_by_value(){
+    use eval::Strategy;
+    use super::corpus;
+
+    for &(definitions, input) in corpus::CASES {
+        let (env, term) = corpus::load(definitions, input);
+        let expected = corpus::substitution(&env, &term, Strategy::CallByValue);
+        let actual = evaluate(&env, &term, corpus::FUEL).map(|r| DbTerm::from_node(&r.term).unwrap());
+        assert_eq!(actual, expected, "evaluating {}", input);
+    }
+}
+
+#[test]
+fn cek_runs_long_programs(){
+    use super::corpus;
+
+    let (env, term) = corpus::load(corpus::ARITHMETIC, "fact 10");
+    let result = evaluate(&env, &term, 1_000_000).unwrap();
+    assert_eq!(result.term, ParseNode::literal_int(3628800));
+
+    let (env, term) = corpus::load(corpus::ARITHMETIC, "fib 20");
+    let result = evaluate(&env, &term, 1_000_000).unwrap();
+    assert_eq!(result.term, ParseNode::literal_int(6765));
+    assert!(result.steps > 100_000);
+
+    let (env, term) = corpus::load(corpus::CHURCH, "to_int (exp two (mul_c two (add_c two two)))");
+    let result = evaluate(&env, &term, 1_000_000).unwrap();
+    assert_eq!(result.term, ParseNode::literal_int(256));
+}
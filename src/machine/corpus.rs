@@ -0,0 +1,89 @@
+use lexer::Lexer;
+use parser::{Parser, ParseNode};
+use errors::error_index::Error;
+use eval::{DbTerm, Environment, Reducer, Strategy};
+
+pub const FUEL: usize = 100000;
+
+pub const CHURCH: &str = "
+    zero = \\f. \\x. x
+    succ = \\n. \\f. \\x. f (n f x)
+    two = succ (succ zero)
+    add_c = \\m. \\n. \\f. \\x. m f (n f x)
+    mul_c = \\m. \\n. \\f. m (n f)
+    exp = \\m. \\n. n m
+    to_int = \\n. n (\\k. add k 1) 0";
+
+pub const CHURCH_RECURSION: &str = "
+    zero = \\f. \\x. x
+    succ = \\n. \\f. \\x. f (n f x)
+    one = succ zero
+    three = succ (succ one)
+    mul_c = \\m. \\n. \\f. m (n f)
+    pred = \\n. \\f. \\x. n (\\g. \\h. h (g f)) (\\u. x) (\\u. u)
+    is_zero = \\n. n (\\x. \\t. \\f. f) (\\t. \\f. t)
+    y = \\f. (\\x. f (x x)) (\\x. f (x x))
+    fact = y (\\fact. \\n. is_zero n one (mul_c n (fact (pred n))))";
+
+pub const ARITHMETIC: &str = "
+    z = \\f. (\\x. f (\\v. x x v)) (\\x. f (\\v. x x v))
+    if = \\c. \\t. \\e. c t e 0
+    fact = z (\\fact. \\n. if (eq n 0) (\\u. 1) (\\u. mul n (fact (sub n 1))))
+    fib = z (\\fib. \\n. if (lt n 2) (\\u. n) (\\u. add (fib (sub n 1)) (fib (sub n 2))))";
+
+pub const COMBINATORS: &str = "
+    id = \\x. x
+    k = \\x. \\y. x
+    s = \\x. \\y. \\z. x z (y z)
+    compose = \\f. \\g. \\x. f (g x)";
+
+/// Definitions, and an expression to evaluate with them. Every evaluator
+/// must agree with the substitution reducer on these, up to renaming.
+pub const CASES: &[(&str, &str)] = &[
+    ("", "\\x. x"),
+    ("", "(\\x. x) 3"),
+    ("", "(\\x. \\y. x) 5"),
+    ("", "(\\x. \\y. x y) (\\z. z)"),
+    ("", "(\\x. \\y. y x) ((\\z. z) 1)"),
+    ("", "add 2 3"),
+    ("", "sub (mul 3 4) (div 9 2)"),
+    ("", "lt 1 2"),
+    ("", "eq 1 2 10 20"),
+    ("", "div 1 0"),
+    ("", "add x 1"),
+    ("", "f ((\\x. x) 1) (g 2)"),
+    ("", "(\\f. f 1 2) add"),
+    ("", "(\\f. f 1) add"),
+    ("", "(\\x. x x) (\\y. y)"),
+    (COMBINATORS, "s k k 7"),
+    (COMBINATORS, "compose (add 1) (mul 2) 5"),
+    (COMBINATORS, "k id"),
+    (COMBINATORS, "s k"),
+    (COMBINATORS, "k (\\y. y y)"),
+    (CHURCH, "to_int (add_c two (mul_c two two))"),
+    (CHURCH, "to_int (exp two two)"),
+    (CHURCH, "mul_c two"),
+    (ARITHMETIC, "fact 5"),
+    (ARITHMETIC, "fib 7"),
+    (ARITHMETIC, "if (lt 2 1)"),
+];
+
+pub fn load(definitions: &str, input: &str) -> (Environment, ParseNode) {
+    let mut env = Environment::new();
+    if !definitions.is_empty() {
+        env.load(&Parser::new(Lexer::new(definitions)).parse().unwrap());
+    }
+    (env, Parser::new(Lexer::new(input)).parse_expr().unwrap())
+}
+
+/// What the substitution reducer gives for `term`, up to renaming.
+pub fn substitution(env: &Environment, term: &ParseNode, strategy: Strategy) -> Result<DbTerm, Error> {
+    let reduction = Reducer::new(env, strategy).normalize(term, FUEL)?;
+    DbTerm::from_node(&reduction.term)
+}
+
+/// The beta normal form of `term`, which evaluators that stop earlier than
+/// normal form are compared by.
+pub fn normal_form(env: &Environment, term: &ParseNode) -> Result<DbTerm, Error> {
+    substitution(env, term, Strategy::Normal)
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::iter;
+use std::rc::Rc;
+
+use parser::ParseNode;
+use errors::error_index::Error;
+use eval::{DbTerm, Environment};
+
+/// A term paired with the closures its free indices refer to.
+#[derive(Debug, Clone)]
+pub struct Closure{
+    pub term: DbTerm,
+    pub env: Env
+}
+
+/// The closures of the enclosing binders, innermost first. Pushing shares
+/// the rest, so closures capture their environment without copying it.
+#[derive(Debug, Clone, Default)]
+pub struct Env(Option<Rc<(Rc<Closure>, Env)>>);
+
+/// One configuration of the machine: the term in head position, the
+/// closures of its bound variables, and the arguments waiting for it.
+#[derive(Debug, Clone)]
+pub struct State{
+    pub term: DbTerm,
+    pub env: Env,
+    pub stack: Vec<Rc<Closure>>     // Next argument last
+}
+
+/// Krivine's machine, which evaluates call-by-name to weak head normal
+/// form. Definitions are unfolded when they reach head position. It has no
+/// primitives, so an integer or primitive in head position stops it like
+/// any other free variable.
+pub struct Krivine{
+    definitions: HashMap<String, DbTerm>,
+    pub state: State,
+    pub steps: usize
+}
+
+impl Krivine{
+    pub fn new(env: &Environment, term: &ParseNode) -> Result<Krivine, Error> {
+        let definitions = env.iter()
+            .map(|(name, value)| Ok((name.clone(), DbTerm::from_node(value)?)))
+            .collect::<Result<_, Error>>()?;
+        Ok(Krivine {
+            definitions,
+            state: State { term: DbTerm::from_node(term)?, env: Env::default(), stack: vec!() },
+            steps: 0
+        })
+    }
+
+    /// Makes one transition, or returns false if the machine has stopped.
+    fn step(&mut self) -> bool {
+        let state = &mut self.state;
+        let next = match state.term {
+            // Push the argument, closed over the current environment
+            DbTerm::Application(ref left, ref right) => {
+                state.stack.push(Rc::new(Closure { term: (**right).clone(), env: state.env.clone() }));
+                (**left).clone()
+            },
+            // Pop an argument into the environment
+            DbTerm::Abstraction(ref body) => match state.stack.pop() {
+                Some(argument) => {
+                    state.env = state.env.push(argument);
+                    (**body).clone()
+                },
+                None => return false
+            },
+            // Continue with the closure the variable refers to
+            DbTerm::Bound(index) => match state.env.lookup(index).cloned() {
+                Some(closure) => {
+                    state.env = closure.env.clone();
+                    closure.term.clone()
+                },
+                None => return false
+            },
+            DbTerm::Free(ref name) => match self.definitions.get(name) {
+                Some(body) => {
+                    state.env = Env::default();
+                    body.clone()
+                },
+                None => return false
+            },
+            DbTerm::Int(_) => return false
+        };
+        state.term = next;
+        self.steps += 1;
+        true
+    }
+
+    /// Runs until the machine stops, or fails with `OutOfFuel` after `fuel`
+    /// transitions.
+    pub fn run(&mut self, fuel: usize) -> Result<ParseNode, Error> {
+        self.run_with(fuel, |_, _| ())
+    }
+
+    /// Like `run`, calling `visit` with every state the machine passes
+    /// through, the first one included, and the transitions taken so far.
+    pub fn run_with<F: FnMut(&State, usize)>(&mut self, fuel: usize, mut visit: F) -> Result<ParseNode, Error> {
+        visit(&self.state, self.steps);
+        while self.step() {
+            if self.steps > fuel {
+                return Err(Error::OutOfFuel(fuel));
+            }
+            visit(&self.state, self.steps);
+        }
+        Ok(self.state.to_node())
+    }
+}
+
+impl State{
+    /// The term this state stands for, with every closure read back and the
+    /// arguments on the stack applied.
+    pub fn to_node(&self) -> ParseNode {
+        let head = substitute(&self.term, &self.env, 0);
+        self.stack.iter().rev()
+            .fold(head, |function, arg| DbTerm::Application(Box::new(function), Box::new(arg.to_term())))
+            .to_node()
+    }
+}
+
+impl Closure{
+    fn to_term(&self) -> DbTerm {
+        substitute(&self.term, &self.env, 0)
+    }
+}
+
+impl Env{
+    fn push(&self, closure: Rc<Closure>) -> Env {
+        Env(Some(Rc::new((closure, self.clone()))))
+    }
+
+    fn lookup(&self, index: usize) -> Option<&Rc<Closure>> {
+        self.iter().nth(index)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rc<Closure>> {
+        let mut env = self;
+        iter::from_fn(move || env.0.as_ref().map(|link| {
+            env = &link.1;
+            &link.0
+        }))
+    }
+}
+
+/// `term` with the indices that point past its `local` binders replaced by
+/// their closures in `env`, which read back to closed terms.
+fn substitute(term: &DbTerm, env: &Env, local: usize) -> DbTerm {
+    match *term {
+        DbTerm::Bound(index) if index < local => DbTerm::Bound(index),
+        DbTerm::Bound(index) => env.lookup(index - local).map_or_else(|| term.clone(), |closure| closure.to_term()),
+        DbTerm::Abstraction(ref body) => DbTerm::Abstraction(Box::new(substitute(body, env, local + 1))),
+        DbTerm::Application(ref left, ref right) =>
+            DbTerm::Application(Box::new(substitute(left, env, local)), Box::new(substitute(right, env, local))),
+        _ => term.clone()
+    }
+}
+
+fn fmt_closures<'a, I: Iterator<Item = &'a Rc<Closure>>>(f: &mut fmt::Formatter, closures: I) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, closure) in closures.enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", closure)?;
+    }
+    write!(f, "]")
+}
+
+impl fmt::Display for Closure{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.env.is_empty() {
+            write!(f, "{}", self.term)
+        } else {
+            write!(f, "⟨{}, ", self.term)?;
+            fmt_closures(f, self.env.iter())?;
+            write!(f, "⟩")
+        }
+    }
+}
+
+impl fmt::Display for State{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} | env ", self.term)?;
+        fmt_closures(f, self.env.iter())?;
+        write!(f, " | stack ")?;
+        // Shown with the next argument first
+        fmt_closures(f, self.stack.iter().rev())
+    }
+}
+
+#[test]
+fn krivine_states(){
+    use super::corpus;
+
+    let (env, term) = corpus::load("", "(\\x. \\y. x) a b");
+    let mut machine = Krivine::new(&env, &term).unwrap();
+    let mut states = vec!(machine.state.to_string());
+    while machine.step() {
+        states.push(machine.state.to_string());
+    }
+    assert_eq!(states, vec![
+        "(λ λ 1) a b | env [] | stack []",
+        "(λ λ 1) a | env [] | stack [b]",
+        "λ λ 1 | env [] | stack [a, b]",
+        "λ 1 | env [a] | stack [b]",
+        "1 | env [b, a] | stack []",
+        "a | env [] | stack []",
+    ]);
+    assert_eq!(machine.state.to_node(), ParseNode::variable("a"));
+}
+
+#[test]
+fn krivine_agrees_with_call_by_name(){
+    use eval::Strategy;
+    use super::corpus;
+
+    for &(definitions, input) in corpus::CASES {
+        let (env, term) = corpus::load(definitions, input);
+        // Without primitives it stops earlier, so results are compared by
+        // their normal forms
+        let expected = corpus::substitution(&env, &term, Strategy::CallByName)
+            .and_then(|t| corpus::normal_form(&env, &t.to_node()));
+        let actual = Krivine::new(&env, &term).unwrap().run(corpus::FUEL)
+            .and_then(|t| corpus::normal_form(&env, &t));
+        assert_eq!(actual, expected, "evaluating {}", input);
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use parser::ParseNode;
+use errors::error_index::Error;
+use eval::{DbTerm, Environment, Primitive, PrimValue, Reduction};
+
+/// How much work sharing saved: every argument becomes a thunk, but only
+/// the ones that are needed are forced, and each of those only once.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Sharing{
+    pub created: usize,
+    pub forced: usize
+}
+
+impl fmt::Display for Sharing{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} thunk{} created, {} forced", self.created,
+            if self.created == 1 { "" } else { "s" }, self.forced)
+    }
+}
+
+/// Evaluates `main` of `program` by need.
+pub fn run_program(program: &ParseNode, fuel: usize) -> Result<(Reduction, Sharing), Error> {
+    let mut env = Environment::new();
+    env.load(program);
+    let main = env.get("main").cloned()
+        .ok_or_else(|| Error::InvalidAst("the program has no 'main' definition".to_string()))?;
+    evaluate(&env, &main, fuel)
+}
+
+/// Runs `term` to weak head normal form by need. Arguments and definitions
+/// are allocated as thunks on a heap, and a thunk is overwritten with its
+/// value the first time it's forced. Steps count beta reductions and
+/// primitive applications, and fail with `OutOfFuel` past `fuel`.
+pub fn evaluate(env: &Environment, term: &ParseNode, fuel: usize) -> Result<(Reduction, Sharing), Error> {
+    let definitions = env.iter()
+        .map(|(name, value)| Ok((name.clone(), DbTerm::from_node(value)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let term = DbTerm::from_node(term)?;
+    // Bodies of the Church booleans that comparisons return
+    let booleans = [
+        DbTerm::Abstraction(Box::new(DbTerm::Bound(1))),
+        DbTerm::Abstraction(Box::new(DbTerm::Bound(0)))
+    ];
+
+    let mut machine = Machine {
+        heap: vec!(),
+        globals: HashMap::new(),
+        booleans: &booleans,
+        sharing: Sharing::default(),
+        steps: 0,
+        fuel
+    };
+    for (name, body) in &definitions {
+        let address = machine.allocate(Thunk::Delayed(body, Env::default()));
+        machine.globals.insert(name.as_str(), address);
+    }
+    let value = machine.run(&term)?;
+    let reduction = Reduction { term: machine.quote(&value).to_node(), steps: machine.steps };
+    Ok((reduction, machine.sharing))
+}
+
+#[derive(Clone)]
+enum Value<'a>{
+    Int(i32),
+    Closure(&'a DbTerm, Env),               // Carries the abstraction's body
+    Primitive(Primitive, Vec<usize>),       // Carries the arguments so far
+    Stuck(Rc<Value<'a>>, Vec<usize>),       // Carries a head that can't be applied, and its arguments
+    Free(String)
+}
+
+enum Thunk<'a>{
+    Delayed(&'a DbTerm, Env),
+    Forcing,                                // Being evaluated, so needing it again is a loop
+    Done(Value<'a>)
+}
+
+/// Heap addresses of the variables in scope, innermost first.
+#[derive(Clone, Default)]
+struct Env(Option<Rc<(usize, Env)>>);
+
+impl Env{
+    fn push(&self, address: usize) -> Env {
+        Env(Some(Rc::new((address, self.clone()))))
+    }
+
+    fn lookup(&self, index: usize) -> usize {
+        let mut env = self;
+        for _ in 0..index {
+            env = &env.0.as_ref().expect("index out of scope").1;
+        }
+        env.0.as_ref().expect("index out of scope").0
+    }
+}
+
+/// What to do with the value being computed.
+enum Frame{
+    Argument(usize),                            // Apply it to the thunk at this address
+    Update(usize),                              // Overwrite the thunk at this address with it
+    Operand(Primitive, Vec<usize>, Vec<i32>)    // Carries a primitive's arguments, and the ones forced so far
+}
+
+enum State<'a>{
+    Eval(&'a DbTerm, Env),
+    Force(usize),
+    Return(Value<'a>)
+}
+
+struct Machine<'a>{
+    heap: Vec<Thunk<'a>>,
+    globals: HashMap<&'a str, usize>,
+    booleans: &'a [DbTerm; 2],
+    sharing: Sharing,
+    steps: usize,
+    fuel: usize
+}
+
+impl<'a> Machine<'a>{
+    fn run(&mut self, term: &'a DbTerm) -> Result<Value<'a>, Error> {
+        let mut stack = vec!();
+        let mut state = State::Eval(term, Env::default());
+        loop {
+            state = match state {
+                State::Eval(term, env) => match *term {
+                    DbTerm::Bound(index) => State::Force(env.lookup(index)),
+                    DbTerm::Int(val) => State::Return(Value::Int(val)),
+                    DbTerm::Abstraction(ref body) => State::Return(Value::Closure(body, env)),
+                    DbTerm::Application(ref left, ref right) => {
+                        let argument = self.delay(right, &env);
+                        stack.push(Frame::Argument(argument));
+                        State::Eval(left, env)
+                    },
+                    DbTerm::Free(ref name) => match self.globals.get(name.as_str()) {
+                        Some(&address) => State::Force(address),
+                        None => State::Return(match Primitive::from_name(name) {
+                            Some(primitive) => Value::Primitive(primitive, vec!()),
+                            None => Value::Free(name.clone())
+                        })
+                    }
+                },
+                State::Force(address) => match self.heap[address] {
+                    Thunk::Done(ref value) => State::Return(value.clone()),
+                    Thunk::Forcing => return Err(Error::InfiniteLoop),
+                    Thunk::Delayed(term, ref env) => {
+                        let env = env.clone();
+                        self.heap[address] = Thunk::Forcing;
+                        self.sharing.forced += 1;
+                        stack.push(Frame::Update(address));
+                        State::Eval(term, env)
+                    }
+                },
+                State::Return(value) => match stack.pop() {
+                    None => return Ok(value),
+                    Some(Frame::Update(address)) => {
+                        self.heap[address] = Thunk::Done(value.clone());
+                        State::Return(value)
+                    },
+                    Some(Frame::Argument(argument)) => self.apply(value, argument, &mut stack)?,
+                    Some(Frame::Operand(primitive, args, mut forced)) => match value {
+                        Value::Int(val) => {
+                            forced.push(val);
+                            self.operate(primitive, args, forced, &mut stack)?
+                        },
+                        _ => State::Return(Value::Stuck(Rc::new(Value::Free(primitive.name().to_string())), args))
+                    }
+                }
+            }
+        }
+    }
+
+    /// A thunk for `term`. A variable needs no new thunk, it passes on the
+    /// one it refers to, which is what shares a value between all its uses.
+    fn delay(&mut self, term: &'a DbTerm, env: &Env) -> usize {
+        match *term {
+            DbTerm::Bound(index) => env.lookup(index),
+            DbTerm::Free(ref name) if self.globals.contains_key(name.as_str()) => self.globals[name.as_str()],
+            DbTerm::Int(val) => self.allocate(Thunk::Done(Value::Int(val))),
+            _ => self.allocate(Thunk::Delayed(term, env.clone()))
+        }
+    }
+
+    fn allocate(&mut self, thunk: Thunk<'a>) -> usize {
+        self.sharing.created += 1;
+        self.heap.push(thunk);
+        self.heap.len() - 1
+    }
+
+    fn apply(&mut self, function: Value<'a>, argument: usize, stack: &mut Vec<Frame>) -> Result<State<'a>, Error> {
+        Ok(State::Return(match function {
+            Value::Closure(body, env) => {
+                self.tick()?;
+                return Ok(State::Eval(body, env.push(argument)));
+            },
+            Value::Primitive(primitive, mut args) => {
+                args.push(argument);
+                if args.len() < 2 {
+                    Value::Primitive(primitive, args)
+                } else {
+                    return self.operate(primitive, args, vec!(), stack);
+                }
+            },
+            Value::Stuck(head, mut args) => {
+                args.push(argument);
+                Value::Stuck(head, args)
+            },
+            head => Value::Stuck(Rc::new(head), vec!(argument))
+        }))
+    }
+
+    /// Forces the next argument of a saturated primitive, or applies it
+    /// once all of them are integers.
+    fn operate(&mut self, primitive: Primitive, args: Vec<usize>, forced: Vec<i32>,
+               stack: &mut Vec<Frame>) -> Result<State<'a>, Error> {
+        if forced.len() < args.len() {
+            let next = args[forced.len()];
+            stack.push(Frame::Operand(primitive, args, forced));
+            return Ok(State::Force(next));
+        }
+        Ok(State::Return(match primitive.apply(forced[0], forced[1]) {
+            Some(result) => {
+                self.tick()?;
+                match result {
+                    PrimValue::Int(val) => Value::Int(val),
+                    PrimValue::Bool(val) => Value::Closure(&self.booleans[if val { 0 } else { 1 }], Env::default())
+                }
+            },
+            None => Value::Stuck(Rc::new(Value::Free(primitive.name().to_string())), args)
+        }))
+    }
+
+    fn tick(&mut self) -> Result<(), Error> {
+        if self.steps == self.fuel {
+            return Err(Error::OutOfFuel(self.fuel));
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// The value as a term. Thunks that were never forced are read back as
+    /// the terms they would have evaluated.
+    fn quote(&self, value: &Value) -> DbTerm {
+        match *value {
+            Value::Int(val) => DbTerm::Int(val),
+            Value::Free(ref name) => DbTerm::Free(name.clone()),
+            Value::Closure(body, ref env) => DbTerm::Abstraction(Box::new(self.quote_body(body, env, 1))),
+            Value::Primitive(primitive, ref args) =>
+                self.quote_application(DbTerm::Free(primitive.name().to_string()), args),
+            Value::Stuck(ref head, ref args) => self.quote_application(self.quote(head), args)
+        }
+    }
+
+    fn quote_application(&self, head: DbTerm, args: &[usize]) -> DbTerm {
+        args.iter().fold(head, |function, &arg|
+            DbTerm::Application(Box::new(function), Box::new(self.quote_thunk(arg))))
+    }
+
+    fn quote_thunk(&self, address: usize) -> DbTerm {
+        // Globals are read back by name, the way they were written
+        if let Some((&name, _)) = self.globals.iter().find(|&(_, &a)| a == address) {
+            return DbTerm::Free(name.to_string());
+        }
+        match self.heap[address] {
+            Thunk::Delayed(term, ref env) => self.quote_body(term, env, 0),
+            Thunk::Done(ref value) => self.quote(value),
+            Thunk::Forcing => unreachable!("thunks are only being forced while the machine runs")
+        }
+    }
+
+    /// `term` with the variables that point past its `local` binders replaced
+    /// by the thunks they refer to in `env`.
+    fn quote_body(&self, term: &DbTerm, env: &Env, local: usize) -> DbTerm {
+        match *term {
+            DbTerm::Bound(index) if index < local => DbTerm::Bound(index),
+            DbTerm::Bound(index) => self.quote_thunk(env.lookup(index - local)),
+            DbTerm::Abstraction(ref body) => DbTerm::Abstraction(Box::new(self.quote_body(body, env, local + 1))),
+            DbTerm::Application(ref left, ref right) => DbTerm::Application(
+                Box::new(self.quote_body(left, env, local)),
+                Box::new(self.quote_body(right, env, local))
+            ),
+            _ => term.clone()
+        }
+    }
+}
+
+#[test]
+fn lazy_agrees_with_call_by_name(){
+    use eval::Strategy;
+    use super::corpus;
+
+    for &(definitions, input) in corpus::CASES {
+        let (env, term) = corpus::load(definitions, input);
+        // Sharing changes which arguments are evaluated in the result, so
+        // both are compared by their normal forms
+        let expected = corpus::substitution(&env, &term, Strategy::CallByName)
+            .and_then(|t| corpus::normal_form(&env, &t.to_node()));
+        let actual = evaluate(&env, &term, corpus::FUEL)
+            .and_then(|(r, _)| corpus::normal_form(&env, &r.term));
+        assert_eq!(actual, expected, "evaluating {}", input);
+    }
+}
+
+#[test]
+fn lazy_shares_arguments(){
+    use super::corpus;
+
+    // By name, each level would evaluate its argument twice, 2^20 times in all
+    let mut program = String::from("double = \\x. add x x\nmain = ");
+    for _ in 0..20 {
+        program.push_str("double (");
+    }
+    program.push('1');
+    program.push_str(&")".repeat(20));
+    let program = corpus::load(&program, "main").0;
+    let (result, sharing) = evaluate(&program, &ParseNode::variable("main"), 1000).unwrap();
+    assert_eq!(result.term, ParseNode::literal_int(1 << 20));
+    assert_eq!(result.steps, 40);
+    assert_eq!(sharing, Sharing { created: 22, forced: 21 });
+
+    let (env, term) = corpus::load(corpus::CHURCH, "to_int (exp two (mul_c two (add_c two two)))");
+    let (result, _) = evaluate(&env, &term, 100_000).unwrap();
+    assert_eq!(result.term, ParseNode::literal_int(256));
+}
+
+#[test]
+fn lazy_runs_y_combinator_programs(){
+    use lexer::Lexer;
+    use parser::Parser;
+
+    let program = Parser::new(Lexer::new("y = \\f. (\\x. f (x x)) (\\x. f (x x))
+        fact = y (\\fact. \\n. eq n 0 1 (mul n (fact (sub n 1))))
+        ones = y (\\ones. \\f. f 1 ones)
+        head = \\list. list (\\h. \\t. h)
+        main = add (fact 10) (head ones)")).parse().unwrap();
+    let (result, _) = run_program(&program, 100_000).unwrap();
+    assert_eq!(result.term, ParseNode::literal_int(3628801));
+
+    let program = Parser::new(Lexer::new("loop = loop\nmain = loop")).parse().unwrap();
+    assert_eq!(run_program(&program, 100).err(), Some(Error::InfiniteLoop));
+}
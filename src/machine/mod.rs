@@ -0,0 +1,39 @@
+pub mod cek;
+pub mod krivine;
+pub mod lazy;
+#[cfg(test)]
+pub mod corpus;
+
+pub use self::krivine::Krivine;
+
+/// How the REPL evaluates terms. Machines other than substitution ignore
+/// the reduction strategy and use their own.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Evaluator{
+    #[default]
+    Substitution,       // Small step rewriting with the chosen strategy
+    Cek,                // Call-by-value on the CEK machine
+    Lazy                // Call-by-need with shared thunks
+}
+
+static EVALUATORS: &[Evaluator] = &[
+    Evaluator::Substitution, Evaluator::Cek, Evaluator::Lazy
+];
+
+impl Evaluator{
+    pub fn all() -> &'static [Evaluator] {
+        EVALUATORS
+    }
+
+    pub fn from_name(name: &str) -> Option<Evaluator> {
+        EVALUATORS.iter().cloned().find(|e| e.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Evaluator::Substitution => "substitution",
+            Evaluator::Cek => "cek",
+            Evaluator::Lazy => "lazy"
+        }
+    }
+}
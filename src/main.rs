@@ -1,19 +1,58 @@
-
 #[macro_use]
 extern crate clap;
 extern crate colored;
 extern crate dialoguer;
+extern crate console;
+extern crate atty;
+#[macro_use]
+extern crate serde_json;
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
 use clap::{Arg, App};
 
+use std::process;
+
+use parser::{export, AstFormat};
+
 pub mod lexer;
 pub mod parser;
 pub mod repl;
 pub mod errors;
+pub mod eval;
+pub mod format;
+pub mod analysis;
+pub mod machine;
+pub mod bytecode;
+pub mod combinators;
+pub mod blc;
+pub mod backend;
+pub mod ir;
 
 arg_enum!{
     enum Mode{
         Repl,
-        Make 
+        Make,
+        Eval,
+        Run,
+        Fmt,
+        Ast,
+        Deps,
+        Exec,
+        Disasm,
+        Blc
+    }
+}
+
+impl Mode{
+    /// How many FILE arguments the mode reads, or `None` when it checks
+    /// them itself.
+    fn max_files(&self) -> Option<usize> {
+        match *self {
+            Mode::Repl | Mode::Eval => Some(0),
+            Mode::Fmt | Mode::Blc => None,
+            _ => Some(1)
+        }
     }
 }
 
@@ -32,10 +71,104 @@ fn main(){
                             .index(1)
                             .possible_values(&values)
                             .required(true))
+                    .arg(Arg::with_name("FILE")
+                            .help("Source file to run, compile or dump, bytecode file to execute, files to format, or a blc action (encode, decode, run) and its file")
+                            .index(2)
+                            .multiple(true)
+                            .required_if("MODE", "run")
+                            .required_if("MODE", "fmt")
+                            .required_if("MODE", "ast")
+                            .required_if("MODE", "deps")
+                            .required_if("MODE", "make")
+                            .required_if("MODE", "exec")
+                            .required_if("MODE", "disasm")
+                            .required_if("MODE", "blc"))
+                    .arg(Arg::with_name("expr")
+                            .short("e")
+                            .long("expr")
+                            .help("Expression or REPL command to evaluate, may be repeated")
+                            .takes_value(true)
+                            .multiple(true)
+                            .number_of_values(1))
+                    .arg(Arg::with_name("no-rc")
+                            .long("no-rc")
+                            .help("Skips loading ~/.lambdarc and ./.lambdarc"))
+                    .arg(Arg::with_name("check")
+                            .long("check")
+                            .help("Only reports files that fmt would change"))
+                    .arg(Arg::with_name("width")
+                            .long("width")
+                            .help("Column at which fmt wraps definitions")
+                            .takes_value(true)
+                            .default_value("80"))
+                    .arg(Arg::with_name("format")
+                            .long("format")
+                            .help("Format ast writes the syntax tree in")
+                            .possible_values(&["json", "sexp", "dot"])
+                            .default_value("json"))
+                    .arg(Arg::with_name("target")
+                            .long("target")
+                            .help("What make compiles to")
+                            .possible_values(&["bytecode", "js", "c", "wat"])
+                            .default_value("bytecode"))
+                    .arg(Arg::with_name("cc")
+                            .long("cc")
+                            .help("Builds an executable with the system cc after make --target c"))
+                    .arg(Arg::with_name("bytes")
+                            .long("bytes")
+                            .help("Makes blc write and read packed bytes instead of 0 and 1"))
                     .get_matches();
 
-    match value_t!(matches.value_of("MODE"), Mode).unwrap(){
-        Mode::Repl => repl::start(),
-        Mode::Make => println!("chose make mode"),
+    let load_rc = !matches.is_present("no-rc");
+
+    let mode = value_t!(matches.value_of("MODE"), Mode).unwrap();
+    let files = matches.values_of("FILE").map_or(0, |files| files.count());
+    if let Some(max) = mode.max_files().filter(|&max| files > max) {
+        eprintln!("Error: {} takes {} file{}, got {}", matches.value_of("MODE").unwrap(),
+            if max == 0 { "no".to_string() } else { max.to_string() }, if max == 1 { "" } else { "s" }, files);
+        process::exit(1);
+    }
+
+    let success = match mode {
+        Mode::Repl => repl::start(load_rc),
+        Mode::Make => {
+            let target = backend::Target::from_name(matches.value_of("target").unwrap()).unwrap();
+            let file = matches.value_of("FILE").unwrap();
+            if matches.is_present("cc") && target != backend::Target::C {
+                eprintln!("Error: --cc only applies to --target c");
+                false
+            } else {
+                backend::make_file(file, target) && (!matches.is_present("cc") || backend::build_native(file))
+            }
+        },
+        // Without -e, expressions are read from stdin one per line
+        Mode::Eval => match matches.values_of("expr") {
+            Some(exprs) => repl::eval(&exprs.collect::<Vec<_>>(), load_rc),
+            None => repl::start(load_rc)
+        },
+        Mode::Run => repl::run(matches.value_of("FILE").unwrap(), load_rc),
+        Mode::Fmt => {
+            let files = matches.values_of("FILE").unwrap().collect::<Vec<_>>();
+            let width = value_t!(matches.value_of("width"), usize).unwrap_or_else(|e| e.exit());
+            format::format_files(&files, matches.is_present("check"), width)
+        },
+        Mode::Ast => {
+            let format = AstFormat::from_name(matches.value_of("format").unwrap()).unwrap();
+            export::dump_file(matches.value_of("FILE").unwrap(), format)
+        },
+        Mode::Deps => analysis::dependencies::print_dependencies(matches.value_of("FILE").unwrap()),
+        Mode::Exec => bytecode::exec_file(matches.value_of("FILE").unwrap()),
+        Mode::Disasm => bytecode::disassemble_file(matches.value_of("FILE").unwrap()),
+        Mode::Blc => match matches.values_of("FILE").unwrap().collect::<Vec<_>>()[..] {
+            [action, file] => blc::blc_command(action, file, matches.is_present("bytes")),
+            _ => {
+                eprintln!("Error: blc takes an action and a file, as in 'lambda blc encode prog.lc'");
+                false
+            }
+        },
+    };
+
+    if !success {
+        process::exit(1);
     }
 }
@@ -0,0 +1,128 @@
+use parser::Visitor;
+use parser::ParseNode;
+use parser::GrammarItem;
+
+/// Draws a `ParseNode` tree as a Graphviz DOT graph. Applications are
+/// drawn as `@` and abstractions as `λx`, and every bound variable has a
+/// dashed edge back to the abstraction that binds it.
+pub struct DotVisitor{
+  lines: Vec<String>,
+  binders: Vec<(String, usize)>,    // Abstractions in scope, innermost last
+  next_id: usize
+}
+
+impl DotVisitor {
+  pub fn new() -> DotVisitor {
+    DotVisitor { lines: vec!(), binders: vec!(), next_id: 0 }
+  }
+
+  /// The DOT source of the graph for `node`.
+  pub fn render(node: &ParseNode) -> String {
+    let mut visitor = DotVisitor::new();
+    visitor.visit(node);
+    format!("digraph ast {{\n  node [fontname=\"monospace\"];\n{}\n}}", visitor.lines.join("\n"))
+  }
+
+  fn node(&mut self, label: &str, attributes: &str) -> usize {
+    let id = self.next_id;
+    self.next_id += 1;
+    self.lines.push(format!("  n{} [label=\"{}\"{}];", id, escape(label), attributes));
+    id
+  }
+
+  fn edge(&mut self, from: usize, to: usize){
+    self.lines.push(format!("  n{} -> n{};", from, to));
+  }
+}
+
+impl Default for DotVisitor {
+  fn default() -> DotVisitor {
+    DotVisitor::new()
+  }
+}
+
+impl Visitor<usize> for DotVisitor {
+  fn visit_program(&mut self, i: &ParseNode) -> usize {
+    if let GrammarItem::Program(ref children) = i.entry {
+      let id = self.node("program", ", shape=box");
+      for child in children {
+        let child = self.visit(child);
+        self.edge(id, child);
+      }
+      id
+    } else { unreachable!() }
+  }
+
+  fn visit_abstraction(&mut self, i: &ParseNode) -> usize {
+    if let GrammarItem::Abstraction(ref param, ref body) = i.entry {
+      let id = self.node(&format!("λ{}", param), "");
+      self.binders.push((param.clone(), id));
+      let body = self.visit(body);
+      self.binders.pop();
+      self.edge(id, body);
+      id
+    } else { unreachable!() }
+  }
+
+  fn visit_application(&mut self, i: &ParseNode) -> usize {
+    if let GrammarItem::Application(ref left, ref right) = i.entry {
+      let id = self.node("@", "");
+      let left = self.visit(left);
+      let right = self.visit(right);
+      self.edge(id, left);
+      self.edge(id, right);
+      id
+    } else { unreachable!() }
+  }
+
+  fn visit_assignment(&mut self, i: &ParseNode) -> usize {
+    if let GrammarItem::Assignment(ref name, ref expr) = i.entry {
+      let id = self.node(&format!("{} =", name), ", shape=box");
+      let expr = self.visit(expr);
+      self.edge(id, expr);
+      id
+    } else { unreachable!() }
+  }
+
+  fn visit_literal_int(&mut self, i: &ParseNode) -> usize {
+    if let GrammarItem::LiteralInt(val) = i.entry {
+      self.node(&val.to_string(), ", shape=plaintext")
+    } else { unreachable!() }
+  }
+
+  fn visit_variable(&mut self, i: &ParseNode) -> usize {
+    if let GrammarItem::Variable(ref name) = i.entry {
+      let id = self.node(name, ", shape=plaintext");
+      let binder = self.binders.iter().rev().find(|(param, _)| param == name).map(|&(_, binder)| binder);
+      if let Some(binder) = binder {
+        self.lines.push(format!("  n{} -> n{} [style=dashed, constraint=false];", id, binder));
+      }
+      id
+    } else { unreachable!() }
+  }
+}
+
+/// Makes `text` safe to use inside a quoted DOT label. Backslashes are
+/// written as `λ`, which is also how lambdas are drawn.
+pub fn escape(text: &str) -> String {
+  text.replace('\\', "λ").replace('"', "\\\"")
+}
+
+#[test]
+fn dot_links_variables_to_binders(){
+  use lexer::Lexer;
+  use parser::Parser;
+
+  let term = Parser::new(Lexer::new("\\x. f x")).parse_expr().unwrap();
+  assert_eq!(DotVisitor::render(&term), "digraph ast {
+  node [fontname=\"monospace\"];
+  n0 [label=\"λx\"];
+  n1 [label=\"@\"];
+  n2 [label=\"f\", shape=plaintext];
+  n3 [label=\"x\", shape=plaintext];
+  n3 -> n0 [style=dashed, constraint=false];
+  n1 -> n2;
+  n1 -> n3;
+  n0 -> n1;
+}");
+}
@@ -0,0 +1,74 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json;
+
+use lexer::Lexer;
+use parser::{Parser, ParseNode, Visitor};
+use parser::json::{JsonVisitor, from_json};
+use parser::sexp::SexpVisitor;
+use parser::dot::DotVisitor;
+use errors::error_index::Error;
+
+/// Machine readable formats a syntax tree can be written in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AstFormat{
+  Json,
+  Sexp,
+  Dot
+}
+
+impl AstFormat {
+  pub fn all() -> &'static [AstFormat] {
+    &[AstFormat::Json, AstFormat::Sexp, AstFormat::Dot]
+  }
+
+  pub fn from_name(name: &str) -> Option<AstFormat> {
+    AstFormat::all().iter().cloned().find(|f| f.name() == name)
+  }
+
+  pub fn name(&self) -> &'static str {
+    match *self {
+      AstFormat::Json => "json",
+      AstFormat::Sexp => "sexp",
+      AstFormat::Dot => "dot"
+    }
+  }
+
+  pub fn export(&self, node: &ParseNode) -> String {
+    match *self {
+      AstFormat::Json => serde_json::to_string_pretty(&JsonVisitor.visit(node)).unwrap(),
+      AstFormat::Sexp => SexpVisitor.visit(node),
+      AstFormat::Dot => DotVisitor::render(node)
+    }
+  }
+}
+
+/// Reads a program from `path`, either as source or, for `.json` files, as
+/// a tree previously exported with `AstFormat::Json`.
+pub fn read_program(path: &Path) -> Result<ParseNode, String> {
+  let contents = fs::read_to_string(path)
+    .map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+  let program = if path.extension().is_some_and(|ext| ext == "json") {
+    serde_json::from_str(&contents)
+      .map_err(|e| Error::InvalidAst(e.to_string()))
+      .and_then(|json| from_json(&json))
+  } else {
+    Parser::new(Lexer::new(&contents)).parse()
+  };
+  program.map_err(|e| format!("can't parse {}: {:?}", path.display(), e))
+}
+
+/// Prints the tree of the program in `path`. Returns whether it could be read.
+pub fn dump_file(path: &str, format: AstFormat) -> bool {
+  match read_program(Path::new(path)) {
+    Ok(program) => {
+      println!("{}", format.export(&program));
+      true
+    },
+    Err(e) => {
+      eprintln!("Error: {}", e);
+      false
+    }
+  }
+}
@@ -0,0 +1,173 @@
+use serde_json::{Map, Value};
+
+use parser::Visitor;
+use parser::{ParseNode, GrammarItem, Type};
+use lexer::Span;
+use errors::error_index::Error;
+
+/// Converts a `ParseNode` into JSON. Every node is an object with a `node`
+/// field naming its kind, a `node_type`, a `span` of byte offsets into the
+/// source, and fields for its children:
+///
+/// ```text
+/// {"node": "Abstraction", "param": "x", "body": {...},
+///  "node_type": {"type": "Unknown"}, "span": {"start": 0, "end": 6}}
+/// ```
+///
+/// Types are objects with a `type` field: `Unknown`, `Variable` with a
+/// `name`, or `Abstraction` with `from` and `to`.
+pub struct JsonVisitor;
+
+impl JsonVisitor {
+  fn node(&self, i: &ParseNode, kind: &str, fields: Vec<(&str, Value)>) -> Value {
+    let mut object = Map::new();
+    object.insert("node".to_string(), Value::from(kind));
+    for (name, value) in fields {
+      object.insert(name.to_string(), value);
+    }
+    object.insert("node_type".to_string(), type_to_json(&i.node_type));
+    object.insert("span".to_string(), json!({ "start": i.span.start, "end": i.span.end }));
+    Value::Object(object)
+  }
+}
+
+impl Visitor<Value> for JsonVisitor {
+  fn visit_program(&mut self, i: &ParseNode) -> Value {
+    if let GrammarItem::Program(ref children) = i.entry {
+      let definitions = children.iter().map(|child| self.visit(child)).collect();
+      self.node(i, "Program", vec![("definitions", Value::Array(definitions))])
+    } else { unreachable!() }
+  }
+
+  fn visit_abstraction(&mut self, i: &ParseNode) -> Value {
+    if let GrammarItem::Abstraction(ref param, ref body) = i.entry {
+      let body = self.visit(body);
+      self.node(i, "Abstraction", vec![("param", Value::from(param.as_str())), ("body", body)])
+    } else { unreachable!() }
+  }
+
+  fn visit_application(&mut self, i: &ParseNode) -> Value {
+    if let GrammarItem::Application(ref left, ref right) = i.entry {
+      let function = self.visit(left);
+      let argument = self.visit(right);
+      self.node(i, "Application", vec![("function", function), ("argument", argument)])
+    } else { unreachable!() }
+  }
+
+  fn visit_assignment(&mut self, i: &ParseNode) -> Value {
+    if let GrammarItem::Assignment(ref name, ref expr) = i.entry {
+      let value = self.visit(expr);
+      self.node(i, "Assignment", vec![("name", Value::from(name.as_str())), ("value", value)])
+    } else { unreachable!() }
+  }
+
+  fn visit_literal_int(&mut self, i: &ParseNode) -> Value {
+    if let GrammarItem::LiteralInt(val) = i.entry {
+      self.node(i, "LiteralInt", vec![("value", Value::from(val))])
+    } else { unreachable!() }
+  }
+
+  fn visit_variable(&mut self, i: &ParseNode) -> Value {
+    if let GrammarItem::Variable(ref name) = i.entry {
+      self.node(i, "Variable", vec![("name", Value::from(name.as_str()))])
+    } else { unreachable!() }
+  }
+}
+
+fn type_to_json(t: &Type) -> Value {
+  match *t {
+    Type::Unknown => json!({ "type": "Unknown" }),
+    Type::Variable(ref name) => json!({ "type": "Variable", "name": name }),
+    Type::Abstraction(ref from, ref to) =>
+      json!({ "type": "Abstraction", "from": type_to_json(from), "to": type_to_json(to) })
+  }
+}
+
+/// Reads a tree written by `JsonVisitor` back into a `ParseNode`.
+pub fn from_json(value: &Value) -> Result<ParseNode, Error> {
+  let entry = match string(value, "node")? {
+    "Program" => GrammarItem::Program(field(value, "definitions")?
+      .as_array()
+      .ok_or_else(|| invalid("'definitions' should be an array"))?
+      .iter()
+      .map(from_json)
+      .collect::<Result<_, _>>()?),
+    "Abstraction" => GrammarItem::Abstraction(
+      string(value, "param")?.to_string(),
+      Box::new(from_json(field(value, "body")?)?)),
+    "Application" => GrammarItem::Application(
+      Box::new(from_json(field(value, "function")?)?),
+      Box::new(from_json(field(value, "argument")?)?)),
+    "Assignment" => GrammarItem::Assignment(
+      string(value, "name")?.to_string(),
+      Box::new(from_json(field(value, "value")?)?)),
+    "LiteralInt" => GrammarItem::LiteralInt(field(value, "value")?
+      .as_i64()
+      .and_then(|n| if n >= i64::from(i32::MIN) && n <= i64::from(i32::MAX) { Some(n as i32) } else { None })
+      .ok_or_else(|| invalid("'value' should be a 32 bit integer"))?),
+    "Variable" => GrammarItem::Variable(string(value, "name")?.to_string()),
+    other => return Err(invalid(&format!("unknown node '{}'", other)))
+  };
+
+  let node_type = match value.get("node_type") {
+    Some(t) => type_from_json(t)?,
+    None => Type::Unknown
+  };
+  let span = match value.get("span") {
+    Some(span) => Span::new(number(span, "start")?, number(span, "end")?),
+    None => Span::default()
+  };
+  Ok(ParseNode::new(entry, node_type).with_span(span))
+}
+
+fn type_from_json(value: &Value) -> Result<Type, Error> {
+  match string(value, "type")? {
+    "Unknown" => Ok(Type::Unknown),
+    "Variable" => Ok(Type::Variable(string(value, "name")?.to_string())),
+    "Abstraction" => Ok(Type::Abstraction(
+      Box::new(type_from_json(field(value, "from")?)?),
+      Box::new(type_from_json(field(value, "to")?)?))),
+    other => Err(invalid(&format!("unknown type '{}'", other)))
+  }
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, Error> {
+  value.get(name).ok_or_else(|| invalid(&format!("missing field '{}'", name)))
+}
+
+fn string<'a>(value: &'a Value, name: &str) -> Result<&'a str, Error> {
+  field(value, name)?.as_str().ok_or_else(|| invalid(&format!("'{}' should be a string", name)))
+}
+
+fn number(value: &Value, name: &str) -> Result<usize, Error> {
+  field(value, name)?.as_u64()
+    .map(|n| n as usize)
+    .ok_or_else(|| invalid(&format!("'{}' should be a number", name)))
+}
+
+fn invalid(message: &str) -> Error {
+  Error::InvalidAst(message.to_string())
+}
+
+#[test]
+fn json_roundtrip_keeps_spans(){
+  use lexer::Lexer;
+  use parser::Parser;
+
+  let program = Parser::new(Lexer::new("id = \\x. x\nmain = id 3")).parse().unwrap();
+  let json = JsonVisitor.visit(&program);
+  let text = ::serde_json::to_string(&json).unwrap();
+  let read = from_json(&::serde_json::from_str(&text).unwrap()).unwrap();
+
+  assert_eq!(read, program);
+  assert_eq!(JsonVisitor.visit(&read), json);
+  assert_eq!(json["definitions"][1]["value"]["argument"],
+    json!({ "node": "LiteralInt", "value": 3, "node_type": { "type": "Unknown" }, "span": { "start": 21, "end": 22 } }));
+}
+
+#[test]
+fn json_rejects_malformed_trees(){
+  assert_eq!(from_json(&json!({ "node": "Lambda" })), Err(Error::InvalidAst("unknown node 'Lambda'".to_string())));
+  assert_eq!(from_json(&json!({ "node": "Variable", "name": 3 })), Err(Error::InvalidAst("'name' should be a string".to_string())));
+  assert!(from_json(&json!({ "node": "Application", "function": { "node": "Variable", "name": "f" } })).is_err());
+}
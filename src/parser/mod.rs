@@ -2,11 +2,18 @@
 pub mod parser;
 pub mod parse_node;
 pub mod visitor;
+pub mod pretty_printer;
+pub mod json;
+pub mod sexp;
+pub mod dot;
+pub mod export;
 
 pub use self::parser::Parser;
 pub use self::parse_node::ParseNode;
 pub use self::parse_node::Type;
 pub use self::parse_node::GrammarItem;
 pub use self::visitor::Visitor;
+pub use self::pretty_printer::PrettyPrinter;
+pub use self::export::AstFormat;
 
 
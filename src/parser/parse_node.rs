@@ -1,5 +1,9 @@
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
+use parser::{PrettyPrinter, Visitor};
+use lexer::Span;
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum GrammarItem{
     LiteralInt(i32),
     Variable(String),
@@ -9,21 +13,79 @@ pub enum GrammarItem{
     Program(Vec<ParseNode>)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Type{
     Variable(String),
     Abstraction(Box<Type>, Box<Type>),
     Unknown,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct ParseNode{
     pub entry: GrammarItem,
-    pub node_type: Type
+    pub node_type: Type,
+    pub span: Span          // Where the node came from, empty for generated nodes
 }
 
 impl ParseNode{
     pub fn new(grammar: GrammarItem, node_type: Type) -> ParseNode {
-        ParseNode { entry: grammar, node_type: node_type }
+        ParseNode { entry: grammar, node_type: node_type, span: Span::default() }
+    }
+
+    pub fn with_span(mut self, span: Span) -> ParseNode {
+        self.span = span;
+        self
+    }
+
+    pub fn variable(name: &str) -> ParseNode {
+        ParseNode::new(GrammarItem::Variable(name.to_string()), Type::Unknown)
+    }
+
+    pub fn literal_int(val: i32) -> ParseNode {
+        ParseNode::new(GrammarItem::LiteralInt(val), Type::Unknown)
+    }
+
+    pub fn application(left: ParseNode, right: ParseNode) -> ParseNode {
+        ParseNode::new(
+            GrammarItem::Application(Box::new(left), Box::new(right)),
+            Type::Unknown
+        )
+    }
+
+    pub fn abstraction(param: &str, body: ParseNode) -> ParseNode {
+        ParseNode::new(
+            GrammarItem::Abstraction(param.to_string(), Box::new(body)),
+            Type::Unknown
+        )
+    }
+}
+
+/// Nodes are compared by structure. Spans only record where a node was
+/// parsed from, so they are ignored.
+impl PartialEq for ParseNode {
+    fn eq(&self, other: &ParseNode) -> bool {
+        self.entry == other.entry && self.node_type == other.node_type
+    }
+}
+
+/// Written as in source, for example `(A -> B) -> A`, with `?` for types
+/// that aren't known.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Variable(ref name) => write!(f, "{}", name),
+            Type::Abstraction(ref from, ref to) => match **from {
+                Type::Abstraction(_, _) => write!(f, "({}) -> {}", from, to),
+                _ => write!(f, "{} -> {}", from, to)
+            },
+            Type::Unknown => write!(f, "?")
+        }
+    }
+}
+
+/// Writes the node back out as source on a single line.
+impl fmt::Display for ParseNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", PrettyPrinter::flat().visit(self))
     }
 }
@@ -1,5 +1,6 @@
 use lexer::Lexer;
 use lexer::Token;
+use lexer::Span;
 use parser::ParseNode;
 use parser::Type;
 use parser::GrammarItem;
@@ -23,15 +24,19 @@ impl<'a> Parser<'a>{
 
     pub fn parse(&mut self) -> ParseResult{
         let mut assignments: Vec<ParseNode> = Vec::new();
-        while let Ok(assign) = self.parse_toplevel_assignment() {
-            assignments.push(assign);
+        while let Token::LIdent(_) = self.lexer.peek_token() {
+            assignments.push(self.parse_toplevel_assignment()?);
         }
         let tok = self.lexer.next_token();
+        let span = match (assignments.first(), assignments.last()) {
+            (Some(first), Some(last)) => first.span.to(last.span),
+            _ => Span::default()
+        };
         match tok {
             Token::EOF => Ok(ParseNode::new(
                 GrammarItem::Program(assignments),
                 Type::Unknown
-            )),
+            ).with_span(span)),
             _ => Err(Error::ExpectedEOF(tok))
         }
         /*
@@ -48,12 +53,16 @@ impl<'a> Parser<'a>{
         let tok = self.lexer.next_token();
         match tok {
             Token::LIdent(id) => {
+                let start = self.lexer.span();
                 self.consume(Token::Assign)?;
                 self.parse_expr().and_then(
-                    |expr| Ok(ParseNode::new(
-                        GrammarItem::Assignment(id, Box::new(expr)),
-                        Type::Unknown
-                    )),
+                    |expr| {
+                        let span = start.to(expr.span);
+                        Ok(ParseNode::new(
+                            GrammarItem::Assignment(id, Box::new(expr)),
+                            Type::Unknown
+                        ).with_span(span))
+                    }
                 )
             },
             _ => Err(Error::ExpectedToken(Token::LIdent("".to_string()), tok))
@@ -70,7 +79,9 @@ impl<'a> Parser<'a>{
         self.lexer.is_empty()
     }
 
-    fn parse_base_expr(&mut self) -> ParseResult{
+    /// Parses a variable, integer, parenthesized expression or abstraction,
+    /// without applying it to anything that follows.
+    pub fn parse_base_expr(&mut self) -> ParseResult{
         let tok = self.lexer.next_token();
         match tok {
             Token::LParen => self.parse_paren_expr(),
@@ -87,11 +98,12 @@ impl<'a> Parser<'a>{
     }
 
     fn parse_paren_expr(&mut self) -> ParseResult{
+        let start = self.lexer.span();
         self.parse_expr().and_then(
             |expr| {
                 let tok = self.lexer.next_token();
                 match tok {
-                    Token::RParen => Ok(expr),
+                    Token::RParen => Ok(expr.with_span(start.to(self.lexer.span()))),
                     _ => Err(Error::ExpectedToken(Token::RParen, tok))
                 }
             }
@@ -100,44 +112,79 @@ impl<'a> Parser<'a>{
 
     fn parse_literal_int(&mut self, num_string: String) -> ParseResult{
         match num_string.parse() {
-            Ok(num) => Ok(ParseNode::new(GrammarItem::LiteralInt(num), Type::Unknown)),
+            Ok(num) => Ok(ParseNode::new(GrammarItem::LiteralInt(num), Type::Unknown)
+                .with_span(self.lexer.span())),
             Err(_) => Err(Error::IntegerParseError)
         }
     }
 
     fn parse_identifier_expr(&mut self, id: String) -> ParseResult{
-        Ok(ParseNode::new(GrammarItem::Variable(id), Type::Unknown))
+        Ok(ParseNode::new(GrammarItem::Variable(id), Type::Unknown)
+            .with_span(self.lexer.span()))
     }
 
     fn parse_abstraction_expr(&mut self) -> ParseResult{
+        let start = self.lexer.span();
         let tok = self.lexer.next_token();
         match tok {
             Token::LIdent(id) => {
                 let t = self.parse_type()?;
                 self.consume(Token::Dot)?;  
                 self.parse_expr().and_then(
-                    |expr| Ok(ParseNode::new(
-                        GrammarItem::Abstraction(id, Box::new(expr)), t))
+                    |expr| {
+                        let span = start.to(expr.span);
+                        Ok(ParseNode::new(GrammarItem::Abstraction(id, Box::new(expr)), t)
+                            .with_span(span))
+                    }
                 )
             }
             _ => Err(Error::ExpectedToken(Token::LIdent("".to_string()), tok))     //Expected identifier
         }
     }
 
+    /// Parses a type such as `(A -> B) -> A -> B`. Arrows associate to
+    /// the right.
+    pub fn parse_type_expr(&mut self) -> Result<Type, ParseError> {
+        let from = match self.lexer.next_token() {
+            Token::UIdent(name) => Type::Variable(name),
+            Token::LParen => {
+                let inner = self.parse_type_expr()?;
+                self.consume(Token::RParen)?;
+                inner
+            },
+            Token::EOF => return Err(Error::UnexpectedEOF),
+            tok => return Err(Error::IllegalToken(tok))
+        };
+        match self.lexer.next_token() {
+            Token::Arrow => Ok(Type::Abstraction(Box::new(from), Box::new(self.parse_type_expr()?))),
+            tok => {
+                self.lexer.put_back(tok);
+                Ok(from)
+            }
+        }
+    }
+
     fn parse_type(&mut self) -> Result<Type, ParseError> {
         Ok(Type::Unknown)
     }
 
     fn parse_expr_prime(&mut self, left: ParseNode) -> ParseResult{
         let tok = self.lexer.next_token();
+        if self.starts_assignment(&tok) {
+            self.lexer.put_back(tok);
+            return Ok(left);
+        }
         match tok {
             Token::LParen | Token::Backslash | Token::LIdent(_) | Token::Integer(_) => {
                 self.lexer.put_back(tok);
                 self.parse_base_expr().and_then(
-                    |expr| self.parse_expr_prime(ParseNode::new(
-                        GrammarItem::Application(Box::new(left), Box::new(expr)),
-                        Type::Unknown
-                    ))
+                    |expr| {
+                        let span = left.span.to(expr.span);
+                        self.parse_expr_prime(ParseNode::new(
+                            GrammarItem::Application(Box::new(left), Box::new(expr)),
+                            Type::Unknown
+                        ).with_span(span))
+                    }
                 )
             },
             _ => {
@@ -147,6 +194,15 @@ impl<'a> Parser<'a>{
         }
     }
 
+    /// Definitions aren't separated by anything, so an identifier followed
+    /// by `=` ends the expression before it rather than being applied.
+    fn starts_assignment(&mut self, tok: &Token) -> bool {
+        match *tok {
+            Token::LIdent(_) => self.lexer.peek_token() == Token::Assign,
+            _ => false
+        }
+    }
+
     fn consume(&mut self, tok : Token) -> Result<Token, ParseError> {
         let new_tok = self.lexer.next_token();
         if new_tok == tok {
@@ -322,3 +378,65 @@ fn parse_expr_test_error_6(){
     let node = parser.parse_expr();
     assert_eq!(Err(Error::UnexpectedEOF), node);
 }
+
+#[test]
+fn parse_program_multiple_assignments(){
+    let input = "id = \\x. x\nk = \\a. \\b. a\nthree = k 3 id";
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    let node = parser.parse();
+    assert_eq!(Ok(ParseNode::new(
+        GrammarItem::Program(vec![
+            ParseNode::new(GrammarItem::Assignment("id".to_string(), Box::new(
+                ParseNode::abstraction("x", ParseNode::variable("x")))), Type::Unknown),
+            ParseNode::new(GrammarItem::Assignment("k".to_string(), Box::new(
+                ParseNode::abstraction("a", ParseNode::abstraction("b", ParseNode::variable("a"))))), Type::Unknown),
+            ParseNode::new(GrammarItem::Assignment("three".to_string(), Box::new(
+                ParseNode::application(
+                    ParseNode::application(ParseNode::variable("k"), ParseNode::literal_int(3)),
+                    ParseNode::variable("id")))), Type::Unknown),
+        ]),
+        Type::Unknown
+    )), node);
+}
+
+#[test]
+fn parse_spans(){
+    let input = "f = (\\x. x) 12";
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    let node = parser.parse().unwrap();
+
+    let assign = match node.entry {
+        GrammarItem::Program(ref children) => &children[0],
+        _ => panic!("expected a program")
+    };
+    assert_eq!(assign.span, Span::new(0, 14));
+    if let GrammarItem::Assignment(_, ref expr) = assign.entry {
+        assert_eq!(expr.span, Span::new(4, 14));
+        if let GrammarItem::Application(ref left, ref right) = expr.entry {
+            assert_eq!(left.span, Span::new(4, 11));
+            assert_eq!(right.span, Span::new(12, 14));
+        }
+    }
+}
+
+#[test]
+fn parse_program_reports_errors_in_definitions(){
+    let input = "id = \\x. x\nbad = (a b\nk = \\a. a";
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    assert_eq!(parser.parse(), Err(Error::ExpectedToken(Token::RParen, Token::LIdent("k".to_string()))));
+}
+
+#[test]
+fn parse_type_expr_arrows(){
+    let mut parser = Parser::new(Lexer::new("(A -> B) -> A -> B"));
+    let a = || Box::new(Type::Variable("A".to_string()));
+    let b = || Box::new(Type::Variable("B".to_string()));
+    assert_eq!(parser.parse_type_expr(), Ok(Type::Abstraction(
+        Box::new(Type::Abstraction(a(), b())),
+        Box::new(Type::Abstraction(a(), b()))
+    )));
+    assert!(parser.is_empty());
+}
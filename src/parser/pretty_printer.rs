@@ -0,0 +1,230 @@
+use parser::Visitor;
+use parser::ParseNode;
+use parser::GrammarItem;
+
+static INDENT_AMOUNT : usize = 2;
+
+/// Turns a `ParseNode` back into source. Parentheses are only added where
+/// the grammar needs them: around applications in argument position, and
+/// around abstractions that something else follows, since an abstraction's
+/// body extends as far right as it can. Terms that don't fit in `width`
+/// columns are broken over several lines.
+pub struct PrettyPrinter{
+  width: usize,
+  column: usize,        // Column the node being visited starts at
+  rightmost: bool       // Whether nothing follows the node being visited
+}
+
+impl PrettyPrinter {
+  pub fn new(width: usize) -> PrettyPrinter {
+    PrettyPrinter { width, column: 0, rightmost: true }
+  }
+
+  /// A printer that never breaks lines.
+  pub fn flat() -> PrettyPrinter {
+    PrettyPrinter::new(usize::MAX)
+  }
+
+  fn is_flat(&self) -> bool {
+    self.width == usize::MAX
+  }
+
+  /// The single line rendering of `node`, if it fits in the space left.
+  fn try_flat(&self, node: &ParseNode) -> Option<String> {
+    if self.is_flat() {
+      return None;
+    }
+    let mut flat = PrettyPrinter { width: usize::MAX, ..*self };
+    let text = flat.visit(node);
+    if self.column + text.chars().count() <= self.width { Some(text) } else { None }
+  }
+
+  fn child(&mut self, node: &ParseNode, column: usize, rightmost: bool) -> String {
+    let saved = (self.column, self.rightmost);
+    self.column = column;
+    self.rightmost = rightmost;
+    let text = self.visit(node);
+    self.column = saved.0;
+    self.rightmost = saved.1;
+    text
+  }
+
+  /// Renders a function or argument of an application, wrapping it in
+  /// parentheses when it would otherwise be read differently.
+  fn operand(&mut self, node: &ParseNode, column: usize, rightmost: bool) -> String {
+    let needs_parens = match node.entry {
+      GrammarItem::Application(_, _) => true,
+      GrammarItem::Abstraction(_, _) => !rightmost,
+      _ => false
+    };
+    if needs_parens {
+      format!("({})", self.child(node, column + 1, true))
+    } else {
+      self.child(node, column, rightmost)
+    }
+  }
+
+  fn separator(&self, column: usize) -> String {
+    if self.is_flat() { " ".to_string() } else { format!("\n{}", " ".repeat(column)) }
+  }
+}
+
+impl Visitor<String> for PrettyPrinter {
+  fn visit_program(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::Program(ref children) = i.entry {
+      children.iter()
+        .map(|child| self.child(child, 0, true))
+        .collect::<Vec<_>>()
+        .join("\n")
+    } else { unreachable!() }
+  }
+
+  fn visit_abstraction(&mut self, i: &ParseNode) -> String {
+    if let Some(text) = self.try_flat(i) {
+      return text;
+    }
+
+    // Consecutive abstractions share a line: \x. \y. body
+    let mut params = vec!();
+    let mut body = i;
+    while let GrammarItem::Abstraction(ref param, ref inner) = body.entry {
+      params.push(format!("\\{}.", param));
+      body = inner;
+    }
+
+    let column = self.column + INDENT_AMOUNT;
+    let header = params.join(" ");
+    let separator = self.separator(column);
+    let body = self.child(body, column, true);
+    format!("{}{}{}", header, separator, body)
+  }
+
+  fn visit_application(&mut self, i: &ParseNode) -> String {
+    if let Some(text) = self.try_flat(i) {
+      return text;
+    }
+
+    // Application is left associative, so `f a b` is a spine of arguments
+    let mut args = vec!();
+    let mut head = i;
+    while let GrammarItem::Application(ref left, ref right) = head.entry {
+      args.push(&**right);
+      head = left;
+    }
+    args.reverse();
+
+    let start = self.column;
+    let column = start + INDENT_AMOUNT;
+    let mut text = self.operand(head, start, false);
+    let last = args.len() - 1;
+    for (index, arg) in args.into_iter().enumerate() {
+      text.push_str(&self.separator(column));
+      let rightmost = index == last && self.rightmost;
+      text.push_str(&self.operand(arg, column, rightmost));
+    }
+    text
+  }
+
+  fn visit_assignment(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::Assignment(ref name, ref expr) = i.entry {
+      if let Some(text) = self.try_flat(i) {
+        return text;
+      }
+      let separator = if self.is_flat() { " ".to_string() } else { self.separator(INDENT_AMOUNT) };
+      let column = if self.is_flat() { 0 } else { INDENT_AMOUNT };
+      format!("{} ={}{}", name, separator, self.child(expr, column, true))
+    } else { unreachable!() }
+  }
+
+  fn visit_literal_int(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::LiteralInt(val) = i.entry {
+      val.to_string()
+    } else { unreachable!() }
+  }
+
+  fn visit_variable(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::Variable(ref val) = i.entry {
+      val.clone()
+    } else { unreachable!() }
+  }
+}
+
+#[cfg(test)]
+fn parse(input: &str) -> ParseNode {
+  use lexer::Lexer;
+  use parser::Parser;
+  Parser::new(Lexer::new(input)).parse_expr().unwrap()
+}
+
+#[test]
+fn print_minimal_parens(){
+  let cases = vec![
+    r#"a b c"#,
+    r#"a (b c)"#,
+    r#"(\x. x) y"#,
+    r#"f \x. x y"#,
+    r#"f (\x. x) y"#,
+    r#"\f. \x. f (f x)"#,
+    r#"g (f \x. x) 3"#,
+  ];
+  for case in cases {
+    assert_eq!(PrettyPrinter::flat().visit(&parse(case)), case);
+  }
+  assert_eq!(PrettyPrinter::flat().visit(&parse(r#"((a) (b)) ((\x. (x)))"#)), r#"a b \x. x"#);
+}
+
+#[test]
+fn print_wraps_long_terms(){
+  let term = parse(r#"\f. \x. first (second x) (\y. third y)"#);
+  assert_eq!(PrettyPrinter::new(20).visit(&term), "\\f. \\x.\n  first\n    (second x)\n    \\y. third y");
+  assert_eq!(parse(&PrettyPrinter::new(20).visit(&term)), term);
+}
+
+#[cfg(test)]
+mod properties {
+  use proptest::prelude::*;
+
+  use lexer::Lexer;
+  use parser::{Parser, ParseNode, GrammarItem, Type, Visitor};
+  use super::PrettyPrinter;
+
+  fn name() -> impl Strategy<Value = String> {
+    prop::sample::select(vec!["a", "b", "f", "x", "y_"]).prop_map(String::from)
+  }
+
+  fn term() -> impl Strategy<Value = ParseNode> {
+    let leaf = prop_oneof![
+      name().prop_map(|n| ParseNode::variable(&n)),
+      any::<i32>().prop_map(ParseNode::literal_int),
+    ];
+    leaf.prop_recursive(6, 64, 2, |inner| prop_oneof![
+      (inner.clone(), inner.clone()).prop_map(|(l, r)| ParseNode::application(l, r)),
+      (name(), inner).prop_map(|(n, body)| ParseNode::abstraction(&n, body)),
+    ])
+  }
+
+  fn program() -> impl Strategy<Value = ParseNode> {
+    prop::collection::vec((name(), term()), 0..4).prop_map(|defs| ParseNode::new(
+      GrammarItem::Program(defs.into_iter()
+        .map(|(n, t)| ParseNode::new(GrammarItem::Assignment(n, Box::new(t)), Type::Unknown))
+        .collect()),
+      Type::Unknown
+    ))
+  }
+
+  proptest! {
+    #[test]
+    fn parse_print_roundtrip(t in term(), width in prop_oneof![Just(usize::MAX), 4..60usize]) {
+      let printed = PrettyPrinter::new(width).visit(&t);
+      let parsed = Parser::new(Lexer::new(&printed)).parse_expr();
+      prop_assert_eq!(parsed, Ok(t));
+    }
+
+    #[test]
+    fn parse_print_roundtrip_program(p in program(), width in prop_oneof![Just(usize::MAX), 4..60usize]) {
+      let printed = PrettyPrinter::new(width).visit(&p);
+      let parsed = Parser::new(Lexer::new(&printed)).parse();
+      prop_assert_eq!(parsed, Ok(p));
+    }
+  }
+}
@@ -0,0 +1,80 @@
+use parser::Visitor;
+use parser::{ParseNode, GrammarItem, Type};
+
+/// Writes a `ParseNode` as an S-expression, one list per node:
+///
+/// ```text
+/// (abstraction x (body ...) :type ? :span (0 6))
+/// ```
+///
+/// Unknown types are written `?`, type variables as their name and
+/// function types as `(-> from to)`.
+pub struct SexpVisitor;
+
+impl SexpVisitor {
+  fn node(&self, i: &ParseNode, fields: Vec<String>) -> String {
+    format!("({} :type {} :span ({} {}))", fields.join(" "), type_to_sexp(&i.node_type), i.span.start, i.span.end)
+  }
+}
+
+impl Visitor<String> for SexpVisitor {
+  fn visit_program(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::Program(ref children) = i.entry {
+      let mut fields = vec!("program".to_string());
+      fields.extend(children.iter().map(|child| self.visit(child)));
+      self.node(i, fields)
+    } else { unreachable!() }
+  }
+
+  fn visit_abstraction(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::Abstraction(ref param, ref body) = i.entry {
+      let body = self.visit(body);
+      self.node(i, vec!("abstraction".to_string(), param.clone(), body))
+    } else { unreachable!() }
+  }
+
+  fn visit_application(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::Application(ref left, ref right) = i.entry {
+      let function = self.visit(left);
+      let argument = self.visit(right);
+      self.node(i, vec!("application".to_string(), function, argument))
+    } else { unreachable!() }
+  }
+
+  fn visit_assignment(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::Assignment(ref name, ref expr) = i.entry {
+      let value = self.visit(expr);
+      self.node(i, vec!("assignment".to_string(), name.clone(), value))
+    } else { unreachable!() }
+  }
+
+  fn visit_literal_int(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::LiteralInt(val) = i.entry {
+      self.node(i, vec!("int".to_string(), val.to_string()))
+    } else { unreachable!() }
+  }
+
+  fn visit_variable(&mut self, i: &ParseNode) -> String {
+    if let GrammarItem::Variable(ref name) = i.entry {
+      self.node(i, vec!("variable".to_string(), name.clone()))
+    } else { unreachable!() }
+  }
+}
+
+fn type_to_sexp(t: &Type) -> String {
+  match *t {
+    Type::Unknown => "?".to_string(),
+    Type::Variable(ref name) => name.clone(),
+    Type::Abstraction(ref from, ref to) => format!("(-> {} {})", type_to_sexp(from), type_to_sexp(to))
+  }
+}
+
+#[test]
+fn sexp_includes_types_and_spans(){
+  use lexer::Lexer;
+  use parser::Parser;
+
+  let term = Parser::new(Lexer::new("\\x. f 2")).parse_expr().unwrap();
+  assert_eq!(SexpVisitor.visit(&term),
+    "(abstraction x (application (variable f :type ? :span (4 5)) (int 2 :type ? :span (6 7)) :type ? :span (4 7)) :type ? :span (0 7))");
+}
@@ -0,0 +1,174 @@
+use lexer::{Lexer, Token, Span};
+
+use colored::*;
+
+/// What a piece of the input line is highlighted as.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Style{
+  Plain,
+  Command,
+  Keyword,
+  Identifier,
+  TypeName,
+  Integer,
+  Error,
+  SelectedParen,
+  Comment
+}
+
+/// Colors a line of REPL input using the tokens produced by the `Lexer`.
+/// `cursor` is a byte offset into `line`; a paren next to the cursor is
+/// highlighted along with its partner.
+pub fn highlight(line: &str, cursor: Option<usize>) -> String {
+  segments(line, cursor).into_iter()
+    .map(|(style, text)| match style {
+      Style::Plain => text.to_string(),
+      Style::Command => text.blue().bold().to_string(),
+      Style::Keyword => text.magenta().bold().to_string(),
+      Style::Identifier => text.cyan().to_string(),
+      Style::TypeName => text.bright_blue().to_string(),
+      Style::Integer => text.yellow().to_string(),
+      Style::Error => text.red().bold().to_string(),
+      Style::SelectedParen => text.bright_white().bold().on_blue().to_string(),
+      Style::Comment => text.dimmed().to_string()
+    })
+    .collect()
+}
+
+/// Splits `line` into consecutive pieces, each with how it is highlighted.
+fn segments(line: &str, cursor: Option<usize>) -> Vec<(Style, &str)> {
+  let (command, rest, offset) = split_command(line);
+  let tokens = tokenize(rest);
+  let parens = match_parens(&tokens);
+  let selected = cursor
+    .and_then(|at| selected_paren(&tokens, &parens, at.saturating_sub(offset)));
+
+  let mut output = vec!();
+  if !command.is_empty() {
+    output.push((Style::Command, command));
+  }
+
+  let mut last = 0;
+  for (index, &(ref token, span)) in tokens.iter().enumerate() {
+    output.push((Style::Plain, &rest[last..span.start]));
+    let style = match *token {
+      Token::Backslash | Token::Dot | Token::Assign | Token::Colon | Token::Arrow => Style::Keyword,
+      Token::LIdent(_) => Style::Identifier,
+      Token::UIdent(_) => Style::TypeName,
+      Token::Integer(_) => Style::Integer,
+      Token::Illegal => Style::Error,
+      Token::LParen | Token::RParen => {
+        if parens[index].is_none() {
+          Style::Error
+        } else if selected.iter().any(|&(a, b)| a == index || b == index) {
+          Style::SelectedParen
+        } else {
+          Style::Plain
+        }
+      },
+      Token::EOF => Style::Plain
+    };
+    output.push((style, &rest[span.start..span.end]));
+    last = span.end;
+  }
+  // Anything after the last token is whitespace or a `--` comment
+  let tail = &rest[last..];
+  match tail.find("--") {
+    Some(at) => {
+      output.push((Style::Plain, &tail[..at]));
+      output.push((Style::Comment, &tail[at..]));
+    },
+    None => output.push((Style::Plain, tail))
+  }
+  output
+}
+
+/// Splits a leading `:command` word off of the line so it can be colored
+/// on its own. Returns the command, the remaining input, and the byte
+/// offset at which the remaining input starts.
+fn split_command(line: &str) -> (&str, &str, usize) {
+  if line.starts_with(':') {
+    let end = line.find(' ').unwrap_or(line.len());
+    (&line[..end], &line[end..], end)
+  } else {
+    ("", line, 0)
+  }
+}
+
+fn tokenize(line: &str) -> Vec<(Token, Span)> {
+  let mut lexer = Lexer::new(line);
+  let mut tokens = vec!();
+  loop {
+    match lexer.next_spanned() {
+      (Token::EOF, _) => break,
+      spanned => tokens.push(spanned)
+    }
+  }
+  tokens
+}
+
+/// For each token, the index of its matching paren. Unmatched parens and
+/// non-paren tokens are mapped to `None`.
+fn match_parens(tokens: &[(Token, Span)]) -> Vec<Option<usize>> {
+  let mut matches = vec![None; tokens.len()];
+  let mut open = vec!();
+  for (index, (token, _)) in tokens.iter().enumerate() {
+    match *token {
+      Token::LParen => open.push(index),
+      Token::RParen => {
+        if let Some(partner) = open.pop() {
+          matches[index] = Some(partner);
+          matches[partner] = Some(index);
+        }
+      },
+      _ => ()
+    }
+  }
+  matches
+}
+
+/// The matched pair of parens touching the cursor, preferring the paren
+/// directly under the cursor over the one just before it.
+fn selected_paren(tokens: &[(Token, Span)], parens: &[Option<usize>], cursor: usize) -> Option<(usize, usize)> {
+  let touching = |at: usize| tokens.iter().position(|&(_, span)| span.start == at);
+  let under = touching(cursor);
+  let before = if cursor > 0 { touching(cursor - 1) } else { None };
+
+  under.into_iter().chain(before)
+    .filter_map(|index| parens[index].map(|partner| (index, partner)))
+    .next()
+}
+
+#[test]
+fn match_parens_test(){
+  let tokens = tokenize("(a (b) c");
+  let parens = match_parens(&tokens);
+  assert_eq!(parens, vec![None, None, Some(4), None, Some(2), None]);
+}
+
+#[test]
+fn selected_paren_test(){
+  let tokens = tokenize("(a b)");
+  let parens = match_parens(&tokens);
+  assert_eq!(selected_paren(&tokens, &parens, 0), Some((0, 3)));
+  assert_eq!(selected_paren(&tokens, &parens, 5), Some((3, 0)));
+  assert_eq!(selected_paren(&tokens, &parens, 2), None);
+}
+
+#[test]
+fn highlight_preserves_text(){
+  let input = ":t  \\x.  (x $ 12)  -- note";
+  let text = |cursor| segments(input, cursor).into_iter().map(|(_, text)| text).collect::<String>();
+  assert_eq!(text(Some(0)), input);
+  assert_eq!(text(None), input);
+}
+
+#[test]
+fn highlight_styles_tokens(){
+  let styled = segments("(x $", Some(0)).into_iter()
+    .filter(|&(_, text)| !text.is_empty())
+    .collect::<Vec<_>>();
+  assert_eq!(styled, vec![
+    (Style::Error, "("), (Style::Identifier, "x"), (Style::Plain, " "), (Style::Error, "$")
+  ]);
+}
@@ -0,0 +1,130 @@
+use std::io;
+
+use console::{measure_text_width, Key, Term};
+
+use super::highlighter::highlight;
+
+/// A single line input that re-renders itself with syntax highlighting
+/// after every key press.
+pub struct LineEditor{
+  term: Term,
+  prompt: String,
+  history: Vec<String>,
+  rows_above: usize         // Rows of the last render above the one the cursor is on
+}
+
+impl LineEditor {
+  pub fn new(prompt_char: char) -> LineEditor {
+    LineEditor {
+      term: Term::stdout(),
+      prompt: format!("{} ", prompt_char),
+      history: vec!(),
+      rows_above: 0
+    }
+  }
+
+  /// Reads one line of input. Returns `None` when the user sends EOF
+  /// (Ctrl-D) on an empty line, or at the end of input that isn't a
+  /// terminal.
+  pub fn read_line(&mut self) -> io::Result<Option<String>> {
+    if !self.term.is_term() {
+      let mut line = String::new();
+      if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(None);
+      }
+      return Ok(Some(line.trim_end_matches(&['\n', '\r'][..]).to_string()));
+    }
+
+    let mut buffer: Vec<char> = vec!();
+    let mut cursor = 0;
+    let mut history_index = self.history.len();
+    self.rows_above = 0;
+
+    self.render(&buffer, Some(cursor))?;
+    loop {
+      match self.term.read_key()? {
+        Key::Enter => break,
+        Key::Char('\u{4}') if buffer.is_empty() => {
+          self.term.write_line("")?;
+          return Ok(None);
+        },
+        Key::Char('\u{3}') => {
+          self.render(&buffer, None)?;
+          self.term.write_line("")?;
+          self.rows_above = 0;
+          buffer.clear();
+          cursor = 0;
+        },
+        Key::Char(ch) if !ch.is_control() => {
+          buffer.insert(cursor, ch);
+          cursor += 1;
+        },
+        Key::Backspace if cursor > 0 => {
+          cursor -= 1;
+          buffer.remove(cursor);
+        },
+        Key::Del if cursor < buffer.len() => {
+          buffer.remove(cursor);
+        },
+        Key::ArrowLeft if cursor > 0 => cursor -= 1,
+        Key::ArrowRight if cursor < buffer.len() => cursor += 1,
+        Key::Home => cursor = 0,
+        Key::End => cursor = buffer.len(),
+        Key::ArrowUp if history_index > 0 => {
+          history_index -= 1;
+          buffer = self.history[history_index].chars().collect();
+          cursor = buffer.len();
+        },
+        Key::ArrowDown if history_index < self.history.len() => {
+          history_index += 1;
+          buffer = self.history.get(history_index)
+            .map_or(vec!(), |line| line.chars().collect());
+          cursor = buffer.len();
+        },
+        _ => continue
+      }
+      self.render(&buffer, Some(cursor))?;
+    }
+
+    // Redraw without the cursor so a paren match isn't left highlighted
+    self.render(&buffer, None)?;
+    self.term.write_line("")?;
+
+    let line: String = buffer.into_iter().collect();
+    if !line.trim().is_empty() && self.history.last() != Some(&line) {
+      self.history.push(line.clone());
+    }
+    Ok(Some(line))
+  }
+
+  /// Redraws the prompt and `buffer`, clearing every row the last render
+  /// wrapped onto, and leaves the terminal cursor at `cursor`, or at the
+  /// end of the line without one.
+  fn render(&mut self, buffer: &[char], cursor: Option<usize>) -> io::Result<()> {
+    let line: String = buffer.iter().collect();
+    let byte_cursor = cursor
+      .map(|at| buffer[..at].iter().map(|c| c.len_utf8()).sum());
+    let columns = (self.term.size().1 as usize).max(1);
+
+    self.term.move_cursor_up(self.rows_above)?;
+    self.term.clear_to_end_of_screen()?;
+    self.term.write_str(&self.prompt)?;
+    self.term.write_str(&highlight(&line, byte_cursor))?;
+
+    // Widths in columns, so wide characters take two
+    let end = measure_text_width(&self.prompt) + measure_text_width(&line);
+    if end > 0 && end.is_multiple_of(columns) {
+      // The terminal waits for another character before wrapping
+      self.term.write_str("\r\n")?;
+    }
+    let at = match byte_cursor {
+      Some(byte) => measure_text_width(&self.prompt) + measure_text_width(&line[..byte]),
+      None => end
+    };
+    self.term.move_cursor_up(end / columns - at / columns)?;
+    self.term.write_str("\r")?;
+    self.term.move_cursor_right(at % columns)?;
+    self.rows_above = at / columns;
+    self.term.flush()
+  }
+}
@@ -1,5 +1,9 @@
 pub mod repl;
 mod prompt;
 mod printer;
+mod highlighter;
+mod line_editor;
+mod options;
+mod rc;
 
-pub use self::repl::start;
\ No newline at end of file
+pub use self::repl::{start, eval, run};
\ No newline at end of file
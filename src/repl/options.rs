@@ -0,0 +1,278 @@
+use std::fmt;
+
+use eval::Strategy;
+use machine::Evaluator;
+use parser::AstFormat;
+use errors::suggest::closest_match;
+
+#[derive(Clone)]
+pub struct Options{
+  pub show_ast: bool,
+  pub ast_format: Option<AstFormat>,    // None prints the indented tree
+  pub show_steps: bool,
+  pub show_type_derivation: bool,
+  pub check_unbound: bool,
+  pub emit_llvm_ir: bool,
+  pub evaluator: Evaluator,
+  pub strategy: Strategy,
+  pub eta: bool,
+  pub fuel: usize,
+  pub width: usize
+}
+
+impl Default for Options{
+  fn default() -> Options {
+    Options {
+      show_ast: false,
+      ast_format: None,
+      show_steps: false,
+      show_type_derivation: false,
+      check_unbound: true,
+      emit_llvm_ir: false,
+      evaluator: Evaluator::default(),
+      strategy: Strategy::default(),
+      eta: false,
+      fuel: 10000,
+      width: 80
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum OptionValue{
+  Flag(bool),
+  Number(usize),
+  Choice(&'static str)
+}
+
+pub enum OptionKind{
+  Flag,
+  Number,
+  Choice(fn() -> Vec<&'static str>)
+}
+
+/// Describes a single REPL option. Every entry in `OPTIONS` can be changed
+/// with `:set`, and flags also show up in the `:options` menu.
+pub struct OptionSpec{
+  pub name: &'static str,
+  pub help: &'static str,
+  pub kind: OptionKind,
+  get: fn(&Options) -> OptionValue,
+  set: fn(&mut Options, OptionValue)
+}
+
+pub static OPTIONS: &[OptionSpec] = &[
+  OptionSpec {
+    name: "show_ast",
+    help: "Print the syntax tree of each input",
+    kind: OptionKind::Flag,
+    get: |o| OptionValue::Flag(o.show_ast),
+    set: |o, v| if let OptionValue::Flag(b) = v { o.show_ast = b }
+  },
+  OptionSpec {
+    name: "ast_format",
+    help: "How show_ast prints the syntax tree",
+    kind: OptionKind::Choice(ast_format_names),
+    get: |o| OptionValue::Choice(o.ast_format.map_or("tree", |f| f.name())),
+    set: |o, v| if let OptionValue::Choice(name) = v { o.ast_format = AstFormat::from_name(name) }
+  },
+  OptionSpec {
+    name: "show_steps",
+    help: "Print how many reduction steps each evaluation took",
+    kind: OptionKind::Flag,
+    get: |o| OptionValue::Flag(o.show_steps),
+    set: |o, v| if let OptionValue::Flag(b) = v { o.show_steps = b }
+  },
+  OptionSpec {
+    name: "show_type_derivation",
+    help: "Print the type derivation of each input",
+    kind: OptionKind::Flag,
+    get: |o| OptionValue::Flag(o.show_type_derivation),
+    set: |o, v| if let OptionValue::Flag(b) = v { o.show_type_derivation = b }
+  },
+  OptionSpec {
+    name: "check_unbound",
+    help: "Reject input that uses names that aren't bound or defined",
+    kind: OptionKind::Flag,
+    get: |o| OptionValue::Flag(o.check_unbound),
+    set: |o, v| if let OptionValue::Flag(b) = v { o.check_unbound = b }
+  },
+  OptionSpec {
+    name: "emit_llvm_ir",
+    help: "Print the LLVM IR generated for each input",
+    kind: OptionKind::Flag,
+    get: |o| OptionValue::Flag(o.emit_llvm_ir),
+    set: |o, v| if let OptionValue::Flag(b) = v { o.emit_llvm_ir = b }
+  },
+  OptionSpec {
+    name: "evaluator",
+    help: "Evaluator that runs each input",
+    kind: OptionKind::Choice(evaluator_names),
+    get: |o| OptionValue::Choice(o.evaluator.name()),
+    set: |o, v| if let OptionValue::Choice(name) = v {
+      o.evaluator = Evaluator::from_name(name).unwrap_or_default()
+    }
+  },
+  OptionSpec {
+    name: "strategy",
+    help: "Order in which redexes are reduced",
+    kind: OptionKind::Choice(strategy_names),
+    get: |o| OptionValue::Choice(o.strategy.name()),
+    set: |o, v| if let OptionValue::Choice(name) = v {
+      o.strategy = Strategy::from_name(name).unwrap_or_default()
+    }
+  },
+  OptionSpec {
+    name: "eta",
+    help: "Also eta reduce, so normal forms are beta-eta normal",
+    kind: OptionKind::Flag,
+    get: |o| OptionValue::Flag(o.eta),
+    set: |o, v| if let OptionValue::Flag(b) = v { o.eta = b }
+  },
+  OptionSpec {
+    name: "fuel",
+    help: "Maximum number of reduction steps per evaluation",
+    kind: OptionKind::Number,
+    get: |o| OptionValue::Number(o.fuel),
+    set: |o, v| if let OptionValue::Number(n) = v { o.fuel = n }
+  },
+  OptionSpec {
+    name: "width",
+    help: "Column at which printed terms are wrapped",
+    kind: OptionKind::Number,
+    get: |o| OptionValue::Number(o.width),
+    set: |o, v| if let OptionValue::Number(n) = v { o.width = n }
+  },
+];
+
+fn strategy_names() -> Vec<&'static str> {
+  Strategy::all().iter().map(|s| s.name()).collect()
+}
+
+fn evaluator_names() -> Vec<&'static str> {
+  Evaluator::all().iter().map(|e| e.name()).collect()
+}
+
+fn ast_format_names() -> Vec<&'static str> {
+  let mut names = vec!("tree");
+  names.extend(AstFormat::all().iter().map(|f| f.name()));
+  names
+}
+
+pub fn find(name: &str) -> Result<&'static OptionSpec, String> {
+  OPTIONS.iter().find(|o| o.name == name).ok_or_else(|| {
+    let suggestion = closest_match(name, OPTIONS.iter().map(|o| o.name))
+      .map_or(String::new(), |s| format!(" Did you mean '{}'?", s));
+    format!("unknown option '{}'.{}", name, suggestion)
+  })
+}
+
+impl OptionSpec{
+  pub fn get(&self, options: &Options) -> OptionValue {
+    (self.get)(options)
+  }
+
+  pub fn apply(&self, options: &mut Options, value: OptionValue){
+    (self.set)(options, value)
+  }
+
+  /// Parses `text` according to this option's kind and applies it.
+  pub fn set(&self, options: &mut Options, text: &str) -> Result<(), String> {
+    let value = self.parse(text)?;
+    self.apply(options, value);
+    Ok(())
+  }
+
+  /// Turns a flag off, or returns any other option to its default value.
+  pub fn unset(&self, options: &mut Options){
+    let value = match self.kind {
+      OptionKind::Flag => OptionValue::Flag(false),
+      _ => self.get(&Options::default())
+    };
+    self.apply(options, value);
+  }
+
+  /// The values accepted by `:set`, for help text.
+  pub fn accepts(&self) -> String {
+    match self.kind {
+      OptionKind::Flag => "on|off".to_string(),
+      OptionKind::Number => "<number>".to_string(),
+      OptionKind::Choice(choices) => choices().join("|")
+    }
+  }
+
+  fn parse(&self, text: &str) -> Result<OptionValue, String> {
+    match self.kind {
+      OptionKind::Flag => match text {
+        "on" | "true" | "yes" | "1" => Ok(OptionValue::Flag(true)),
+        "off" | "false" | "no" | "0" => Ok(OptionValue::Flag(false)),
+        _ => Err(self.invalid(text))
+      },
+      OptionKind::Number => text.parse()
+        .map(OptionValue::Number)
+        .map_err(|_| self.invalid(text)),
+      OptionKind::Choice(choices) => {
+        let choices = choices();
+        match choices.iter().find(|&&c| c == text) {
+          Some(choice) => Ok(OptionValue::Choice(choice)),
+          None => Err(match closest_match(text, choices) {
+            Some(s) => format!("{} Did you mean '{}'?", self.invalid(text), s),
+            None => self.invalid(text)
+          })
+        }
+      }
+    }
+  }
+
+  fn invalid(&self, text: &str) -> String {
+    format!("invalid value '{}' for {}, expected {}.", text, self.name, self.accepts())
+  }
+}
+
+impl fmt::Display for OptionValue{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      OptionValue::Flag(true) => write!(f, "on"),
+      OptionValue::Flag(false) => write!(f, "off"),
+      OptionValue::Number(n) => write!(f, "{}", n),
+      OptionValue::Choice(c) => write!(f, "{}", c)
+    }
+  }
+}
+
+#[test]
+fn set_typed_values(){
+  let mut options = Options::default();
+  find("show_ast").unwrap().set(&mut options, "on").unwrap();
+  find("strategy").unwrap().set(&mut options, "cbv").unwrap();
+  find("fuel").unwrap().set(&mut options, "42").unwrap();
+  assert!(options.show_ast);
+  assert_eq!(options.strategy, Strategy::CallByValue);
+  assert_eq!(options.fuel, 42);
+
+  find("ast_format").unwrap().set(&mut options, "sexp").unwrap();
+  assert_eq!(options.ast_format, Some(AstFormat::Sexp));
+  find("ast_format").unwrap().set(&mut options, "tree").unwrap();
+  assert_eq!(options.ast_format, None);
+  assert!(find("ast_format").unwrap().set(&mut options, "dot").is_ok());
+}
+
+#[test]
+fn set_rejects_invalid_values(){
+  let mut options = Options::default();
+  assert!(find("show_ast").unwrap().set(&mut options, "maybe").is_err());
+  assert!(find("fuel").unwrap().set(&mut options, "-1").is_err());
+  assert_eq!(
+    find("strategy").unwrap().set(&mut options, "norml"),
+    Err("invalid value 'norml' for strategy, expected normal|applicative|cbn|cbv. Did you mean 'normal'?".to_string()));
+  assert_eq!(find("fule").err(), Some("unknown option 'fule'. Did you mean 'fuel'?".to_string()));
+}
+
+#[test]
+fn unset_resets_value(){
+  let mut options = Options { show_ast: true, fuel: 5, ..Options::default() };
+  find("show_ast").unwrap().unset(&mut options);
+  find("fuel").unwrap().unset(&mut options);
+  assert!(!options.show_ast);
+  assert_eq!(options.fuel, Options::default().fuel);
+}
@@ -1,17 +1,32 @@
-use dialoguer::{theme::CustomPromptCharacterTheme, Input};
+use std::borrow::Cow;
+
+use colored::*;
+
+use super::line_editor::LineEditor;
+use errors::suggest::closest_match;
+
 pub enum PromptResult {
   Command(String, Option<String>),
   Input(String),
-  InvalidCommand(String)
+  InvalidCommand(String, Option<String>)      // Carries command, suggestion
 }
 
 pub struct Prompt<'a>{
-  options: Vec<PromptOption<'a>>
+  options: Vec<PromptOption<'a>>,
+  aliases: Vec<(String, String)>,
+  editor: LineEditor
 }
 
 impl<'a> Prompt<'a>{
   pub fn new() -> Self {
-    Prompt{options: vec!()}
+    let help = PromptOption::with_name("help")
+      .short("h")
+      .help("Lists the available commands, or shows usage for one")
+      .usage(":help [command]\n\n\
+        With no argument, lists every command along with its short alias.\n\
+        Given a command name, with or without the leading ':', shows its\n\
+        detailed usage.");
+    Prompt{options: vec!(help), aliases: vec!(), editor: LineEditor::new('>')}
   }
 
   pub fn option(mut self, option: PromptOption<'a>) -> Self {
@@ -19,12 +34,15 @@ impl<'a> Prompt<'a>{
     self
   }
 
-  pub fn show(&self) -> PromptResult {
-    let theme = CustomPromptCharacterTheme::new('>');
-    let input: String = Input::with_theme(&theme)
-      .interact()
-      .unwrap();
+  pub fn show(&mut self) -> PromptResult {
+    match self.editor.read_line().unwrap() {
+      Some(input) => self.read(&input),
+      None => PromptResult::Command("QUIT".to_string(), None)
+    }
+  }
 
+  /// Interprets a line of input the same way `show` would.
+  pub fn read(&self, input: &str) -> PromptResult {
     let input = input.trim().to_string();
 
     if input.starts_with(":") {
@@ -34,43 +52,133 @@ impl<'a> Prompt<'a>{
     }
   }
 
-  pub fn show_help(&self){
+  /// Registers `name` as a shorthand for `expansion`, which must start
+  /// with the name of a registered command. Any arguments given to the
+  /// alias are appended to the expansion.
+  pub fn alias(&mut self, name: &str, expansion: &str) -> Result<(), String> {
+    let name = name.trim_start_matches(':');
+    let expansion = expansion.trim().trim_start_matches(':');
+    let target = expansion.split(' ').next().unwrap_or("");
+
+    if name.is_empty() || name.contains(char::is_whitespace) {
+      return Err(format!("invalid alias name '{}'.", name));
+    }
+    if self.find_option(name).is_some() {
+      return Err(format!("':{}' is already a command.", name));
+    }
+    if self.find_option(target).is_none() {
+      return Err(format!("can't alias unknown command ':{}'.", target));
+    }
+
+    self.aliases.retain(|(n, _)| n != name);
+    self.aliases.push((name.to_string(), expansion.to_string()));
+    Ok(())
+  }
 
+  pub fn show_aliases(&self){
+    for (name, expansion) in &self.aliases {
+      println!("  {} = :{}", format!(":{}", name).blue().bold(), expansion);
+    }
+  }
+
+  /// Prints every registered command, or the detailed usage of `command`
+  /// when one is given.
+  pub fn show_help(&self, command: Option<&str>){
+    match command.map(str::trim).filter(|c| !c.is_empty()) {
+      None => {
+        let width = self.options.iter().map(|o| o.signature().len()).max().unwrap_or(0);
+        println!("Commands:");
+        for option in &self.options {
+          println!("  {}{}  {}",
+            option.signature().blue().bold(),
+            " ".repeat(width - option.signature().len()),
+            option.help.unwrap_or(""));
+        }
+        if !self.aliases.is_empty() {
+          println!("Aliases:");
+          self.show_aliases();
+        }
+        println!("Type ':help <command>' for more information on a command.");
+      },
+      Some(name) => match self.find_option(name.trim_start_matches(':')) {
+        Some(option) => {
+          println!("{}", option.signature().blue().bold());
+          if let Some(help) = option.help {
+            println!("  {}", help);
+          }
+          if let Some(ref usage) = option.usage {
+            println!();
+            for line in usage.lines() {
+              println!("{}", format!("  {}", line).trim_end());
+            }
+          }
+        },
+        None => {
+          let name = name.trim_start_matches(':');
+          match self.aliases.iter().find(|(n, _)| n == name) {
+            Some((_, expansion)) => println!("{} is an alias for :{}",
+              format!(":{}", name).blue().bold(), expansion),
+            None => eprintln!("{} {}", "Error:".red().bold(), self.invalid_message(name, self.suggest(name)))
+          }
+        }
+      }
+    }
+  }
+
+  /// Describes an unknown command, along with a close match if there is one.
+  pub fn invalid_message(&self, command: &str, suggestion: Option<&str>) -> String {
+    match suggestion {
+      Some(s) => format!("unknown command ':{}'. Did you mean ':{}'?", command, s),
+      None => format!("unknown command ':{}'. Type ':help' for a list of commands.", command)
+    }
+  }
+
+  fn find_option(&self, name: &str) -> Option<&PromptOption<'a>> {
+    self.options.iter().find(
+      |x| x.name == name || x.short_name == Some(name))
+  }
+
+  fn suggest<'b>(&'b self, command: &str) -> Option<&'b str> {
+    let aliases = self.aliases.iter().map(|(name, _)| name.as_str());
+    closest_match(command, self.options.iter().map(|o| o.name).chain(aliases))
   }
 
   fn handle_command(&self, input: String) -> PromptResult {
     let command_parts = &input[1..].split(" ").collect::<Vec<_>>();
     let command_string = command_parts[0].to_string();
 
-    match &*command_string {
-      "h" | "help" => PromptResult::Command("HELP".to_string(), None),
-      _ => {
-        let option = self.options.iter().find(
-          |x| x.name == command_string || x.short_name == Some(&command_string));
-        match option {
-          Some(command) => PromptResult::Command(command.name.to_uppercase(), Some(command_parts[1..].join(" "))),
-          None => self.handle_invalid(command_string)
-        }
-      }
+    let alias = self.aliases.iter().find(|(name, _)| *name == command_string);
+    match (self.find_option(&command_string), alias) {
+      (Some(command), _) => PromptResult::Command(command.name.to_uppercase(), Some(command_parts[1..].join(" "))),
+      (None, Some((_, expansion))) =>
+        self.handle_command(format!(":{} {}", expansion, command_parts[1..].join(" "))),
+      (None, None) => self.handle_invalid(command_string)
     }
   }
 
 
   fn handle_invalid(&self, command: String) -> PromptResult{
-
-    PromptResult::InvalidCommand(command)
+    let suggestion = self.suggest(&command).map(str::to_string);
+    PromptResult::InvalidCommand(command, suggestion)
   }
 }
 
 pub struct PromptOption<'a> {
   name: &'a str,
   help: Option<&'a str>,
+  usage: Option<Cow<'a, str>>,
   short_name: Option<&'a str>,
 }
 
 impl<'a> PromptOption<'a> {
   pub fn with_name(name: &str) -> PromptOption{
-    PromptOption { name, help: None, short_name: None}
+    PromptOption { name, help: None, usage: None, short_name: None}
+  }
+
+  /// Detailed usage shown by `:help <command>`.
+  pub fn usage<S: Into<Cow<'a, str>>>(mut self, usage: S) -> Self{
+    self.usage = Some(usage.into());
+    self
   }
 
   pub fn help(mut self, help: &'a str) -> Self{
@@ -82,4 +190,11 @@ impl<'a> PromptOption<'a> {
     self.short_name = Some(short_name);
     self
   }
+
+  fn signature(&self) -> String{
+    match self.short_name {
+      Some(short) => format!(":{}, :{}", self.name, short),
+      None => format!(":{}", self.name)
+    }
+  }
 }
@@ -0,0 +1,40 @@
+use std::env;
+use std::path::PathBuf;
+
+static RC_NAME: &str = ".lambdarc";
+
+/// Startup files to run, in order: the global `~/.lambdarc` followed by a
+/// project local `.lambdarc` in the current directory. Running the local
+/// file second lets its settings override the global ones.
+pub fn rc_files() -> Vec<PathBuf> {
+  let home = env::var_os("HOME")
+    .or_else(|| env::var_os("USERPROFILE"))
+    .map(|home| PathBuf::from(home).join(RC_NAME));
+  let local = env::current_dir().ok().map(|dir| dir.join(RC_NAME));
+
+  let mut files: Vec<PathBuf> = home.into_iter().filter(|p| p.is_file()).collect();
+  if let Some(local) = local.filter(|p| p.is_file()) {
+    let same_file = files.iter().any(|p| p.canonicalize().ok() == local.canonicalize().ok());
+    if !same_file {
+      files.push(local);
+    }
+  }
+  files
+}
+
+/// The lines of a startup file that should be run, with their 1-based
+/// line numbers, skipping blank lines and `--` comments.
+pub fn script_lines(contents: &str) -> Vec<(usize, &str)> {
+  contents.lines()
+    .map(str::trim)
+    .enumerate()
+    .filter(|&(_, line)| !line.is_empty() && !line.starts_with("--"))
+    .map(|(index, line)| (index + 1, line))
+    .collect()
+}
+
+#[test]
+fn script_lines_test(){
+  let contents = "-- course settings\n:set fuel 500\n\n  id = \\x. x  \n:load prelude.lc\n";
+  assert_eq!(script_lines(contents), vec![(2, ":set fuel 500"), (4, "id = \\x. x"), (5, ":load prelude.lc")]);
+}
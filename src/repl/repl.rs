@@ -1,51 +1,352 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use atty;
 use dialoguer::{theme::ColorfulTheme, Checkboxes};
+use colored::*;
 
 use parser::Parser;
+use parser::ParseNode;
+use parser::GrammarItem;
 use lexer::Lexer;
 use parser::Visitor;
+use parser::PrettyPrinter;
+use parser::export;
+use eval::{DbTerm, Environment, Equivalence, Primitive, Reducer, Reduction, ReductionGraph, equivalence, eta_reduce, eta_expand};
+use eval::nbe;
+use analysis::unbound_names;
+use combinators::{self, Algorithm, CombinatorReducer};
+use ir::{anf, cps};
+use machine::{Evaluator, Krivine, cek, lazy};
+use machine::lazy::Sharing;
+use parser::dot::DotVisitor;
 use super::printer::PrintVisitor;
 
+use super::rc;
+use super::options::{self, Options, OptionKind, OptionValue, OPTIONS};
 use super::prompt::{PromptOption, Prompt, PromptResult};
+use errors::error_index::Error;
 use errors::error_index::Error::UnexpectedEOF;
 
+type CommandResult = Result<(), String>;
+
+/// Starts an interactive session. When stdin isn't a terminal its lines
+/// are run as a script instead, and the result is whether every line
+/// succeeded.
+pub fn start(load_rc: bool) -> bool {
+  if !atty::is(atty::Stream::Stdin) {
+    let mut script = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut script) {
+      eprintln!("Error: can't read stdin: {}", e);
+      return false;
+    }
+    let mut repl = Repl::new(false, load_rc);
+    repl.run_lines(&script, None);
+    return !repl.failed;
+  }
+
+  let mut repl = Repl::new(true, load_rc);
+  loop {
+    let result = repl.prompt.show();
+    if !repl.handle(result) {
+      break;
+    }
+  }
+  true
+}
+
+/// Evaluates each expression or command in turn, printing the results.
+pub fn eval(inputs: &[&str], load_rc: bool) -> bool {
+  let mut repl = Repl::new(false, load_rc);
+  for input in inputs {
+    let result = repl.prompt.read(input);
+    if !repl.handle(result) {
+      break;
+    }
+  }
+  !repl.failed
+}
 
-#[derive(Default, Clone)]
-struct Options{
-  show_ast: bool,
-  show_type_derivation: bool,
-  emit_llvm_ir: bool
+/// Loads the definitions in `path` and prints the normal form of `main`.
+pub fn run(path: &str, load_rc: bool) -> bool {
+  let mut repl = Repl::new(false, load_rc);
+  let result = repl.load(path).and_then(|()| {
+    let main = repl.env.get("main").cloned()
+      .ok_or_else(|| format!("{} has no 'main' definition.", path))?;
+    evaluate(&main, &repl.options, &repl.env)
+  });
+  repl.report(result);
+  !repl.failed
 }
 
-pub fn start(){
-  main_loop();
+/// State of a REPL session. Lines typed at the prompt, lines of a script
+/// and lines read from startup files are all run through `handle`.
+struct Repl{
+  prompt: Prompt<'static>,
+  options: Options,
+  env: Environment,
+  base_dir: PathBuf,        // Relative `:load` paths are resolved from here
+  interactive: bool,
+  quiet: bool,              // Suppresses AST output while running rc files
+  rc_line: Option<String>,  // 'path:line' of the startup file line being run
+  failed: bool              // Set once any line outside startup files reports an error
 }
 
-fn main_loop() {
-  let prompt = Prompt::new()
+impl Repl{
+  fn new(interactive: bool, load_rc: bool) -> Repl {
+    if !interactive {
+      colored::control::set_override(false);
+    }
+
+    let mut repl = Repl {
+      prompt: build_prompt(),
+      options: Options { show_ast: interactive, show_steps: interactive, ..Options::default() },
+      env: Environment::new(),
+      base_dir: PathBuf::from("."),
+      interactive,
+      quiet: false,
+      rc_line: None,
+      failed: false
+    };
+
+    if load_rc {
+      for path in rc::rc_files() {
+        repl.run_file(&path);
+      }
+    }
+    repl
+  }
+
+  /// Runs a startup file line by line, as if each line had been typed at
+  /// the prompt. Its errors are only warnings, so that a stale line doesn't
+  /// fail every `eval` and `run`.
+  fn run_file(&mut self, path: &Path){
+    let contents = match fs::read_to_string(path) {
+      Ok(contents) => contents,
+      Err(e) => return warn(&path.display().to_string(), &format!("can't read it: {}", e))
+    };
+
+    let base_dir = mem::replace(&mut self.base_dir,
+      path.parent().map_or(PathBuf::from("."), Path::to_path_buf));
+    self.quiet = true;
+    self.run_lines(&contents, Some(path));
+    self.quiet = false;
+    self.base_dir = base_dir;
+  }
+
+  /// Runs each line of a script, or of the startup file `rc`. Blank lines
+  /// and `--` comments are skipped.
+  fn run_lines(&mut self, contents: &str, rc: Option<&Path>){
+    for (number, line) in rc::script_lines(contents) {
+      self.rc_line = rc.map(|path| format!("{}:{}", path.display(), number));
+      let result = self.prompt.read(line);
+      if !self.handle(result) {
+        break;
+      }
+    }
+    self.rc_line = None;
+  }
+
+  /// Acts on one line of input. Returns false once the session should end.
+  fn handle(&mut self, result: PromptResult) -> bool {
+    let outcome = match result {
+      PromptResult::Input(ref expr) if expr.is_empty() => Ok(()),
+      PromptResult::Input(expr) => {
+        let options = Options { show_ast: self.options.show_ast && !self.quiet, ..self.options.clone() };
+        handle_expr(expr, &options, &mut self.env)
+      },
+      PromptResult::Command(ref c, _) if c == "QUIT" => return false,
+      PromptResult::Command(ref c, ref rest) if c == "HELP" => {
+        self.prompt.show_help(rest.as_deref());
+        Ok(())
+      },
+      PromptResult::Command(ref c, ref rest) if c == "ALIAS" => self.alias(rest.as_deref().unwrap_or("")),
+      PromptResult::Command(ref c, ref rest) if c == "LOAD" => self.load(rest.as_deref().unwrap_or("")),
+      PromptResult::Command(ref c, _) if c == "OPTIONS" && !self.interactive =>
+        Err("the options menu needs a terminal, use ':set' instead.".to_string()),
+      PromptResult::Command(c, rest) => handle_command(c, rest, &mut self.options, &self.env),
+      PromptResult::InvalidCommand(command, suggestion) =>
+        Err(self.prompt.invalid_message(&command, suggestion.as_deref()))
+    };
+    self.report(outcome);
+    true
+  }
+
+  fn report(&mut self, result: CommandResult){
+    match (result, self.rc_line.as_ref()) {
+      (Ok(()), _) => (),
+      (Err(e), Some(at)) => warn(at, &e),
+      (Err(e), None) => {
+        eprintln!("{} {}", "Error:".red().bold(), e);
+        self.failed = true;
+      }
+    }
+  }
+
+  fn alias(&mut self, args: &str) -> CommandResult {
+    let mut parts = args.trim().splitn(2, ' ');
+    match (parts.next(), parts.next()) {
+      (Some(""), None) => {
+        self.prompt.show_aliases();
+        Ok(())
+      },
+      (Some(name), Some(expansion)) => self.prompt.alias(name, expansion),
+      _ => Err("expected ':alias <name> <command...>'.".to_string())
+    }
+  }
+
+  fn load(&mut self, path: &str) -> CommandResult {
+    let path = self.base_dir.join(path.trim());
+    let program = export::read_program(&path)?;
+
+    if self.options.check_unbound {
+      // Trees read from JSON carry the spans of a source that isn't here
+      let source = match path.extension() {
+        Some(ext) if ext == "json" => None,
+        _ => fs::read_to_string(&path).ok()
+      };
+      check_unbound(&program, &self.env, source.as_deref(), &format!("{}:", path.display()))?;
+    }
+    self.env.load(&program);
+    if self.interactive && !self.quiet {
+      if let GrammarItem::Program(ref definitions) = program.entry {
+        println!("Loaded {} definitions from {}", definitions.len(), path.display());
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Prints a problem in a startup file, `at` its path and line.
+fn warn(at: &str, message: &str){
+  eprintln!("{} {}: {}", "Warning:".yellow().bold(), at, message);
+}
+
+fn build_prompt() -> Prompt<'static> {
+  let set_usage = format!(":set [option [value]]\n\n\
+    Changes an option of the REPL environment. With only an option name,\n\
+    shows its current value. With no arguments, shows every option.\n\n{}",
+    describe_options());
+
+  Prompt::new()
     .option(PromptOption::with_name("type")
       .short("t")
-      .help("Displays the type of the expression provided "))
+      .help("Displays the type of an expression (not supported yet)")
+      .usage(":type <expr>\n\n\
+        Will show the inferred type of the expression. Type inference isn't\n\
+        implemented yet, so for now this only reports that."))
+    .option(PromptOption::with_name("load")
+      .short("l")
+      .help("Loads the definitions in a source file")
+      .usage(":load <file>\n\n\
+        Parses the file as a program and adds each of its definitions to\n\
+        the environment, replacing any with the same name."))
+    .option(PromptOption::with_name("set")
+      .short("s")
+      .help("Sets an option of the REPL environment")
+      .usage(set_usage))
+    .option(PromptOption::with_name("unset")
+      .short("u")
+      .help("Turns a flag off, or resets an option to its default")
+      .usage(":unset <option>\n\n\
+        Flags are turned off. Any other option returns to its default value."))
+    .option(PromptOption::with_name("show")
+      .help("Shows the REPL's options or bindings")
+      .usage(":show options|bindings\n\n\
+        'options' lists every option with its current value.\n\
+        'bindings' lists every definition made so far."))
+    .option(PromptOption::with_name("alias")
+      .help("Defines a shorthand for a command")
+      .usage(":alias [name command...]\n\n\
+        Makes ':name' run ':command...', with any extra arguments appended.\n\
+        For example ':alias cbv set strategy cbv'. With no arguments, lists\n\
+        the aliases defined so far."))
+    .option(PromptOption::with_name("debruijn")
+      .short("d")
+      .help("Shows an expression with De Bruijn indices")
+      .usage(":debruijn <expr>\n\n\
+        Replaces each bound variable with the number of abstractions between\n\
+        it and its binder, counting from 0, and drops binder names. Names\n\
+        that aren't bound are listed as free variables."))
+    .option(PromptOption::with_name("eta_reduce")
+      .help("Eta reduces an expression")
+      .usage(":eta_reduce <expr>\n\n\
+        Rewrites every '\\x. f x' where x isn't free in f to 'f', innermost\n\
+        first, without unfolding definitions or beta reducing. Use\n\
+        ':set eta on' to eta reduce while evaluating instead."))
+    .option(PromptOption::with_name("eta_expand")
+      .help("Eta expands an expression to fit a type")
+      .usage(":eta_expand <expr> : <type>\n\n\
+        Wraps the expression in abstractions until it takes one argument per\n\
+        arrow of the type, expanding each new argument by its own type.\n\
+        For example ':eta_expand f : (A -> B) -> C' gives '\\x. f \\y. x y'."))
+    .option(PromptOption::with_name("dot")
+      .help("Prints an expression as a Graphviz graph")
+      .usage(":dot [--reductions] <expr>\n\n\
+        Prints the syntax tree of the expression in DOT format, with an edge\n\
+        from each bound variable back to its binder. With --reductions,\n\
+        prints every term the expression reduces to under any choice of\n\
+        redex instead, with edges labelled by the position of the redex.\n\
+        At most 'fuel' terms are explored."))
+    .option(PromptOption::with_name("eq")
+      .help("Checks whether two expressions are equal")
+      .usage(":eq <expr> <expr>\n\n\
+        Decides whether the expressions are alpha-equivalent, and if not,\n\
+        whether they are beta-eta convertible by reducing both to normal\n\
+        form in normal order, within 'fuel' steps each. When they aren't,\n\
+        shows the first position at which the normal forms differ.\n\
+        Wrap each expression in parentheses unless it is a single name,\n\
+        for example ':eq (\\x. x) id'."))
+    .option(PromptOption::with_name("nf")
+      .help("Shows the normal form of an expression, found by evaluation")
+      .usage(":nf <expr>\n\n\
+        Normalizes the expression by evaluation: it is run as Rust closures,\n\
+        with arguments evaluated by need, and read back as a term, going\n\
+        under abstractions. Finds the same beta normal form as the normal\n\
+        strategy, much faster on large terms. Stops after 'fuel' steps."))
+    .option(PromptOption::with_name("ski")
+      .help("Compiles an expression to combinators")
+      .usage(":ski <expr>\n\n\
+        Removes every abstraction by bracket abstraction, once with only S,\n\
+        K and I, and once with Turner's rules, which add B, C, W, S' and eta\n\
+        reduction, and shows the size of each result. Then reduces Turner's\n\
+        translation, with definitions compiled the same way, within 'fuel'\n\
+        steps."))
+    .option(PromptOption::with_name("ir")
+      .help("Shows an expression in A-normal form and continuation passing style")
+      .usage(":ir <expr>\n\n\
+        Converts the expression to A-normal form, where the result of every\n\
+        application that isn't in tail position is named by a 'let', and to\n\
+        continuation passing style, where every call is passed what to do\n\
+        with its result. Then evaluates it call-by-value by interpreting\n\
+        each form, within 'fuel' steps, and checks that they agree."))
+    .option(PromptOption::with_name("machine")
+      .help("Runs an expression on the Krivine machine, showing each state")
+      .usage(":machine <expr>\n\n\
+        Evaluates the expression call-by-name to weak head normal form on\n\
+        Krivine's machine, printing every state as 'term | env | stack'.\n\
+        Terms use De Bruijn indices, which look up closures in the\n\
+        environment, innermost binder first. The stack holds arguments,\n\
+        next one first. A closure with an environment shows as\n\
+        '⟨term, env⟩'. Primitives aren't applied. Stops after 'fuel' steps."))
     .option(PromptOption::with_name("options")
       .short("o")
-      .help("Allows you to choose various options for the REPL environment"))
+      .help("Allows you to choose various options for the REPL environment")
+      .usage(":options\n\n\
+        Opens a menu of toggles for the REPL environment. Use the arrow\n\
+        keys to move, space to toggle and enter to confirm."))
     .option(PromptOption::with_name("quit")
       .short("q")
-      .help("Exits the REPL environment"));
-
-  let mut options = Options::default();
-  options.show_ast = true;
-
-  loop {
-    match prompt.show() {
-      PromptResult::Input(expr) => handle_expr(expr, &options),
-      PromptResult::Command(ref c, _) if *c == "QUIT".to_string() => break,
-      PromptResult::Command(c, rest) => handle_command(c, rest, &mut options),
-      PromptResult::InvalidCommand(command) => ()
-    }
-  }
+      .help("Exits the REPL environment")
+      .usage(":quit\n\n\
+        Exits the REPL. Ctrl-D on an empty line does the same."))
 }
 
-fn handle_expr(expr: String, options: &Options){
+fn handle_expr(expr: String, options: &Options, env: &mut Environment) -> CommandResult {
   let lexer = Lexer::new(expr.as_str());
   let mut parser = Parser::new(lexer);
   let mut printer = PrintVisitor::new();
@@ -62,31 +363,293 @@ fn handle_expr(expr: String, options: &Options){
 
   match result {
     Ok(ast) => {
+      if options.check_unbound {
+        check_unbound(&ast, env, Some(&expr), "")?;
+      }
       if options.show_ast {
-        printer.visit(&ast)
+        match options.ast_format {
+          Some(format) => println!("{}", format.export(&ast)),
+          None => printer.visit(&ast)
+        }
+      }
+      match ast.entry {
+        GrammarItem::Assignment(ref name, ref value) => {
+          env.define(name, (**value).clone());
+          Ok(())
+        },
+        _ => evaluate(&ast, options, env)
       }
     },
-    Err(e) => println!("Error parsing: {:?}",e)
+    Err(e) => Err(format!("can't parse input: {:?}", e))
   }
 }
 
-fn handle_command(command: String, rest: Option<String>, options: &mut Options){
+/// Fails with every use in `node` of a name that isn't bound by an
+/// abstraction, defined, or a primitive. Uses are located by line and
+/// column in `source`, prefixed with `origin`, or by byte offsets when the
+/// source is unknown.
+fn check_unbound(node: &ParseNode, env: &Environment, source: Option<&str>, origin: &str) -> CommandResult {
+  let mut globals: HashSet<String> = env.iter().map(|(name, _)| name.clone()).collect();
+  globals.extend(Primitive::all().iter().map(|p| p.name().to_string()));
+
+  let definitions = match node.entry {
+    GrammarItem::Program(ref definitions) => definitions.iter().collect(),
+    _ => vec!(node)
+  };
+  let errors = unbound_names(node, &globals).into_iter()
+    .filter_map(|error| match error {
+      Error::UnboundVariable(name, span, suggestion) => {
+        let location = match source {
+          Some(source) => {
+            let (line, column) = span.line_column(source);
+            format!("{}{}:{}", origin, line, column)
+          },
+          None => format!("{}{}..{}", origin, span.start, span.end)
+        };
+        let context = definitions.iter()
+          .find(|d| d.span.start <= span.start && span.end <= d.span.end)
+          .and_then(|d| match d.entry {
+            GrammarItem::Assignment(ref name, _) => Some(format!(" in '{}'", name)),
+            _ => None
+          })
+          .unwrap_or_default();
+        let suggestion = suggestion.map_or(String::new(), |s| format!(" Did you mean '{}'?", s));
+        Some(format!("{}: '{}' isn't defined{}.{}", location, name, context, suggestion))
+      },
+      _ => None
+    })
+    .collect::<Vec<_>>();
+  if errors.is_empty() { Ok(()) } else { Err(errors.join("\n")) }
+}
+
+/// Parses the argument of a command that expects a single expression.
+fn parse_term(text: &str) -> Result<ParseNode, String> {
+  let mut parser = Parser::new(Lexer::new(text));
+  parser.parse_expr()
+    .and_then(|term| if parser.is_empty() { Ok(term) } else { Err(UnexpectedEOF) })
+    .map_err(|e| format!("can't parse input: {:?}", e))
+}
+
+/// Parses the arguments of a command that compares two expressions. The
+/// first ends after a single name, integer, parenthesized expression or
+/// abstraction.
+fn parse_two_terms(text: &str) -> Result<(ParseNode, ParseNode), String> {
+  let mut parser = Parser::new(Lexer::new(text));
+  parser.parse_base_expr()
+    .and_then(|a| parser.parse_expr().map(|b| (a, b)))
+    .and_then(|terms| if parser.is_empty() { Ok(terms) } else { Err(UnexpectedEOF) })
+    .map_err(|e| format!("can't parse input: {:?}", e))
+}
+
+fn evaluate(ast: &ParseNode, options: &Options, env: &Environment) -> CommandResult {
+  let result = match options.evaluator {
+    Evaluator::Substitution => Reducer::new(env, options.strategy).with_eta(options.eta)
+      .normalize(ast, options.fuel).map(|r| (r, None)),
+    Evaluator::Cek => cek::evaluate(env, ast, options.fuel).map(|r| (r, None)),
+    Evaluator::Lazy => lazy::evaluate(env, ast, options.fuel).map(|(r, sharing)| (r, Some(sharing)))
+  };
+  let (reduction, sharing) = result.map_err(|e| evaluation_error(e, "evaluate"))?;
+  print_reduction(&reduction, sharing, options);
+  Ok(())
+}
+
+/// The message for an evaluation that failed, naming the `action` tried
+/// unless it ran out of fuel or looped.
+fn evaluation_error(error: Error, action: &str) -> String {
+  match error {
+    Error::OutOfFuel(fuel) => format!("no normal form within {} steps.", fuel),
+    Error::InfiniteLoop => "evaluation loops forever.".to_string(),
+    Error::TooDeep(depth) => format!("no normal form within {} levels of nesting.", depth),
+    e => format!("can't {}: {:?}", action, e)
+  }
+}
+
+/// How long an evaluation took, e.g. '1 step'.
+fn count_steps(steps: usize) -> String {
+  format!("{} step{}", steps, if steps == 1 { "" } else { "s" })
+}
+
+/// Prints the result of an evaluation, along with how long it took when
+/// 'show_steps' is on.
+fn print_reduction(reduction: &Reduction, sharing: Option<Sharing>, options: &Options){
+  let term = PrettyPrinter::new(options.width).visit(&reduction.term);
+  if options.show_steps {
+    let mut steps = count_steps(reduction.steps);
+    if let Some(sharing) = sharing {
+      steps = format!("{}, {}", steps, sharing);
+    }
+    println!("{} {}", term, format!("({})", steps).dimmed());
+  } else {
+    println!("{}", term);
+  }
+}
+
+fn handle_command(command: String, rest: Option<String>, options: &mut Options, env: &Environment) -> CommandResult {
+  let rest = rest.unwrap_or_default();
   match &*command {
-    "HELP" => println!("help"),
-    "TYPE" => println!("type"),
+    "TYPE" => return Err("type inference isn't supported yet.".to_string()),
+    "SET" => return set_option(rest.trim(), options),
+    "UNSET" => options::find(rest.trim())?.unset(options),
+    "SHOW" => match rest.trim() {
+      "options" => show_option_values(options),
+      "bindings" => {
+        for (name, value) in env.iter() {
+          println!("{} = {}", name.cyan(), value);
+        }
+      },
+      other => return Err(format!("can't show '{}', expected options or bindings.", other))
+    },
     "OPTIONS" => show_options(options),
+    "DEBRUIJN" => {
+      let term = DbTerm::from_node(&parse_term(&rest)?).map_err(|e| format!("{:?}", e))?;
+      println!("{}", term);
+      let free = term.free_variables();
+      if !free.is_empty() {
+        println!("{} {}", "free:".dimmed(), free.join(", "));
+      }
+    },
+    "EQ" => {
+      let (a, b) = parse_two_terms(&rest)?;
+      match equivalence(env, &a, &b, options.fuel) {
+        Ok(Equivalence::Alpha) => println!("alpha-equivalent"),
+        Ok(Equivalence::Convertible(normal)) =>
+          println!("beta-eta convertible, both reduce to {}", normal),
+        Ok(Equivalence::Different(position, left, right)) =>
+          println!("not equal, normal forms differ at {}: {} vs {}", position, left, right),
+        Err(Error::OutOfFuel(fuel)) =>
+          return Err(format!("can't decide, no normal form within {} steps.", fuel)),
+        Err(e) => return Err(format!("can't compare: {:?}", e))
+      }
+    },
+    "ETA_REDUCE" => println!("{}", PrettyPrinter::new(options.width).visit(&eta_reduce(&parse_term(&rest)?))),
+    "ETA_EXPAND" => {
+      let (expr, ty) = match rest.rfind(':') {
+        Some(at) => (&rest[..at], &rest[at + 1..]),
+        None => return Err("expected ':eta_expand <expr> : <type>'.".to_string())
+      };
+      let mut parser = Parser::new(Lexer::new(ty));
+      let ty = parser.parse_type_expr()
+        .and_then(|ty| if parser.is_empty() { Ok(ty) } else { Err(UnexpectedEOF) })
+        .map_err(|e| format!("can't parse type: {:?}", e))?;
+      println!("{}", PrettyPrinter::new(options.width).visit(&eta_expand(&parse_term(expr)?, &ty)));
+    },
+    "DOT" => {
+      let rest = rest.trim();
+      if let Some(expr) = rest.strip_prefix("--reductions") {
+        let term = parse_term(expr)?;
+        let graph = ReductionGraph::explore(&Reducer::new(env, options.strategy).with_eta(options.eta), &term, options.fuel);
+        println!("{}", graph.to_dot());
+        if !graph.complete {
+          eprintln!("{}", format!("stopped after {} terms, see ':set fuel'.", options.fuel).dimmed());
+        }
+      } else {
+        println!("{}", DotVisitor::render(&parse_term(rest)?));
+      }
+    },
+    "NF" => {
+      let reduction = nbe::normalize(env, &parse_term(&rest)?, options.fuel)
+        .map_err(|e| evaluation_error(e, "normalize"))?;
+      print_reduction(&reduction, None, options);
+    },
+    "SKI" => {
+      let term = parse_term(&rest)?;
+      let compile = |node: &ParseNode, algorithm| combinators::compile(node, algorithm).map_err(|e| format!("{:?}", e));
+      for &algorithm in Algorithm::all() {
+        let compiled = compile(&term, algorithm)?;
+        println!("{:<7} {} {}", algorithm.name().cyan(), compiled, format!("(size {})", compiled.size()).dimmed());
+      }
+      let definitions = env.iter()
+        .map(|(name, value)| Ok((name.clone(), compile(value, Algorithm::Turner)?)))
+        .collect::<Result<_, String>>()?;
+      let (normal, steps) = CombinatorReducer::new(&definitions)
+        .normalize(&compile(&term, Algorithm::Turner)?, options.fuel)
+        .map_err(|e| evaluation_error(e, "reduce"))?;
+      println!("{:<7} {} {}", "=".cyan(), normal, format!("({})", count_steps(steps)).dimmed());
+    },
+    "IR" => {
+      let term = parse_term(&rest)?;
+      let anf = anf::convert(&term).map_err(|e| format!("{:?}", e))?;
+      let cps = cps::convert(&term).map_err(|e| format!("{:?}", e))?;
+      // Each form is shown with its interpreter's value, so they can be compared
+      let show = |name: &str, ir: &dyn fmt::Display, result: Result<Reduction, Error>| {
+        println!("{:<4} {}", name.cyan(), ir);
+        match result {
+          Ok(Reduction { term, steps }) => {
+            println!("{:<4} {} {}", "=".cyan(), PrettyPrinter::new(options.width).visit(&term),
+              format!("({})", count_steps(steps)).dimmed());
+            Some(term)
+          },
+          Err(e) => {
+            println!("{:<4} {}", "=".cyan(), evaluation_error(e, "evaluate").red());
+            None
+          }
+        }
+      };
+      let anf_value = show("anf", &anf, anf::evaluate(env, &term, options.fuel));
+      let cps_value = show("cps", &cps, cps::evaluate(env, &term, options.fuel));
+      match (anf_value, cps_value) {
+        (Some(anf_value), Some(cps_value)) if anf_value != cps_value =>
+          return Err("the A-normal form and continuation passing interpreters disagree.".to_string()),
+        (Some(_), Some(_)) => (),
+        _ => return Err("evaluation failed.".to_string())
+      }
+    },
+    "MACHINE" => {
+      let mut machine = Krivine::new(env, &parse_term(&rest)?).map_err(|e| format!("{:?}", e))?;
+      let node = machine
+        .run_with(options.fuel, |state, steps| println!("{}  {}", format!("{:>3}", steps).dimmed(), state))
+        .map_err(|e| evaluation_error(e, "run"))?;
+      println!("{}", PrettyPrinter::new(options.width).visit(&node));
+    },
     _ => println!("Other")
   }
+  Ok(())
 }
 
-fn show_options(options: &mut Options){
-  let checkboxes = &[
-    ("Show AST", options.show_ast),
-    ("Show type derivation", options.show_type_derivation),
-    ("Emit LLVM Ir", options.emit_llvm_ir)
-  ];
+fn set_option(args: &str, options: &mut Options) -> CommandResult {
+  let mut parts = args.splitn(2, ' ');
+  let name = parts.next().unwrap_or("");
+  let value = parts.next().map(str::trim);
 
-  *options = Options::default();
+  if name.is_empty() {
+    show_option_values(options);
+    return Ok(());
+  }
+
+  let option = options::find(name)?;
+  match value {
+    Some(value) => option.set(options, value),
+    None => {
+      println!("{} = {}", option.name, option.get(options));
+      Ok(())
+    }
+  }
+}
+
+fn show_option_values(options: &Options){
+  let width = OPTIONS.iter().map(|o| o.name.len()).max().unwrap_or(0);
+  for option in OPTIONS {
+    let value = option.get(options).to_string();
+    println!("  {}{}  {:8}  {}", option.name.cyan(), " ".repeat(width - option.name.len()),
+      value, option.help.dimmed());
+  }
+}
+
+fn describe_options() -> String {
+  let width = OPTIONS.iter().map(|o| o.name.len()).max().unwrap_or(0);
+  OPTIONS.iter()
+    .map(|o| format!("{}{}  {} ({})", o.name, " ".repeat(width - o.name.len()), o.help, o.accepts()))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn show_options(options: &mut Options){
+  let flags: Vec<_> = OPTIONS.iter()
+    .filter(|o| matches!(o.kind, OptionKind::Flag))
+    .collect();
+  let checkboxes: Vec<(&str, bool)> = flags.iter()
+    .map(|o| (o.help, o.get(options) == OptionValue::Flag(true)))
+    .collect();
 
   let selections = Checkboxes::with_theme(&ColorfulTheme::default())
     .with_prompt("Options")
@@ -94,17 +657,7 @@ fn show_options(options: &mut Options){
     .interact()
     .unwrap();
 
-  {
-    let handlers: &[Box<Fn(&mut Options)>] = &[
-      Box::new(|ops| ops.show_ast = true),
-      Box::new(|ops| ops.show_type_derivation = true),
-      Box::new(|ops| ops.emit_llvm_ir = true)
-    ];
-
-    if !selections.is_empty() {
-      for selection in selections {
-        handlers[selection](options);
-      }
-    }
+  for (index, flag) in flags.iter().enumerate() {
+    flag.apply(options, OptionValue::Flag(selections.contains(&index)));
   }
 }